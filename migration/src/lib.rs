@@ -7,6 +7,15 @@ mod m20230304_000004_create_city_table;
 mod m20240408_000005_alter_profile_with_weight;
 mod m20240420_000006_alter_profile_with_view;
 mod m20240501_000007_create_comment_table;
+mod m20241001_000008_alter_comment_with_review;
+mod m20241002_000009_alter_user_with_role;
+mod m20241003_000010_create_refresh_token_table;
+mod m20241004_000011_create_session_table;
+mod m20241005_000012_alter_profilephoto_with_renditions;
+mod m20241006_000013_alter_profilephoto_with_position;
+mod m20241007_000014_create_job_table;
+mod m20241008_000015_alter_profilephoto_with_deleted_at;
+mod m20241009_000016_alter_user_with_status;
 
 pub struct Migrator;
 
@@ -21,6 +30,15 @@ impl MigratorTrait for Migrator {
             Box::new(m20240408_000005_alter_profile_with_weight::Migration),
             Box::new(m20240420_000006_alter_profile_with_view::Migration),
             Box::new(m20240501_000007_create_comment_table::Migration),
+            Box::new(m20241001_000008_alter_comment_with_review::Migration),
+            Box::new(m20241002_000009_alter_user_with_role::Migration),
+            Box::new(m20241003_000010_create_refresh_token_table::Migration),
+            Box::new(m20241004_000011_create_session_table::Migration),
+            Box::new(m20241005_000012_alter_profilephoto_with_renditions::Migration),
+            Box::new(m20241006_000013_alter_profilephoto_with_position::Migration),
+            Box::new(m20241007_000014_create_job_table::Migration),
+            Box::new(m20241008_000015_alter_profilephoto_with_deleted_at::Migration),
+            Box::new(m20241009_000016_alter_user_with_status::Migration),
         ]
     }
 }
@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Comment::Table)
+                    .add_column(ColumnDef::new(Comment::ReviewReason).string())
+                    .add_column(ColumnDef::new(Comment::ReviewedAt).timestamp())
+                    .add_column(ColumnDef::new(Comment::ReviewedBy).big_integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-comment-status")
+                    .table(Comment::Table)
+                    .col(Comment::Status)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-comment-status")
+                    .table(Comment::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Comment::Table)
+                    .drop_column(Comment::ReviewReason)
+                    .drop_column(Comment::ReviewedAt)
+                    .drop_column(Comment::ReviewedBy)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Comment {
+    Table,
+    Status,
+    ReviewReason,
+    ReviewedAt,
+    ReviewedBy,
+}
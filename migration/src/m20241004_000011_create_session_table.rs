@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230223_000001_create_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Session::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Session::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Session::UserId).big_integer().not_null())
+                    .col(ColumnDef::new(Session::UserAgent).string())
+                    .col(ColumnDef::new(Session::IpAddress).string())
+                    .col(ColumnDef::new(Session::CreatedAt).timestamp().not_null())
+                    .col(ColumnDef::new(Session::ExpiresAt).timestamp().not_null())
+                    .col(ColumnDef::new(Session::RevokedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-session-user_id")
+                            .from(Session::Table, Session::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // refresh token keeps the session of the api client alive
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RefreshToken::Table)
+                    .add_column(ColumnDef::new(RefreshToken::SessionId).uuid())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk-refreshtoken-session_id")
+                            .from_tbl(RefreshToken::Table)
+                            .from_col(RefreshToken::SessionId)
+                            .to_tbl(Session::Table)
+                            .to_col(Session::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RefreshToken::Table)
+                    .drop_foreign_key(Alias::new("fk-refreshtoken-session_id"))
+                    .drop_column(RefreshToken::SessionId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Session::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Session {
+    Table,
+    Id,
+    UserId,
+    UserAgent,
+    IpAddress,
+    CreatedAt,
+    ExpiresAt,
+    RevokedAt,
+}
+
+#[derive(Iden)]
+enum RefreshToken {
+    Table,
+    SessionId,
+}
@@ -0,0 +1,42 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // file_name keeps the full size rendition. Old photos have only file_name
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ProfilePhoto::Table)
+                    .add_column(ColumnDef::new(ProfilePhoto::ThumbnailFileName).string())
+                    .add_column(ColumnDef::new(ProfilePhoto::CardFileName).string())
+                    .add_column(ColumnDef::new(ProfilePhoto::OriginalFileName).string())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ProfilePhoto::Table)
+                    .drop_column(ProfilePhoto::ThumbnailFileName)
+                    .drop_column(ProfilePhoto::CardFileName)
+                    .drop_column(ProfilePhoto::OriginalFileName)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum ProfilePhoto {
+    Table,
+    ThumbnailFileName,
+    CardFileName,
+    OriginalFileName,
+}
@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // photo with the lowest position is the profile cover
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ProfilePhoto::Table)
+                    .add_column(
+                        ColumnDef::new(ProfilePhoto::Position)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+
+        // existing photos keep the upload order
+        db.execute_unprepared(
+            "UPDATE profile_photo SET position = ordered.row_number - 1
+            FROM (SELECT id, ROW_NUMBER() OVER (PARTITION BY profile_id ORDER BY created_at, id) AS row_number
+                FROM profile_photo) AS ordered
+            WHERE profile_photo.id = ordered.id;",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ProfilePhoto::Table)
+                    .drop_column(ProfilePhoto::Position)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum ProfilePhoto {
+    Table,
+    Position,
+}
@@ -0,0 +1,69 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // background work, taken by workers with `FOR UPDATE SKIP LOCKED`
+        manager
+            .create_table(
+                Table::create()
+                    .table(Job::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Job::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Job::Kind).string().not_null())
+                    .col(ColumnDef::new(Job::TargetId).big_integer().not_null())
+                    .col(ColumnDef::new(Job::Status).string().not_null())
+                    .col(ColumnDef::new(Job::Attempts).integer().not_null())
+                    .col(ColumnDef::new(Job::MaxAttempts).integer().not_null())
+                    .col(ColumnDef::new(Job::LastError).text())
+                    .col(ColumnDef::new(Job::RunAt).timestamp().not_null())
+                    .col(ColumnDef::new(Job::LockedAt).timestamp())
+                    .col(ColumnDef::new(Job::CreatedAt).timestamp().not_null())
+                    .col(ColumnDef::new(Job::UpdatedAt).timestamp().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-job-status-run_at")
+                    .table(Job::Table)
+                    .col(Job::Status)
+                    .col(Job::RunAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Job::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Job {
+    Table,
+    Id,
+    Kind,
+    TargetId,
+    Status,
+    Attempts,
+    MaxAttempts,
+    LastError,
+    RunAt,
+    LockedAt,
+    CreatedAt,
+    UpdatedAt,
+}
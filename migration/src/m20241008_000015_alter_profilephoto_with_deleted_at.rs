@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // retention of deleted files is counted from this date
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ProfilePhoto::Table)
+                    .add_column(ColumnDef::new(ProfilePhoto::DeletedAt).date_time().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+
+        // deletion date of old photos is unknown, retention starts now for them
+        db.execute_unprepared(
+            "UPDATE profile_photo SET deleted_at = CURRENT_TIMESTAMP WHERE status = 'deleted';",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ProfilePhoto::Table)
+                    .drop_column(ProfilePhoto::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum ProfilePhoto {
+    Table,
+    DeletedAt,
+}
@@ -0,0 +1,312 @@
+use std::error::Error;
+
+use actix_web::web;
+use clap::{Parser, Subcommand};
+use env_logger::Builder;
+use uuid::Uuid;
+
+use rust_dating_board::{
+    config::Config,
+    db::{DbProvider, ProfilePhotoModel, StatusTable, UserModel},
+    establish_connection, init_photo_storages,
+    web_api::{self, JobContext, PhotoService, ALL_ROLES, JOB_KIND_PROCESS_PHOTO},
+};
+
+type CliResult = Result<(), Box<dyn Error>>;
+
+/// Operating tasks of the board. Uses the same `.env` as the server
+#[derive(Parser)]
+#[clap(name = "admin")]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create, disable and enable users
+    #[clap(subcommand)]
+    User(UserCommand),
+    /// Cities offered in the profile form
+    #[clap(subcommand)]
+    City(CityCommand),
+    /// Force-delete profiles
+    #[clap(subcommand)]
+    Profile(ProfileCommand),
+    /// Photo processing and storage checks
+    #[clap(subcommand)]
+    Photo(PhotoCommand),
+    /// Rows of users, profiles, photos, comments and jobs by status
+    Stats,
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    /// New user, signs in with google by the same email
+    Create {
+        #[clap(long)]
+        email: String,
+        #[clap(long)]
+        name: String,
+        #[clap(long, default_value = "user")]
+        role: String,
+    },
+    /// Revokes every session, sign in is refused until enabled
+    Disable {
+        email: String,
+    },
+    Enable {
+        email: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CityCommand {
+    List,
+    Add { name: String },
+    Enable { name: String },
+    Disable { name: String },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommand {
+    /// Deletes profile with its photos, drafts included
+    Delete { id: Uuid },
+}
+
+#[derive(Subcommand)]
+enum PhotoCommand {
+    /// Queues processing again. Running server does the job
+    Reprocess {
+        #[clap(long)]
+        id: Option<i64>,
+        /// every photo of the profile
+        #[clap(long)]
+        profile: Option<Uuid>,
+        /// every failed photo
+        #[clap(long)]
+        failed: bool,
+    },
+    /// Compares photo rows with storages
+    Check {
+        /// rebuild missing renditions, remove orphan files and update sizes
+        #[clap(long)]
+        fix: bool,
+    },
+}
+
+#[actix_web::main]
+async fn main() {
+    Builder::new()
+        .filter_level(log::LevelFilter::Warn)
+        .filter_module("sqlx::query", log::LevelFilter::Off)
+        .init();
+
+    let cli = Cli::parse();
+    let conf = Config::init();
+    let db_provider = web::Data::new(DbProvider::new(establish_connection(&conf).await.unwrap()));
+
+    let result = match cli.command {
+        Command::User(command) => run_user_command(command, &db_provider).await,
+        Command::City(command) => run_city_command(command, &db_provider).await,
+        Command::Profile(command) => run_profile_command(command, &conf, &db_provider).await,
+        Command::Photo(command) => run_photo_command(command, &conf, &db_provider).await,
+        Command::Stats => print_stats(&db_provider).await,
+    };
+
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run_user_command(command: UserCommand, db_provider: &DbProvider) -> CliResult {
+    match command {
+        UserCommand::Create { email, name, role } => {
+            if !ALL_ROLES.contains(&role.as_str()) {
+                return Err(format!("Role must be one of {:?}", ALL_ROLES).into());
+            }
+            if db_provider.find_user_by_email(&email).await?.is_some() {
+                return Err(format!("User {} already exists", &email).into());
+            }
+
+            let user = db_provider.add_user(None, &name, &email, None).await?;
+            let user = db_provider.update_user_role(&user, &role).await?;
+            println!(
+                "User {} created with id {} and role {}",
+                &user.email, user.id, &user.role
+            );
+        }
+        UserCommand::Disable { email } => {
+            let user = find_user(db_provider, &email).await?;
+            db_provider.update_user_status(&user, "disabled").await?;
+            let revoked = db_provider.revoke_all_user_sessions(user.id).await?;
+            println!("User {} disabled, {} sessions revoked", &email, revoked);
+        }
+        UserCommand::Enable { email } => {
+            let user = find_user(db_provider, &email).await?;
+            db_provider.update_user_status(&user, "active").await?;
+            println!("User {} enabled", &email);
+        }
+    }
+    Ok(())
+}
+
+async fn run_city_command(command: CityCommand, db_provider: &DbProvider) -> CliResult {
+    let (name, status) = match command {
+        CityCommand::List => {
+            for city in db_provider.find_all_cities().await? {
+                println!("{}\t{}\t{}", city.id, &city.status, &city.name);
+            }
+            return Ok(());
+        }
+        CityCommand::Add { name } => {
+            if db_provider.find_city_by_name(&name).await?.is_some() {
+                return Err(format!("City {} already exists", &name).into());
+            }
+            let city = db_provider.add_city(&name).await?;
+            println!("City {} added with id {}", &city.name, city.id);
+            return Ok(());
+        }
+        CityCommand::Enable { name } => (name, "on"),
+        CityCommand::Disable { name } => (name, "off"),
+    };
+
+    let city = db_provider
+        .find_city_by_name(&name)
+        .await?
+        .ok_or(format!("City {} not found", &name))?;
+    db_provider.update_city_status(&city, status).await?;
+    println!("City {} switched {}", &name, status);
+    Ok(())
+}
+
+async fn run_profile_command(
+    command: ProfileCommand,
+    conf: &Config,
+    db_provider: &DbProvider,
+) -> CliResult {
+    let ProfileCommand::Delete { id } = command;
+    let profile = db_provider
+        .find_not_deleted_profile_by(&id)
+        .await?
+        .ok_or(format!("Profile {} not found", &id))?;
+    let profile_photos = db_provider
+        .find_all_uploaded_profile_photos_for(&profile.id)
+        .await?;
+
+    let (photo_storage, _) = init_photo_storages(conf);
+    db_provider
+        .delete_profile_and_photos(&profile, &profile_photos)
+        .await?;
+    PhotoService::delete_profile(photo_storage.as_ref(), &profile.id, &profile_photos).await?;
+    println!(
+        "Profile {} deleted with {} photos",
+        &profile.id,
+        profile_photos.len()
+    );
+    Ok(())
+}
+
+async fn run_photo_command(
+    command: PhotoCommand,
+    conf: &Config,
+    db_provider: &web::Data<DbProvider>,
+) -> CliResult {
+    match command {
+        PhotoCommand::Reprocess {
+            id,
+            profile,
+            failed,
+        } => {
+            let profile_photos = if let Some(id) = id {
+                db_provider
+                    .find_profile_photo_by_id(id)
+                    .await?
+                    .into_iter()
+                    .collect()
+            } else if let Some(profile_id) = profile {
+                db_provider
+                    .find_all_profile_photos_with_any_status_for(&profile_id)
+                    .await?
+            } else if failed {
+                db_provider.find_profile_photos_by_status("failed").await?
+            } else {
+                return Err("One of --id, --profile or --failed is required".into());
+            };
+            reprocess_photos(&profile_photos, conf, db_provider).await
+        }
+        PhotoCommand::Check { fix } => {
+            let (photo_storage, original_photo_storage) = init_photo_storages(conf);
+            let job_context = JobContext {
+                db_provider: db_provider.clone(),
+                photo_storage: web::Data::from(photo_storage),
+                original_photo_storage: web::Data::new(original_photo_storage),
+                watermark: web::Data::new(web_api::Watermark::new(conf)),
+            };
+            let report = web_api::check_photos(&job_context, fix, conf.job_max_attempts).await?;
+
+            println!("Checked photos: {}", report.checked_photos);
+            for (title, keys) in [
+                ("Missing files", &report.missing_files),
+                ("Orphan files", &report.orphan_files),
+                ("Size mismatches", &report.size_mismatches),
+            ] {
+                println!("{}: {}", title, keys.len());
+                keys.iter().for_each(|key| println!("  {}", key));
+            }
+            if fix {
+                println!("Fixed: {}", report.fixed);
+            }
+            Ok(())
+        }
+    }
+}
+
+// deleted photos and photos without original have nothing to be processed
+async fn reprocess_photos(
+    profile_photos: &[ProfilePhotoModel],
+    conf: &Config,
+    db_provider: &DbProvider,
+) -> CliResult {
+    let mut queued = 0;
+    for profile_photo in profile_photos {
+        let is_deleted = profile_photo.status == "deleted" || profile_photo.status == "purged";
+        if is_deleted || profile_photo.original_file_name.is_none() {
+            println!("Photo {} is skipped", profile_photo.id);
+            continue;
+        }
+
+        db_provider
+            .update_profile_photo_status(profile_photo, "processing")
+            .await?;
+        let job = db_provider
+            .add_job(
+                JOB_KIND_PROCESS_PHOTO,
+                profile_photo.id,
+                conf.job_max_attempts,
+            )
+            .await?;
+        println!("Photo {} is queued as job {}", profile_photo.id, job.id);
+        queued += 1;
+    }
+    println!("Queued: {}", queued);
+    Ok(())
+}
+
+async fn print_stats(db_provider: &DbProvider) -> CliResult {
+    for table in StatusTable::ALL {
+        println!("{}:", table.name());
+        for (status, count) in db_provider.count_by_status(table).await? {
+            println!("  {}: {}", status, count);
+        }
+    }
+    Ok(())
+}
+
+async fn find_user(db_provider: &DbProvider, email: &str) -> Result<UserModel, Box<dyn Error>> {
+    db_provider
+        .find_user_by_email(email)
+        .await?
+        .ok_or_else(|| format!("User {} not found", email).into())
+}
@@ -1,69 +1,737 @@
+use std::{collections::HashMap, fs, io, ops::RangeInclusive, path::Path, str::FromStr};
+
 use dotenv::dotenv;
 
+use crate::web_api::{load_watermark_font, load_watermark_logo};
+
+static DEFAULT_CONFIG_FILE: &str = "config.toml";
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub site_protocol: String,
     pub site_url: String,
     pub site_port: i64,
 
+    pub bind_address: String,
+    pub bind_port: u16,
+    /// Zero means one worker per cpu core
+    pub server_workers: usize,
+    pub server_keep_alive_seconds: u64,
+    /// In-flight requests and background jobs get this long to finish on stop
+    pub shutdown_timeout_seconds: u64,
+    /// Plain http when not configured
+    pub tls: Option<TlsConfig>,
+
+    /// env_logger filter syntax, `info,rust_dating_board::db=debug`
+    pub log_level: String,
+    /// `json` lines for collectors or `text` for a terminal
+    pub log_format: String,
+    /// Spans are not collected when not configured
+    pub otel: Option<OtelConfig>,
+    /// Bearer token for `/metrics`, the endpoint is off without it
+    pub metrics_token_opt: Option<String>,
+
     pub database_url: String,
     pub jwt_secret: String,
     pub jwt_max_age: i64,
+    pub refresh_token_max_age: i64,
 
-    pub oauth_google_client_id: String,
-    pub oauth_google_client_secret: String,
-    pub oauth_google_redirect_url: String,
+    /// Google sign in is hidden when not configured
+    pub oauth_google: Option<GoogleOAuthConfig>,
 
     pub all_photos_folder_name: String,
+    pub original_photos_folder_name: String,
+    pub photo_storage: String,
+    pub s3_endpoint: String,
+    pub s3_bucket: String,
+    pub s3_originals_bucket: String,
+    pub s3_region: String,
+    pub s3_access_key: String,
+    pub s3_secret_key: String,
+    pub s3_public_url: String,
+    pub photo_max_bytes: usize,
+    pub photo_max_megapixels: u64,
+    pub max_profile_photos: u64,
+    pub watermark: String,
+    pub watermark_text: String,
+    pub watermark_font_path: String,
+    pub watermark_logo_path: String,
+    pub watermark_color: String,
+    pub watermark_anchor: String,
+    pub watermark_opacity: f32,
+    pub watermark_scale: f32,
+    pub job_workers: usize,
+    pub job_max_attempts: i32,
+    pub cleanup_interval_hours: u64,
+    pub cleanup_retention_days: i64,
+    pub cleanup_dry_run: bool,
 
-    pub captcha_google_id: String,
-    pub captcha_google_secret: String,
-    pub captcha_google_score: f64,
+    pub profiles_on_page: u64,
+    pub comments_on_moderation_page: u64,
+    pub admin_rows_on_page: u64,
+
+    pub rate_limit_store: String,
+    /// Client ip from `X-Forwarded-For`, only safe behind a proxy that sets it
+    pub rate_limit_trust_forwarded: bool,
+    /// Route classes without a limit are not counted
+    pub rate_limit_profile: Option<RateLimitConfig>,
+    pub rate_limit_comment: Option<RateLimitConfig>,
+    pub rate_limit_photo_upload: Option<RateLimitConfig>,
+    pub rate_limit_sign_in: Option<RateLimitConfig>,
+
+    /// `enforce`, `report_only` or `off`
+    pub csp_mode: String,
+    pub csp_report_uri: String,
+    /// Sent only over https, 0 turns it off
+    pub hsts_max_age_seconds: u64,
+    /// `DENY` or `SAMEORIGIN`, `frame-ancestors` of the CSP follows it
+    pub frame_options: String,
+    pub referrer_policy: String,
+    pub permissions_policy: String,
+
+    /// Forms are accepted without the bot check when not configured
+    pub captcha: Option<CaptchaConfig>,
+    pub comment_review_all: bool,
+    pub comment_review_links: bool,
+    pub comment_review_stop_words: Vec<String>,
+    pub admin_emails: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+/// `requests` per `window_seconds`, from values like `10/3600`
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitConfig {
+    pub requests: u32,
+    pub window_seconds: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct OtelConfig {
+    /// Base url of the OTLP/HTTP receiver, `/v1/traces` is appended
+    pub otlp_endpoint: String,
+    pub service_name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GoogleOAuthConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_url: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptchaConfig {
+    /// `recaptcha`, `hcaptcha`, `turnstile`, or the `pass` and `fail` stubs for tests and offline dev
+    pub provider: String,
+    /// Public key of the page widget, empty for the stubs
+    pub site_key: String,
+    pub secret: String,
+    /// Lowest accepted reCAPTCHA score, other providers have no score
+    pub min_score: f64,
 }
 
 impl Config {
+    /// Loads the config or exits with every error found
     pub fn init() -> Config {
+        Config::load().unwrap_or_else(|errors| {
+            eprintln!("Configuration is invalid:");
+            errors.iter().for_each(|error| eprintln!("  {}", error));
+            std::process::exit(1)
+        })
+    }
+
+    /// Defaults, then the toml file from CONFIG_FILE, then environment and `.env`.
+    /// Errors are collected instead of failing on the first one
+    pub fn load() -> Result<Config, Vec<String>> {
         dotenv().ok();
+        Config::from_source(ConfigSource::new())
+    }
 
-        let site_protocol = std::env::var("SITE_PROTOCOL").expect("SITE_PROTOCOL must be set");
-        let site_url = std::env::var("SITE_URL").expect("SITE_URL must be set");
-        let site_port = std::env::var("SITE_PORT").expect("SITE_PORT must be set");
+    fn from_source(mut source: ConfigSource) -> Result<Config, Vec<String>> {
+        let site_protocol = source.one_of("SITE_PROTOCOL", "http", &["http", "https"]);
+        let site_url = source.required("SITE_URL");
+        let site_port = source.required_parse::<i64>("SITE_PORT");
+
+        // hosting platforms pass the port in PORT, the public one fits local runs
+        let site_bind_port = u16::try_from(site_port).unwrap_or_default();
+        let default_bind_port = source.parse::<u16>("PORT", site_bind_port);
+        let bind_address = source.string("BIND_ADDRESS", "127.0.0.1");
+        let bind_port = source.parse::<u16>("BIND_PORT", default_bind_port);
+        let server_workers = source.parse::<usize>("SERVER_WORKERS", 0);
+        let server_keep_alive_seconds = source.parse::<u64>("SERVER_KEEP_ALIVE_SECONDS", 5);
+        let shutdown_timeout_seconds = source.parse::<u64>("SHUTDOWN_TIMEOUT_SECONDS", 30);
+        let tls_cert_path_opt = source.value("TLS_CERT_PATH");
+        let tls = tls_cert_path_opt.map(|cert_path| TlsConfig {
+            cert_path,
+            key_path: source.required("TLS_KEY_PATH"),
+        });
+        let log_level = source.string("LOG_LEVEL", "info");
+        let log_format = source.one_of("LOG_FORMAT", "json", &["json", "text"]);
+        let otel_endpoint_opt = source.value("OTEL_EXPORTER_OTLP_ENDPOINT");
+        let otel = otel_endpoint_opt.map(|otlp_endpoint| OtelConfig {
+            otlp_endpoint,
+            service_name: source.string("OTEL_SERVICE_NAME", "rust-dating-board"),
+        });
+        let metrics_token_opt = source.value("METRICS_TOKEN");
+
+        let database_url = source.required("DATABASE_URL");
+        let jwt_secret = source.required("JWT_SECRET");
+        let jwt_max_age = source.required_parse::<i64>("JWT_MAXAGE");
+        // 30 days by default
+        let refresh_token_max_age = source.parse::<i64>("REFRESH_TOKEN_MAXAGE", 43200);
+
+        let oauth_google_client_id_opt = source.value("OAUTH_GOOGLE_CLIENT_ID");
+        let oauth_google = oauth_google_client_id_opt.map(|client_id| GoogleOAuthConfig {
+            client_id,
+            client_secret: source.required("OAUTH_GOOGLE_CLIENT_SECRET"),
+            redirect_url: source.required("OAUTH_GOOGLE_REDIRECT_URL"),
+        });
+
+        let all_photos_folder_name = source.required("ALL_PHOTOS_FOLDER_NAME");
+        let original_photos_folder_name =
+            source.string("ORIGINAL_PHOTOS_FOLDER_NAME", "photo_originals");
+        let photo_storage = source.one_of("PHOTO_STORAGE", "fs", &["fs", "s3"]);
+        let is_s3_photo_storage = photo_storage == "s3";
+        let s3_endpoint = source.required_if("S3_ENDPOINT", is_s3_photo_storage);
+        let s3_bucket = source.required_if("S3_BUCKET", is_s3_photo_storage);
+        let s3_originals_bucket = source.required_if("S3_ORIGINALS_BUCKET", is_s3_photo_storage);
+        let s3_region = source.string("S3_REGION", "us-east-1");
+        let s3_access_key = source.string("S3_ACCESS_KEY", "");
+        let s3_secret_key = source.string("S3_SECRET_KEY", "");
+        let s3_public_url = source.string("S3_PUBLIC_URL", "");
+        // 15 MB by default, same as the upload widget
+        let photo_max_bytes = source.parse::<usize>("PHOTO_MAX_BYTES", 15728640);
+        let photo_max_megapixels = source.parse::<u64>("PHOTO_MAX_MEGAPIXELS", 40);
+        let max_profile_photos = source.positive("MAX_PROFILE_PHOTOS", 5);
+
+        let watermark = source.one_of("WATERMARK", "text", &["text", "logo", "off"]);
+        let watermark_text = source.string("WATERMARK_TEXT", "");
+        let watermark_font_path = source.string("WATERMARK_FONT_PATH", "");
+        let watermark_logo_path = source.required_if("WATERMARK_LOGO_PATH", watermark == "logo");
+        let watermark_color = source.string("WATERMARK_COLOR", "#FFFFFF");
+        if !is_hex_color(&watermark_color) {
+            source.error(format!(
+                "WATERMARK_COLOR must be like #FFFFFF, got [{}]",
+                &watermark_color
+            ));
+        }
+        let watermark_anchor = source.one_of(
+            "WATERMARK_ANCHOR",
+            "bottom_right",
+            &[
+                "top_left",
+                "top_right",
+                "bottom_left",
+                "bottom_right",
+                "center",
+            ],
+        );
+        // files are opened now, so their errors are listed with the others
+        if watermark == "logo" && !watermark_logo_path.is_empty() {
+            if let Err(err) = load_watermark_logo(&watermark_logo_path) {
+                source.error(err);
+            }
+        }
+        if watermark == "text" {
+            if let Err(err) = load_watermark_font(&watermark_font_path) {
+                source.error(err);
+            }
+        }
+        let watermark_opacity = source.in_range("WATERMARK_OPACITY", 0.8, 0.0..=1.0);
+        // part of the image width
+        let watermark_scale = source.in_range("WATERMARK_SCALE", 0.25, 0.01..=1.0);
 
-        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-        let jwt_maxage = std::env::var("JWT_MAXAGE").expect("JWT_MAXAGE must be set");
+        let job_workers = source.parse::<usize>("JOB_WORKERS", 2);
+        let job_max_attempts = source.parse::<i32>("JOB_MAX_ATTEMPTS", 3);
+        let cleanup_interval_hours = source.parse::<u64>("CLEANUP_INTERVAL_HOURS", 24);
+        let cleanup_retention_days = source.parse::<i64>("CLEANUP_RETENTION_DAYS", 30);
+        let cleanup_dry_run = source.parse::<bool>("CLEANUP_DRY_RUN", false);
 
-        let oauth_google_client_id =
-            std::env::var("OAUTH_GOOGLE_CLIENT_ID").expect("DATABASE_URL must be set");
-        let oauth_google_client_secret = std::env::var("OAUTH_GOOGLE_CLIENT_SECRET")
-            .expect("OAUTH_GOOGLE_CLIENT_SECRET must be set");
-        let oauth_google_redirect_url = std::env::var("OAUTH_GOOGLE_REDIRECT_URL")
-            .expect("OAUTH_GOOGLE_REDIRECT_URL must be set");
+        let profiles_on_page = source.positive("PROFILES_ON_PAGE", 12);
+        let comments_on_moderation_page = source.positive("COMMENTS_ON_MODERATION_PAGE", 20);
+        let admin_rows_on_page = source.positive("ADMIN_ROWS_ON_PAGE", 30);
 
-        let all_photos_folder_name =
-            std::env::var("ALL_PHOTOS_FOLDER_NAME").expect("ALL_PHOTOS_FOLDER_NAME must be set");
+        let rate_limit_store = source.one_of("RATE_LIMIT_STORE", "memory", &["memory"]);
+        let rate_limit_trust_forwarded = source.parse::<bool>("RATE_LIMIT_TRUST_FORWARDED", false);
+        let rate_limit_profile = source.rate_limit("RATE_LIMIT_PROFILE", "10/3600");
+        let rate_limit_comment = source.rate_limit("RATE_LIMIT_COMMENT", "20/3600");
+        let rate_limit_photo_upload = source.rate_limit("RATE_LIMIT_PHOTO_UPLOAD", "60/3600");
+        let rate_limit_sign_in = source.rate_limit("RATE_LIMIT_SIGN_IN", "20/600");
 
-        let captcha_google_id =
-            std::env::var("CAPTCHA_GOOGLE_ID").expect("CAPTCHA_GOOGLE_ID must be set");
-        let captcha_google_secret =
-            std::env::var("CAPTCHA_GOOGLE_SECRET").expect("CAPTCHA_GOOGLE_SECRET must be set");
-        let captcha_google_score =
-            std::env::var("CAPTCHA_GOOGLE_SCORE").expect("CAPTCHA_GOOGLE_SCORE must be set");
+        let csp_mode = source.one_of("CSP_MODE", "enforce", &["enforce", "report_only", "off"]);
+        let csp_report_uri = source.string("CSP_REPORT_URI", "");
+        // a year, the minimum for browser preload lists
+        let hsts_max_age_seconds = source.parse::<u64>("HSTS_MAX_AGE_SECONDS", 31536000);
+        let frame_options = source.one_of("FRAME_OPTIONS", "DENY", &["DENY", "SAMEORIGIN"]);
+        let referrer_policy = source.string("REFERRER_POLICY", "strict-origin-when-cross-origin");
+        let permissions_policy = source.string(
+            "PERMISSIONS_POLICY",
+            "camera=(), microphone=(), geolocation=(), payment=()",
+        );
 
-        Config {
+        // reCAPTCHA is on whenever its key is set, as before the provider setting
+        let default_captcha_provider = source
+            .value("CAPTCHA_GOOGLE_ID")
+            .map_or("off", |_| "recaptcha");
+        let captcha_provider = source.one_of(
+            "CAPTCHA_PROVIDER",
+            default_captcha_provider,
+            &["off", "recaptcha", "hcaptcha", "turnstile", "pass", "fail"],
+        );
+        let captcha_keys_opt = match captcha_provider.as_str() {
+            "recaptcha" => Some(("CAPTCHA_GOOGLE_ID", "CAPTCHA_GOOGLE_SECRET")),
+            "hcaptcha" => Some(("CAPTCHA_HCAPTCHA_SITE_KEY", "CAPTCHA_HCAPTCHA_SECRET")),
+            "turnstile" => Some(("CAPTCHA_TURNSTILE_SITE_KEY", "CAPTCHA_TURNSTILE_SECRET")),
+            _ => None,
+        };
+        let captcha = if captcha_provider == "off" {
+            None
+        } else {
+            let (site_key, secret) = captcha_keys_opt
+                .map(|(site_key_name, secret_name)| {
+                    (source.required(site_key_name), source.required(secret_name))
+                })
+                .unwrap_or_default();
+            Some(CaptchaConfig {
+                provider: captcha_provider,
+                site_key,
+                secret,
+                min_score: source.parse::<f64>("CAPTCHA_GOOGLE_SCORE", 0.7),
+            })
+        };
+        let comment_review_all = source.parse::<bool>("COMMENT_REVIEW_ALL", false);
+        let comment_review_links = source.parse::<bool>("COMMENT_REVIEW_LINKS", false);
+        let comment_review_stop_words = split_list(&source.string("COMMENT_REVIEW_STOP_WORDS", ""));
+        let admin_emails = split_list(&source.string("ADMIN_EMAILS", ""));
+
+        if !source.errors.is_empty() {
+            return Err(source.errors);
+        }
+
+        Ok(Config {
             site_protocol,
             site_url,
-            site_port: site_port.parse::<i64>().unwrap(),
+            site_port,
+            bind_address,
+            bind_port,
+            server_workers,
+            server_keep_alive_seconds,
+            shutdown_timeout_seconds,
+            tls,
+            log_level,
+            log_format,
+            otel,
+            metrics_token_opt,
             database_url,
             jwt_secret,
-            jwt_max_age: jwt_maxage.parse::<i64>().unwrap(),
-            oauth_google_client_id,
-            oauth_google_client_secret,
-            oauth_google_redirect_url,
+            jwt_max_age,
+            refresh_token_max_age,
+            oauth_google,
             all_photos_folder_name,
-            captcha_google_id,
-            captcha_google_secret,
-            captcha_google_score: captcha_google_score.parse::<f64>().unwrap(),
+            original_photos_folder_name,
+            photo_storage,
+            s3_endpoint,
+            s3_bucket,
+            s3_originals_bucket,
+            s3_region,
+            s3_access_key,
+            s3_secret_key,
+            s3_public_url,
+            photo_max_bytes,
+            photo_max_megapixels,
+            max_profile_photos,
+            watermark,
+            watermark_text,
+            watermark_font_path,
+            watermark_logo_path,
+            watermark_color,
+            watermark_anchor,
+            watermark_opacity,
+            watermark_scale,
+            job_workers,
+            job_max_attempts,
+            cleanup_interval_hours,
+            cleanup_retention_days,
+            cleanup_dry_run,
+            profiles_on_page,
+            comments_on_moderation_page,
+            admin_rows_on_page,
+            rate_limit_store,
+            rate_limit_trust_forwarded,
+            rate_limit_profile,
+            rate_limit_comment,
+            rate_limit_photo_upload,
+            rate_limit_sign_in,
+            csp_mode,
+            csp_report_uri,
+            hsts_max_age_seconds,
+            frame_options,
+            referrer_policy,
+            permissions_policy,
+            captcha,
+            comment_review_all,
+            comment_review_links,
+            comment_review_stop_words,
+            admin_emails,
+        })
+    }
+}
+
+#[cfg(test)]
+static TEST_CONFIG_FILE: &str = r#"
+site_url = "localhost"
+site_port = 8080
+database_url = "postgres://localhost/db"
+jwt_secret = "secret"
+jwt_maxage = 60
+all_photos_folder_name = "photos"
+"#;
+
+#[cfg(test)]
+impl Config {
+    /// Required values are set, `env_values` override them and the defaults
+    pub fn for_tests(env_values: &[(&str, &str)]) -> Config {
+        let env_values = env_values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let source =
+            ConfigSource::from_sources(env_values, "test.toml", Ok(TEST_CONFIG_FILE.to_string()));
+        Config::from_source(source).unwrap()
+    }
+}
+
+/// Values by env name. Environment wins over the file, empty value means not set
+struct ConfigSource {
+    env_values: HashMap<String, String>,
+    file_values: HashMap<String, String>,
+    errors: Vec<String>,
+}
+
+impl ConfigSource {
+    // the default file is optional, the one from CONFIG_FILE must exist
+    fn new() -> Self {
+        let env_values: HashMap<String, String> = std::env::vars().collect();
+        let (path, is_required) = match env_values.get("CONFIG_FILE") {
+            Some(path) if !path.is_empty() => (path.clone(), true),
+            _ => (DEFAULT_CONFIG_FILE.to_string(), false),
+        };
+        if !is_required && !Path::new(&path).exists() {
+            return ConfigSource::from_sources(env_values, &path, Ok(String::new()));
+        }
+        let file_content = fs::read_to_string(&path);
+        ConfigSource::from_sources(env_values, &path, file_content)
+    }
+
+    fn from_sources(
+        env_values: HashMap<String, String>,
+        path: &str,
+        file_content: Result<String, io::Error>,
+    ) -> Self {
+        let mut source = ConfigSource {
+            env_values,
+            file_values: HashMap::new(),
+            errors: vec![],
+        };
+        match file_content {
+            Ok(content) => match content.parse::<toml::Table>() {
+                Ok(table) => flatten_table("", &table, &mut source.file_values),
+                Err(err) => source.error(format!("Config file {} is invalid: {}", path, err)),
+            },
+            Err(err) => source.error(format!("Config file {} is not readable: {}", path, err)),
         }
+        source
+    }
+
+    fn error(&mut self, message: String) {
+        self.errors.push(message);
+    }
+
+    fn value(&self, name: &str) -> Option<String> {
+        self.env_values
+            .get(name)
+            .filter(|value| !value.is_empty())
+            .cloned()
+            .or_else(|| self.file_values.get(name).cloned())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn required(&mut self, name: &str) -> String {
+        self.required_if(name, true)
+    }
+
+    fn required_if(&mut self, name: &str, is_required: bool) -> String {
+        let value_opt = self.value(name);
+        if value_opt.is_none() && is_required {
+            self.error(format!("{} must be set", name));
+        }
+        value_opt.unwrap_or_default()
+    }
+
+    fn string(&self, name: &str, default: &str) -> String {
+        self.value(name).unwrap_or(default.to_string())
+    }
+
+    fn one_of(&mut self, name: &str, default: &str, allowed: &[&str]) -> String {
+        let value = self.string(name, default);
+        if !allowed.contains(&value.as_str()) {
+            self.error(format!(
+                "{} must be one of {}, got [{}]",
+                name,
+                allowed.join(", "),
+                &value
+            ));
+        }
+        value
+    }
+
+    fn parse<T: FromStr>(&mut self, name: &str, default: T) -> T {
+        match self.value(name) {
+            Some(value) => self.parse_value(name, &value).unwrap_or(default),
+            None => default,
+        }
+    }
+
+    fn required_parse<T: FromStr + Default>(&mut self, name: &str) -> T {
+        let value = self.required(name);
+        if value.is_empty() {
+            return T::default();
+        }
+        self.parse_value(name, &value).unwrap_or_default()
+    }
+
+    fn positive(&mut self, name: &str, default: u64) -> u64 {
+        let value = self.parse::<u64>(name, default);
+        if value == 0 {
+            self.error(format!("{} must be greater than 0", name));
+        }
+        value
+    }
+
+    fn in_range(&mut self, name: &str, default: f32, range: RangeInclusive<f32>) -> f32 {
+        let value = self.parse::<f32>(name, default);
+        if !range.contains(&value) {
+            self.error(format!(
+                "{} must be between {} and {}, got [{}]",
+                name,
+                range.start(),
+                range.end(),
+                value
+            ));
+        }
+        value
+    }
+
+    /// `off` turns the limit off
+    fn rate_limit(&mut self, name: &str, default: &str) -> Option<RateLimitConfig> {
+        let value = self.string(name, default);
+        if value == "off" {
+            return None;
+        }
+        let rate_limit_opt = value
+            .split_once('/')
+            .and_then(|(requests, window_seconds)| {
+                Some(RateLimitConfig {
+                    requests: requests.trim().parse().ok()?,
+                    window_seconds: window_seconds.trim().parse().ok()?,
+                })
+            })
+            .filter(|rate_limit| rate_limit.requests > 0 && rate_limit.window_seconds > 0);
+        if rate_limit_opt.is_none() {
+            self.error(format!(
+                "{} must be like 10/3600 (requests/seconds) or off, got [{}]",
+                name, &value
+            ));
+        }
+        rate_limit_opt
+    }
+
+    fn parse_value<T: FromStr>(&mut self, name: &str, value: &str) -> Option<T> {
+        let parsed_opt = value.trim().parse::<T>().ok();
+        if parsed_opt.is_none() {
+            self.error(format!(
+                "{} must be {}, got [{}]",
+                name,
+                std::any::type_name::<T>(),
+                value
+            ));
+        }
+        parsed_opt
+    }
+}
+
+// `[s3] bucket = "photos"` is the same as S3_BUCKET, arrays become comma separated lists
+fn flatten_table(prefix: &str, table: &toml::Table, values: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let name = if prefix.is_empty() {
+            key.to_uppercase()
+        } else {
+            format!("{}_{}", prefix, key.to_uppercase())
+        };
+        match value {
+            toml::Value::Table(nested_table) => flatten_table(&name, nested_table, values),
+            value => {
+                values.insert(name, toml_value_to_string(value));
+            }
+        }
+    }
+}
+
+fn toml_value_to_string(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(text) => text.clone(),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(toml_value_to_string)
+            .collect::<Vec<String>>()
+            .join(","),
+        value => value.to_string(),
+    }
+}
+
+// `#RRGGBB`
+fn is_hex_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .map(|hex_color| hex_color.len() == 6 && hex::decode(hex_color).is_ok())
+        .unwrap_or_default()
+}
+
+// comma separated env value into lowercase list without empty items
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(env_values: &[(&str, &str)], file_content: &str) -> ConfigSource {
+        let env_values = env_values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        ConfigSource::from_sources(env_values, "test.toml", Ok(file_content.to_string()))
+    }
+
+    fn config(env_values: &[(&str, &str)], file_content: &str) -> Result<Config, Vec<String>> {
+        Config::from_source(source(env_values, file_content))
+    }
+
+    #[test]
+    fn loads_required_values_from_file() {
+        let config = config(&[], TEST_CONFIG_FILE).unwrap();
+
+        assert_eq!(config.site_url, "localhost");
+        assert_eq!(config.site_port, 8080);
+        assert_eq!(config.bind_port, 8080);
+        assert_eq!(config.jwt_max_age, 60);
+    }
+
+    #[test]
+    fn env_wins_over_file() {
+        let config = config(&[("SITE_URL", "example.com")], TEST_CONFIG_FILE).unwrap();
+
+        assert_eq!(config.site_url, "example.com");
+    }
+
+    #[test]
+    fn empty_env_value_falls_back_to_file() {
+        let config = config(&[("SITE_URL", ""), ("LOG_LEVEL", "")], TEST_CONFIG_FILE).unwrap();
+
+        assert_eq!(config.site_url, "localhost");
+        assert_eq!(config.log_level, "info");
+    }
+
+    #[test]
+    fn empty_file_value_is_not_set() {
+        let file_content = format!("{}\nmetrics_token = \"\"\n", TEST_CONFIG_FILE);
+        let config = config(&[], &file_content).unwrap();
+
+        assert_eq!(config.metrics_token_opt, None);
+    }
+
+    #[test]
+    fn collects_all_errors() {
+        let errors = config(&[("SITE_PORT", "port"), ("LOG_FORMAT", "xml")], "").unwrap_err();
+
+        assert!(errors.contains(&"SITE_URL must be set".to_string()));
+        assert!(errors.contains(&"DATABASE_URL must be set".to_string()));
+        assert!(errors.contains(&"LOG_FORMAT must be one of json, text, got [xml]".to_string()));
+        assert!(errors
+            .iter()
+            .any(|error| error.starts_with("SITE_PORT must be i64")));
+    }
+
+    #[test]
+    fn invalid_file_is_an_error() {
+        let source = source(&[], "site_url = ");
+
+        assert_eq!(source.errors.len(), 1);
+        assert!(source.errors[0].starts_with("Config file test.toml is invalid"));
+    }
+
+    #[test]
+    fn maps_tables_to_prefixed_names() {
+        let file_content = format!(
+            "{}\nphoto_storage = \"s3\"\n[s3]\nendpoint = \"http://localhost:9000\"\nbucket = \"photos\"\noriginals_bucket = \"originals\"\n",
+            TEST_CONFIG_FILE
+        );
+        let config = config(&[], &file_content).unwrap();
+
+        assert_eq!(config.s3_endpoint, "http://localhost:9000");
+        assert_eq!(config.s3_bucket, "photos");
+        assert_eq!(config.s3_originals_bucket, "originals");
+    }
+
+    #[test]
+    fn joins_arrays_into_lists() {
+        let source = source(
+            &[],
+            "[admin]\nemails = [\"a@example.com\", \"b@example.com\"]\n",
+        );
+
+        assert_eq!(
+            source.value("ADMIN_EMAILS").as_deref(),
+            Some("a@example.com,b@example.com")
+        );
+    }
+
+    #[test]
+    fn checks_watermark_ranges() {
+        let errors = config(
+            &[("WATERMARK_OPACITY", "1.5"), ("WATERMARK_SCALE", "0")],
+            TEST_CONFIG_FILE,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                "WATERMARK_OPACITY must be between 0 and 1, got [1.5]".to_string(),
+                "WATERMARK_SCALE must be between 0.01 and 1, got [0]".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_watermark_range_bounds() {
+        let config = config(
+            &[("WATERMARK_OPACITY", "0"), ("WATERMARK_SCALE", "1")],
+            TEST_CONFIG_FILE,
+        )
+        .unwrap();
+
+        assert_eq!(config.watermark_opacity, 0.0);
+        assert_eq!(config.watermark_scale, 1.0);
     }
 }
@@ -12,6 +12,9 @@ pub struct Model {
     pub status: String,
     pub created_at: DateTime,
     pub text: String,
+    pub review_reason: Option<String>,
+    pub reviewed_at: Option<DateTime>,
+    pub reviewed_by: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -6,22 +6,25 @@ use log::info;
 use sea_orm::query::*;
 use sea_orm::sea_query::Expr;
 use sea_orm::ActiveValue::NotSet;
+use sea_orm::{prelude::DateTime, DbConn, EntityTrait};
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, DbBackend, DbErr, FromQueryResult, Order, PaginatorTrait,
-    QueryFilter, QueryOrder, Set, Statement,
+    QueryFilter, QueryOrder, Set, Statement, TransactionError,
 };
-use sea_orm::{DbConn, EntityTrait};
 use uuid::Uuid;
 
 use crate::db::comment;
+use crate::web_api::db_span;
 
-use super::city::{self};
+use super::city::{self, Model as CityModel};
+use super::job::{self, Model as JobModel};
 use super::profile::{self, Model as ProfileModel};
 use super::profile_photo::{self, Model as ProfilePhotoModel};
+use super::refresh_token::{self, Model as RefreshTokenModel};
+use super::session::{self, Model as SessionModel};
 use super::user::{self, Model as UserModel};
 use super::CommentModel;
 
-#[derive(Clone)]
 pub struct DbProvider {
     pub db_con: DbConn,
 }
@@ -33,13 +36,83 @@ impl DbProvider {
         DbProvider { db_con }
     }
 
+    /// Cheapest query that still needs a live connection
+    pub async fn ping(&self) -> Result<(), DbErr> {
+        let _span = db_span("ping");
+        self.db_con
+            .execute(Statement::from_string(
+                DbBackend::Postgres,
+                "SELECT 1;".to_owned(),
+            ))
+            .await
+            .map(|_| ())
+    }
+
     pub async fn find_user_by_email(&self, email: &str) -> Result<Option<UserModel>, DbErr> {
+        let _span = db_span("find_user_by_email");
         user::Entity::find()
             .filter(user::Column::Email.eq(email))
             .one(&self.db_con)
             .await
     }
 
+    pub async fn find_user_by_id(&self, id: i64) -> Result<Option<UserModel>, DbErr> {
+        let _span = db_span("find_user_by_id");
+        user::Entity::find_by_id(id).one(&self.db_con).await
+    }
+
+    pub async fn update_user_role(
+        &self,
+        model: &UserModel,
+        role: &str,
+    ) -> Result<UserModel, DbErr> {
+        let _span = db_span("update_user_role");
+        let mut mutable: user::ActiveModel = model.to_owned().into();
+        mutable.role = Set(role.to_owned());
+        mutable.update(&self.db_con).await
+    }
+
+    pub async fn update_user_status(
+        &self,
+        model: &UserModel,
+        status: &str,
+    ) -> Result<UserModel, DbErr> {
+        let _span = db_span("update_user_status");
+        let mut mutable: user::ActiveModel = model.to_owned().into();
+        mutable.status = Set(status.to_owned());
+        mutable.update(&self.db_con).await
+    }
+
+    pub async fn users_pagination(
+        &self,
+        number_of_entities: u64,
+        page_opt: &Option<u64>,
+        search_opt: &Option<String>,
+    ) -> Result<(TotalPages, Vec<UserModel>), DbErr> {
+        let _span = db_span("users_pagination");
+        let query = user::Entity::find()
+            .apply_if(search_opt.to_owned(), |query, v| {
+                query.filter(
+                    Condition::any()
+                        .add(user::Column::Name.contains(&v))
+                        .add(user::Column::Email.contains(&v)),
+                )
+            })
+            .order_by(user::Column::CreatedAt, Order::Desc)
+            .paginate(&self.db_con, number_of_entities);
+
+        let total_pages = query.num_pages().await?;
+        let query_page = page_opt.map(|f| if f > 0 { f - 1 } else { f }).unwrap_or(0);
+        info!(
+            "Fetching users page: [{}]. Search: [{}]. Total num of pages: [{}]",
+            query_page + 1,
+            search_opt.as_deref().unwrap_or_default(),
+            total_pages
+        );
+        let users = query.fetch_page(query_page).await;
+        users.map(|data| (total_pages, data))
+    }
+
     pub async fn add_user(
         &self,
         id: Option<i64>,
@@ -47,6 +120,7 @@ impl DbProvider {
         email: &str,
         provider_opt: Option<&str>,
     ) -> Result<UserModel, DbErr> {
+        let _span = db_span("add_user");
         let user = user::ActiveModel {
             id: id.map_or(NotSet, |f| Set(f)),
             name: Set(name.to_string()),
@@ -58,39 +132,219 @@ impl DbProvider {
         user.insert(&self.db_con).await
     }
 
+    pub async fn add_refresh_token(
+        &self,
+        id: Uuid,
+        user_id: i64,
+        session_id: &Uuid,
+        token_hash: &str,
+        expires_at: DateTime,
+    ) -> Result<RefreshTokenModel, DbErr> {
+        let _span = db_span("add_refresh_token");
+        let refresh_token = refresh_token::ActiveModel {
+            id: Set(id),
+            user_id: Set(user_id),
+            session_id: Set(Some(session_id.to_owned())),
+            token_hash: Set(token_hash.to_owned()),
+            created_at: Set(Utc::now().naive_utc()),
+            expires_at: Set(expires_at),
+            ..Default::default()
+        };
+        refresh_token.insert(&self.db_con).await
+    }
+
+    pub async fn find_refresh_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshTokenModel>, DbErr> {
+        let _span = db_span("find_refresh_token_by_hash");
+        refresh_token::Entity::find()
+            .filter(refresh_token::Column::TokenHash.eq(token_hash))
+            .one(&self.db_con)
+            .await
+    }
+
+    /// Revokes the token only if it is not revoked yet. False means another request
+    /// has already revoked or rotated it
+    pub async fn revoke_refresh_token(
+        &self,
+        model: &RefreshTokenModel,
+        replaced_by_opt: Option<Uuid>,
+    ) -> Result<bool, DbErr> {
+        let _span = db_span("revoke_refresh_token");
+        let update_result = refresh_token::Entity::update_many()
+            .col_expr(
+                refresh_token::Column::RevokedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .col_expr(
+                refresh_token::Column::ReplacedBy,
+                Expr::value(replaced_by_opt),
+            )
+            .filter(refresh_token::Column::Id.eq(model.id))
+            .filter(refresh_token::Column::RevokedAt.is_null())
+            .exec(&self.db_con)
+            .await?;
+        Ok(update_result.rows_affected == 1)
+    }
+
+    pub async fn add_session(
+        &self,
+        user_id: i64,
+        user_agent_opt: Option<&str>,
+        ip_address_opt: Option<&str>,
+        expires_at: DateTime,
+    ) -> Result<SessionModel, DbErr> {
+        let _span = db_span("add_session");
+        let session = session::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            user_agent: Set(user_agent_opt.map(|f| f.to_owned())),
+            ip_address: Set(ip_address_opt.map(|f| f.to_owned())),
+            created_at: Set(Utc::now().naive_utc()),
+            expires_at: Set(expires_at),
+            ..Default::default()
+        };
+        session.insert(&self.db_con).await
+    }
+
+    pub async fn find_active_session(
+        &self,
+        id: &Uuid,
+        user_id: i64,
+    ) -> Result<Option<SessionModel>, DbErr> {
+        let _span = db_span("find_active_session");
+        session::Entity::find_by_id(id.to_owned())
+            .filter(session::Column::UserId.eq(user_id))
+            .filter(session::Column::RevokedAt.is_null())
+            .filter(session::Column::ExpiresAt.gt(Utc::now().naive_utc()))
+            .one(&self.db_con)
+            .await
+    }
+
+    pub async fn all_active_user_sessions(&self, user_id: i64) -> Result<Vec<SessionModel>, DbErr> {
+        let _span = db_span("all_active_user_sessions");
+        session::Entity::find()
+            .filter(session::Column::UserId.eq(user_id))
+            .filter(session::Column::RevokedAt.is_null())
+            .filter(session::Column::ExpiresAt.gt(Utc::now().naive_utc()))
+            .order_by(session::Column::CreatedAt, Order::Desc)
+            .all(&self.db_con)
+            .await
+    }
+
+    pub async fn prolong_session(
+        &self,
+        model: &SessionModel,
+        expires_at: DateTime,
+    ) -> Result<SessionModel, DbErr> {
+        let _span = db_span("prolong_session");
+        let mut mutable: session::ActiveModel = model.to_owned().into();
+        mutable.expires_at = Set(expires_at);
+        mutable.update(&self.db_con).await
+    }
+
+    /// Refresh tokens of the session are revoked as well
+    pub async fn revoke_session(&self, model: &SessionModel) -> Result<(), DbErr> {
+        let _span = db_span("revoke_session");
+        let now = Utc::now().naive_utc();
+        let mut mutable: session::ActiveModel = model.to_owned().into();
+        mutable.revoked_at = Set(Some(now));
+        mutable.update(&self.db_con).await?;
+
+        refresh_token::Entity::update_many()
+            .col_expr(refresh_token::Column::RevokedAt, Expr::value(now))
+            .filter(refresh_token::Column::SessionId.eq(model.id))
+            .filter(refresh_token::Column::RevokedAt.is_null())
+            .exec(&self.db_con)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Log out everywhere. Returns number of revoked sessions
+    pub async fn revoke_all_user_sessions(&self, user_id: i64) -> Result<u64, DbErr> {
+        let _span = db_span("revoke_all_user_sessions");
+        let now = Utc::now().naive_utc();
+        let update_result = session::Entity::update_many()
+            .col_expr(session::Column::RevokedAt, Expr::value(now))
+            .filter(session::Column::UserId.eq(user_id))
+            .filter(session::Column::RevokedAt.is_null())
+            .exec(&self.db_con)
+            .await?;
+
+        refresh_token::Entity::update_many()
+            .col_expr(refresh_token::Column::RevokedAt, Expr::value(now))
+            .filter(refresh_token::Column::UserId.eq(user_id))
+            .filter(refresh_token::Column::RevokedAt.is_null())
+            .exec(&self.db_con)
+            .await?;
+
+        Ok(update_result.rows_affected)
+    }
+
     pub async fn add_comment(
         &self,
         profile_id: &Uuid,
         user_id: &i64,
         text: &String,
+        status: &str,
     ) -> Result<CommentModel, DbErr> {
+        let _span = db_span("add_comment");
         let comment = comment::ActiveModel {
             id: Set(Uuid::new_v4()),
             created_at: Set(Utc::now().naive_utc()),
             profile_id: Set(profile_id.clone()),
             user_id: Set(user_id.clone()),
             text: Set(text.clone()),
-            status: Set(String::from("approved")),
+            status: Set(status.to_owned()),
             ..Default::default()
         };
         comment.insert(&self.db_con).await
     }
 
+    // photos being processed count too, otherwise the limit is easy to pass with parallel uploads
     pub async fn count_profile_photos(&self, profile_id: &Uuid) -> Result<u64, DbErr> {
+        let _span = db_span("count_profile_photos");
         profile_photo::Entity::find()
             .filter(profile_photo::Column::ProfileId.eq(profile_id.to_owned()))
-            .filter(profile_photo::Column::Status.eq("active"))
+            .filter(profile_photo::Column::Status.is_in(["active", "processing"]))
             .count(&self.db_con)
             .await
     }
 
-    pub async fn find_active_profile_photo_with_profile_by_id_and_user_id(
+    pub async fn find_profile_photo_by_id(
+        &self,
+        id: i64,
+    ) -> Result<Option<ProfilePhotoModel>, DbErr> {
+        let _span = db_span("find_profile_photo_by_id");
+        profile_photo::Entity::find_by_id(id)
+            .one(&self.db_con)
+            .await
+    }
+
+    pub async fn find_profile_photos_by_ids_and_user_id(
+        &self,
+        ids: &[i64],
+        user_id: i64,
+    ) -> Result<Vec<ProfilePhotoModel>, DbErr> {
+        let _span = db_span("find_profile_photos_by_ids_and_user_id");
+        profile_photo::Entity::find()
+            .filter(profile_photo::Column::Id.is_in(ids.to_owned()))
+            .inner_join(profile::Entity)
+            .filter(profile::Column::UserId.eq(user_id))
+            .all(&self.db_con)
+            .await
+    }
+
+    pub async fn find_uploaded_profile_photo_with_profile_by_id_and_user_id(
         &self,
         id: i64,
         user_id: i64,
     ) -> Result<Option<(ProfilePhotoModel, ProfileModel)>, DbErr> {
+        let _span = db_span("find_uploaded_profile_photo_with_profile_by_id_and_user_id");
         profile_photo::Entity::find_by_id(id)
-            .filter(profile_photo::Column::Status.eq("active"))
+            .filter(profile_photo::Column::Status.is_in(["active", "processing"]))
             .find_also_related(profile::Entity)
             .filter(profile::Column::UserId.eq(user_id))
             .one(&self.db_con)
@@ -102,6 +356,7 @@ impl DbProvider {
         &self,
         user_id: i64,
     ) -> Result<Option<ProfileModel>, DbErr> {
+        let _span = db_span("find_draft_profile_for");
         profile::Entity::find()
             .filter(profile::Column::UserId.eq(user_id))
             .filter(profile::Column::Status.eq("draft"))
@@ -110,6 +365,7 @@ impl DbProvider {
     }
 
     pub async fn find_active_profile_by(&self, id: &Uuid) -> Result<Option<ProfileModel>, DbErr> {
+        let _span = db_span("find_active_profile_by");
         profile::Entity::find_by_id(id.to_owned())
             .filter(profile::Column::Status.eq("active"))
             .one(&self.db_con)
@@ -121,6 +377,7 @@ impl DbProvider {
         id: &Uuid,
         user_id: i64,
     ) -> Result<Option<ProfileModel>, DbErr> {
+        let _span = db_span("find_active_profile_by_id_and_user_id");
         profile::Entity::find_by_id(id.to_owned())
             .filter(profile::Column::Status.eq("active"))
             .filter(profile::Column::UserId.eq(user_id))
@@ -128,18 +385,141 @@ impl DbProvider {
             .await
     }
 
+    pub async fn find_not_deleted_profile_by(
+        &self,
+        id: &Uuid,
+    ) -> Result<Option<ProfileModel>, DbErr> {
+        let _span = db_span("find_not_deleted_profile_by");
+        profile::Entity::find_by_id(id.to_owned())
+            .filter(profile::Column::Status.ne("deleted"))
+            .one(&self.db_con)
+            .await
+    }
+
+    pub async fn not_deleted_profiles_pagination(
+        &self,
+        number_of_entities: u64,
+        page_opt: &Option<u64>,
+        search_opt: &Option<String>,
+    ) -> Result<(TotalPages, Vec<ProfileModel>), DbErr> {
+        let _span = db_span("not_deleted_profiles_pagination");
+        let query = profile::Entity::find()
+            .filter(profile::Column::Status.ne("deleted"))
+            .apply_if(search_opt.to_owned(), |query, v| {
+                query.filter(
+                    Condition::any()
+                        .add(profile::Column::Name.contains(&v))
+                        .add(profile::Column::PhoneNumber.contains(&v))
+                        .add(profile::Column::Description.contains(&v)),
+                )
+            })
+            .order_by(profile::Column::UpdatedAt, Order::Desc)
+            .paginate(&self.db_con, number_of_entities);
+
+        let total_pages = query.num_pages().await?;
+        let query_page = page_opt.map(|f| if f > 0 { f - 1 } else { f }).unwrap_or(0);
+        info!(
+            "Fetching not deleted profiles page: [{}]. Search: [{}]. Total num of pages: [{}]",
+            query_page + 1,
+            search_opt.as_deref().unwrap_or_default(),
+            total_pages
+        );
+        let profiles = query.fetch_page(query_page).await;
+        profiles.map(|data| (total_pages, data))
+    }
+
+    pub async fn find_active_profile_photo_by_id(
+        &self,
+        id: i64,
+    ) -> Result<Option<ProfilePhotoModel>, DbErr> {
+        let _span = db_span("find_active_profile_photo_by_id");
+        profile_photo::Entity::find_by_id(id)
+            .filter(profile_photo::Column::Status.eq("active"))
+            .one(&self.db_con)
+            .await
+    }
+
     pub async fn find_all_profile_photos_for(
         &self,
         profile_id: &Uuid,
     ) -> Result<Vec<ProfilePhotoModel>, DbErr> {
+        let _span = db_span("find_all_profile_photos_for");
         profile_photo::Entity::find()
             .filter(profile_photo::Column::ProfileId.eq(profile_id.to_owned()))
             .filter(profile_photo::Column::Status.eq("active"))
+            .order_by(profile_photo::Column::Position, Order::Asc)
+            .order_by(profile_photo::Column::Id, Order::Asc)
+            .all(&self.db_con)
+            .await
+    }
+
+    /// Every photo row of the profile, deleted and failed included
+    pub async fn find_all_profile_photos_with_any_status_for(
+        &self,
+        profile_id: &Uuid,
+    ) -> Result<Vec<ProfilePhotoModel>, DbErr> {
+        let _span = db_span("find_all_profile_photos_with_any_status_for");
+        profile_photo::Entity::find()
+            .filter(profile_photo::Column::ProfileId.eq(profile_id.to_owned()))
+            .order_by(profile_photo::Column::Id, Order::Asc)
+            .all(&self.db_con)
+            .await
+    }
+
+    /// Active and still processing photos, i.e. everything the owner sees while editing
+    pub async fn find_all_uploaded_profile_photos_for(
+        &self,
+        profile_id: &Uuid,
+    ) -> Result<Vec<ProfilePhotoModel>, DbErr> {
+        let _span = db_span("find_all_uploaded_profile_photos_for");
+        profile_photo::Entity::find()
+            .filter(profile_photo::Column::ProfileId.eq(profile_id.to_owned()))
+            .filter(profile_photo::Column::Status.is_in(["active", "processing"]))
+            .order_by(profile_photo::Column::Position, Order::Asc)
+            .order_by(profile_photo::Column::Id, Order::Asc)
             .all(&self.db_con)
             .await
     }
 
+    /// Drafts untouched since `updated_before` that got no photos since then either
+    pub async fn find_abandoned_draft_profiles(
+        &self,
+        updated_before: DateTime,
+    ) -> Result<Vec<ProfileModel>, DbErr> {
+        let _span = db_span("find_abandoned_draft_profiles");
+        profile::Entity::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "SELECT * FROM profile WHERE status = 'draft' AND updated_at < $1
+                AND NOT EXISTS (
+                    SELECT 1 FROM profile_photo WHERE profile_photo.profile_id = profile.id AND profile_photo.created_at >= $1
+                )
+                ORDER BY updated_at;",
+                [updated_before.into()],
+            ))
+            .all(&self.db_con)
+            .await
+    }
+
+    /// Draft was never shown to anybody, so rows are removed, not marked
+    pub async fn delete_draft_profile_and_photos(
+        &self,
+        draft_profile: &ProfileModel,
+    ) -> Result<(), DbErr> {
+        let _span = db_span("delete_draft_profile_and_photos");
+        profile_photo::Entity::delete_many()
+            .filter(profile_photo::Column::ProfileId.eq(draft_profile.id))
+            .exec(&self.db_con)
+            .await?;
+        profile::Entity::delete_by_id(draft_profile.id)
+            .filter(profile::Column::Status.eq("draft"))
+            .exec(&self.db_con)
+            .await
+            .map(|_| ())
+    }
+
     pub async fn add_draft_profile_for(&self, user_id: i64) -> Result<ProfileModel, DbErr> {
+        let _span = db_span("add_draft_profile_for");
         let profile = profile::ActiveModel {
             id: Set(Uuid::new_v4()),
             created_at: Set(Utc::now().naive_utc()),
@@ -157,29 +537,190 @@ impl DbProvider {
         profile.insert(&self.db_con).await
     }
 
+    /// The photo and the job making its renditions are inserted together, a photo without
+    /// its job would stay processing forever
+    #[allow(clippy::too_many_arguments)]
     pub async fn add_profile_photo(
         &self,
         profile_id: &Uuid,
         file_name: &str,
         file_size: i64,
-    ) -> Result<ProfilePhotoModel, DbErr> {
+        thumbnail_file_name: &str,
+        card_file_name: &str,
+        original_file_name: &str,
+        job_kind: &str,
+        job_max_attempts: i32,
+    ) -> Result<(ProfilePhotoModel, JobModel), DbErr> {
+        let _span = db_span("add_profile_photo");
+        let profile_id = profile_id.to_owned();
         let profile_photo = profile_photo::ActiveModel {
             id: NotSet,
             created_at: Set(Utc::now().naive_utc()),
-            status: Set(String::from("active")),
-            profile_id: Set(profile_id.to_owned()),
+            // renditions are made by the background job
+            status: Set(String::from("processing")),
+            profile_id: Set(profile_id),
             file_name: Set(file_name.to_string()),
             size: Set(file_size),
+            thumbnail_file_name: Set(Some(thumbnail_file_name.to_string())),
+            card_file_name: Set(Some(card_file_name.to_string())),
+            original_file_name: Set(Some(original_file_name.to_string())),
+            position: NotSet,
+            deleted_at: Set(None),
         };
-        profile_photo.insert(&self.db_con).await
+        let job_kind = job_kind.to_owned();
+        self.db_con
+            .transaction::<_, (ProfilePhotoModel, JobModel), DbErr>(|txn| {
+                Box::pin(async move {
+                    // new photo goes to the end, photos uploaded together are ordered by id
+                    let last_position_opt = profile_photo::Entity::find()
+                        .filter(profile_photo::Column::ProfileId.eq(profile_id))
+                        .filter(profile_photo::Column::Status.is_in(["active", "processing"]))
+                        .order_by(profile_photo::Column::Position, Order::Desc)
+                        .one(txn)
+                        .await?
+                        .map(|photo| photo.position);
+                    let mut profile_photo = profile_photo;
+                    profile_photo.position =
+                        Set(last_position_opt.map_or(0, |position| position + 1));
+                    let profile_photo = profile_photo.insert(txn).await?;
+                    let job = new_job(&job_kind, profile_photo.id, job_max_attempts)
+                        .insert(txn)
+                        .await?;
+                    Ok((profile_photo, job))
+                })
+            })
+            .await
+            .map_err(|err| match err {
+                TransactionError::Connection(err) | TransactionError::Transaction(err) => err,
+            })
+    }
+
+    pub async fn reorder_profile_photos(
+        &self,
+        profile_id: &Uuid,
+        photo_ids: &[i64],
+    ) -> Result<(), DbErr> {
+        let _span = db_span("reorder_profile_photos");
+        let profile_id = profile_id.to_owned();
+        let photo_ids = photo_ids.to_vec();
+        // all positions or none, a half applied order would mix two orders up
+        self.db_con
+            .transaction::<_, (), DbErr>(|txn| {
+                Box::pin(async move {
+                    for (position, photo_id) in photo_ids.into_iter().enumerate() {
+                        profile_photo::Entity::update_many()
+                            .col_expr(
+                                profile_photo::Column::Position,
+                                Expr::value(position as i32),
+                            )
+                            .filter(profile_photo::Column::Id.eq(photo_id))
+                            .filter(profile_photo::Column::ProfileId.eq(profile_id))
+                            .exec(txn)
+                            .await?;
+                    }
+                    Ok(())
+                })
+            })
+            .await
+            .map_err(|err| match err {
+                TransactionError::Connection(err) | TransactionError::Transaction(err) => err,
+            })
+    }
+
+    pub async fn update_profile_photo_status(
+        &self,
+        model: &ProfilePhotoModel,
+        status: &str,
+    ) -> Result<ProfilePhotoModel, DbErr> {
+        let _span = db_span("update_profile_photo_status");
+        let mut mutable: profile_photo::ActiveModel = model.to_owned().into();
+        mutable.status = Set(status.to_owned());
+
+        mutable.update(&self.db_con).await
+    }
+
+    /// Only a photo that is still processing becomes active. False means it was deleted meanwhile
+    pub async fn activate_processed_profile_photo(&self, id: i64) -> Result<bool, DbErr> {
+        let _span = db_span("activate_processed_profile_photo");
+        let update_result = profile_photo::Entity::update_many()
+            .col_expr(profile_photo::Column::Status, Expr::value("active"))
+            .filter(profile_photo::Column::Id.eq(id))
+            .filter(profile_photo::Column::Status.eq("processing"))
+            .exec(&self.db_con)
+            .await?;
+        Ok(update_result.rows_affected == 1)
     }
 
     pub async fn update_profile_photo_with_delete_status(
         &self,
         model: &ProfilePhotoModel,
     ) -> Result<ProfilePhotoModel, DbErr> {
+        let _span = db_span("update_profile_photo_with_delete_status");
         let mut mutable: profile_photo::ActiveModel = model.to_owned().into();
         mutable.status = Set("deleted".to_owned());
+        mutable.deleted_at = Set(Some(Utc::now().naive_utc()));
+
+        mutable.update(&self.db_con).await
+    }
+
+    /// Deleted photos whose files outlived the retention
+    pub async fn find_deleted_profile_photos_before(
+        &self,
+        deleted_before: DateTime,
+    ) -> Result<Vec<ProfilePhotoModel>, DbErr> {
+        let _span = db_span("find_deleted_profile_photos_before");
+        profile_photo::Entity::find()
+            .filter(profile_photo::Column::Status.eq("deleted"))
+            .filter(profile_photo::Column::DeletedAt.lt(deleted_before))
+            .order_by_asc(profile_photo::Column::Id)
+            .all(&self.db_con)
+            .await
+    }
+
+    pub async fn find_profile_photos_by_status(
+        &self,
+        status: &str,
+    ) -> Result<Vec<ProfilePhotoModel>, DbErr> {
+        let _span = db_span("find_profile_photos_by_status");
+        profile_photo::Entity::find()
+            .filter(profile_photo::Column::Status.eq(status))
+            .order_by_asc(profile_photo::Column::Id)
+            .all(&self.db_con)
+            .await
+    }
+
+    /// Every photo row that may still have files
+    pub async fn find_all_not_purged_profile_photos(
+        &self,
+    ) -> Result<Vec<ProfilePhotoModel>, DbErr> {
+        let _span = db_span("find_all_not_purged_profile_photos");
+        profile_photo::Entity::find()
+            .filter(profile_photo::Column::Status.ne("purged"))
+            .order_by_asc(profile_photo::Column::Id)
+            .all(&self.db_con)
+            .await
+    }
+
+    pub async fn update_profile_photo_size(
+        &self,
+        model: &ProfilePhotoModel,
+        size: i64,
+    ) -> Result<ProfilePhotoModel, DbErr> {
+        let _span = db_span("update_profile_photo_size");
+        let mut mutable: profile_photo::ActiveModel = model.to_owned().into();
+        mutable.size = Set(size);
+
+        mutable.update(&self.db_con).await
+    }
+
+    /// Files are gone, the row stays for history
+    pub async fn update_profile_photo_with_purged_status(
+        &self,
+        model: &ProfilePhotoModel,
+    ) -> Result<ProfilePhotoModel, DbErr> {
+        let _span = db_span("update_profile_photo_with_purged_status");
+        let mut mutable: profile_photo::ActiveModel = model.to_owned().into();
+        mutable.status = Set("purged".to_owned());
 
         mutable.update(&self.db_con).await
     }
@@ -194,6 +735,7 @@ impl DbProvider {
         description: &str,
         phone_number: &str,
     ) -> Result<ProfileModel, DbErr> {
+        let _span = db_span("publish_profie");
         let mut mutable: profile::ActiveModel = model.to_owned().into();
         mutable.name = Set(name.to_owned());
         mutable.height = Set(height);
@@ -208,6 +750,7 @@ impl DbProvider {
     }
 
     pub async fn find_city_names(&self) -> Result<Vec<String>, DbErr> {
+        let _span = db_span("find_city_names");
         let query_result = city::Entity::find()
             .select_only()
             .column(city::Column::Name)
@@ -219,7 +762,50 @@ impl DbProvider {
         Ok(query_result.iter().map(|row| row.name.to_owned()).collect())
     }
 
+    pub async fn find_all_cities(&self) -> Result<Vec<CityModel>, DbErr> {
+        let _span = db_span("find_all_cities");
+        city::Entity::find()
+            .order_by(city::Column::Name, Order::Asc)
+            .all(&self.db_con)
+            .await
+    }
+
+    pub async fn find_city_by_name(&self, name: &str) -> Result<Option<CityModel>, DbErr> {
+        let _span = db_span("find_city_by_name");
+        city::Entity::find()
+            .filter(city::Column::Name.eq(name))
+            .one(&self.db_con)
+            .await
+    }
+
+    pub async fn add_city(&self, name: &str) -> Result<CityModel, DbErr> {
+        let _span = db_span("add_city");
+        let city = city::ActiveModel {
+            id: NotSet,
+            name: Set(name.to_owned()),
+            status: Set(String::from("on")),
+        };
+        city.insert(&self.db_con).await
+    }
+
+    pub async fn find_city_by_id(&self, id: i64) -> Result<Option<CityModel>, DbErr> {
+        let _span = db_span("find_city_by_id");
+        city::Entity::find_by_id(id).one(&self.db_con).await
+    }
+
+    pub async fn update_city_status(
+        &self,
+        model: &CityModel,
+        status: &str,
+    ) -> Result<CityModel, DbErr> {
+        let _span = db_span("update_city_status");
+        let mut mutable: city::ActiveModel = model.to_owned().into();
+        mutable.status = Set(status.to_owned());
+        mutable.update(&self.db_con).await
+    }
+
     pub async fn all_user_profiles(&self, user_id: i64) -> Result<Vec<ProfileModel>, DbErr> {
+        let _span = db_span("all_user_profiles");
         info!("User [{}] fetches all his profiles", user_id);
         profile::Entity::find()
             .filter(profile::Column::Status.eq("active"))
@@ -233,6 +819,7 @@ impl DbProvider {
         &self,
         profile_id: &Uuid,
     ) -> Result<Vec<(CommentModel, Option<UserModel>)>, DbErr> {
+        let _span = db_span("all_profile_comments");
         info!("Fetching all profile [{}] comments", profile_id);
         comment::Entity::find()
             .find_also_related(user::Entity)
@@ -248,6 +835,7 @@ impl DbProvider {
         profile_id: &Uuid,
         user_id: &i64,
     ) -> Result<Option<CommentModel>, DbErr> {
+        let _span = db_span("find_comment_by_profile_user_ids");
         comment::Entity::find()
             .filter(comment::Column::Status.is_in(["approved", "in_review"]))
             .filter(comment::Column::ProfileId.eq(profile_id.clone()))
@@ -257,11 +845,62 @@ impl DbProvider {
             .await
     }
 
+    pub async fn find_comment_in_review_by(
+        &self,
+        id: &Uuid,
+    ) -> Result<Option<CommentModel>, DbErr> {
+        let _span = db_span("find_comment_in_review_by");
+        comment::Entity::find_by_id(id.to_owned())
+            .filter(comment::Column::Status.eq("in_review"))
+            .one(&self.db_con)
+            .await
+    }
+
+    pub async fn comments_in_review_pagination(
+        &self,
+        number_of_entities: u64,
+        page_opt: &Option<u64>,
+    ) -> Result<(TotalPages, Vec<(CommentModel, Option<UserModel>)>), DbErr> {
+        let _span = db_span("comments_in_review_pagination");
+        let query = comment::Entity::find()
+            .find_also_related(user::Entity)
+            .filter(comment::Column::Status.eq("in_review"))
+            .order_by(comment::Column::CreatedAt, Order::Asc)
+            .paginate(&self.db_con, number_of_entities);
+
+        let total_pages = query.num_pages().await?;
+        let query_page = page_opt.map(|f| if f > 0 { f - 1 } else { f }).unwrap_or(0);
+        info!(
+            "Fetching comments in review page: [{}]. Total num of pages: [{}]",
+            query_page + 1,
+            total_pages
+        );
+        let comments = query.fetch_page(query_page).await;
+        comments.map(|data| (total_pages, data))
+    }
+
+    pub async fn review_comment(
+        &self,
+        comment_model: &CommentModel,
+        status: &str,
+        reason_opt: Option<&str>,
+        moderator_id: i64,
+    ) -> Result<CommentModel, DbErr> {
+        let _span = db_span("review_comment");
+        let mut mutable_comment: comment::ActiveModel = comment_model.to_owned().into();
+        mutable_comment.status = Set(status.to_owned());
+        mutable_comment.review_reason = Set(reason_opt.map(|f| f.to_owned()));
+        mutable_comment.reviewed_at = Set(Some(Utc::now().naive_utc()));
+        mutable_comment.reviewed_by = Set(Some(moderator_id));
+        mutable_comment.update(&self.db_con).await
+    }
+
     pub async fn search_profiles(
         &self,
         text: &str,
         limit: i64,
     ) -> Result<Vec<ProfileModel>, DbErr> {
+        let _span = db_span("search_profiles");
         info!("User search for profiles: {}", text);
 
         if text.is_empty() {
@@ -288,6 +927,7 @@ impl DbProvider {
     pub async fn find_latest_active_profile_from_every_city(
         &self,
     ) -> Result<Vec<ProfileModel>, DbErr> {
+        let _span = db_span("find_latest_active_profile_from_every_city");
         let raw_query = "SELECT *
         FROM (
             SELECT *,
@@ -314,6 +954,7 @@ impl DbProvider {
         page_opt: &Option<u64>,
         city_opt: &Option<String>,
     ) -> Result<(TotalPages, Vec<ProfileModel>), DbErr> {
+        let _span = db_span("profiles_pagination");
         let query = profile::Entity::find()
             .filter(profile::Column::Status.eq("active"))
             .apply_if(city_opt.to_owned(), |query, v| {
@@ -337,6 +978,7 @@ impl DbProvider {
     }
 
     pub async fn find_any_active_profile_photo(&self) -> Result<Option<ProfilePhotoModel>, DbErr> {
+        let _span = db_span("find_any_active_profile_photo");
         profile_photo::Entity::find()
             .filter(profile_photo::Column::Status.eq("active"))
             .order_by(profile_photo::Column::CreatedAt, Order::Desc)
@@ -345,6 +987,7 @@ impl DbProvider {
     }
 
     pub async fn increase_view_for_profiles(&self, profile_ids: &Vec<Uuid>) -> Result<u64, DbErr> {
+        let _span = db_span("increase_view_for_profiles");
         if profile_ids.is_empty() {
             Ok(0)
         } else {
@@ -365,6 +1008,7 @@ impl DbProvider {
         &self,
         profile_ids: &Vec<Uuid>,
     ) -> Result<HashMap<Uuid, Option<ProfilePhotoModel>>, DbErr> {
+        let _span = db_span("find_first_profile_photos_for");
         let profile_photo_str_ids: Vec<String> =
             profile_ids.clone().iter().map(|f| f.to_string()).collect();
 
@@ -372,7 +1016,7 @@ impl DbProvider {
             Ok(HashMap::new())
         } else {
             let query = format!(
-                "SELECT DISTINCT ON (profile_id) * FROM profile_photo WHERE status = 'active' and profile_id IN ({}) order by profile_id, created_at;",
+                "SELECT DISTINCT ON (profile_id) * FROM profile_photo WHERE status = 'active' and profile_id IN ({}) order by profile_id, position, id;",
                 profile_photo_str_ids.iter().map(|id| format!("'{}'", id)).collect::<Vec<String>>().join(",")
             );
 
@@ -409,6 +1053,7 @@ impl DbProvider {
         profile_model: &ProfileModel,
         profole_photos: &Vec<ProfilePhotoModel>,
     ) -> Result<(), DbErr> {
+        let _span = db_span("delete_profile_and_photos");
         let mut mutable_profile: profile::ActiveModel = profile_model.to_owned().into();
         mutable_profile.status = Set("deleted".to_owned());
 
@@ -423,15 +1068,327 @@ impl DbProvider {
         Ok(())
     }
 
+    /// Row count of every status value in the table
+    pub async fn count_by_status(&self, table: StatusTable) -> Result<Vec<(String, i64)>, DbErr> {
+        let _span = db_span("count_by_status");
+        let query_result = match table {
+            StatusTable::User => {
+                self.count_entity_by_status::<user::Entity>(user::Column::Status)
+                    .await?
+            }
+            StatusTable::Profile => {
+                self.count_entity_by_status::<profile::Entity>(profile::Column::Status)
+                    .await?
+            }
+            StatusTable::ProfilePhoto => {
+                self.count_entity_by_status::<profile_photo::Entity>(profile_photo::Column::Status)
+                    .await?
+            }
+            StatusTable::Comment => {
+                self.count_entity_by_status::<comment::Entity>(comment::Column::Status)
+                    .await?
+            }
+            StatusTable::Job => {
+                self.count_entity_by_status::<job::Entity>(job::Column::Status)
+                    .await?
+            }
+        };
+
+        Ok(query_result
+            .into_iter()
+            .map(|row| (row.status, row.count))
+            .collect())
+    }
+
+    async fn count_entity_by_status<E: EntityTrait>(
+        &self,
+        status_column: E::Column,
+    ) -> Result<Vec<StatusCountResult>, DbErr> {
+        E::find()
+            .select_only()
+            .column_as(status_column, "status")
+            .column_as(Expr::col(status_column).count(), "count")
+            .group_by(status_column)
+            .order_by_asc(status_column)
+            .into_model::<StatusCountResult>()
+            .all(&self.db_con)
+            .await
+    }
+
     pub async fn delete_comment(&self, comment_model: &CommentModel) -> Result<(), DbErr> {
+        let _span = db_span("delete_comment");
         let mut mutable_comment: comment::ActiveModel = comment_model.to_owned().into();
         mutable_comment.status = Set("removed".to_owned());
 
         mutable_comment.update(&self.db_con).await.map(|_| ())
     }
+
+    pub async fn add_job(
+        &self,
+        kind: &str,
+        target_id: i64,
+        max_attempts: i32,
+    ) -> Result<JobModel, DbErr> {
+        let _span = db_span("add_job");
+        new_job(kind, target_id, max_attempts)
+            .insert(&self.db_con)
+            .await
+    }
+
+    /// Locks the next due job. `SKIP LOCKED` keeps parallel workers off the same row
+    pub async fn take_next_job(&self) -> Result<Option<JobModel>, DbErr> {
+        let _span = db_span("take_next_job");
+        let now = Utc::now().naive_utc();
+        job::Entity::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "UPDATE job SET status = 'running', attempts = attempts + 1, locked_at = $1, updated_at = $1
+                WHERE id = (
+                    SELECT id FROM job WHERE status = 'pending' AND run_at <= $1
+                    ORDER BY run_at, id LIMIT 1 FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *;",
+                [now.into()],
+            ))
+            .one(&self.db_con)
+            .await
+    }
+
+    pub async fn complete_job(&self, job_model: &JobModel) -> Result<JobModel, DbErr> {
+        let _span = db_span("complete_job");
+        let mut mutable_job: job::ActiveModel = job_model.to_owned().into();
+        mutable_job.status = Set("done".to_owned());
+        mutable_job.locked_at = Set(None);
+        mutable_job.updated_at = Set(Utc::now().naive_utc());
+
+        mutable_job.update(&self.db_con).await
+    }
+
+    /// Without `retry_at_opt` the job is failed for good
+    pub async fn fail_job(
+        &self,
+        job_model: &JobModel,
+        error: &str,
+        retry_at_opt: Option<DateTime>,
+    ) -> Result<JobModel, DbErr> {
+        let _span = db_span("fail_job");
+        let mut mutable_job: job::ActiveModel = job_model.to_owned().into();
+        mutable_job.last_error = Set(Some(error.to_owned()));
+        mutable_job.locked_at = Set(None);
+        mutable_job.updated_at = Set(Utc::now().naive_utc());
+        match retry_at_opt {
+            Some(retry_at) => {
+                mutable_job.status = Set("pending".to_owned());
+                mutable_job.run_at = Set(retry_at);
+            }
+            None => mutable_job.status = Set("failed".to_owned()),
+        }
+
+        mutable_job.update(&self.db_con).await
+    }
+
+    /// Jobs of a worker that died in the middle go back to the queue. The lost run counts as
+    /// an attempt, so jobs that kill their worker every time do not loop forever: the ones
+    /// without attempts left are failed and returned
+    pub async fn requeue_stale_jobs(
+        &self,
+        locked_before: DateTime,
+        error: &str,
+    ) -> Result<(u64, Vec<JobModel>), DbErr> {
+        let _span = db_span("requeue_stale_jobs");
+        let now = Utc::now().naive_utc();
+        let failed_jobs = job::Entity::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "UPDATE job SET status = 'failed', locked_at = NULL, last_error = $2, updated_at = $3
+                WHERE status = 'running' AND locked_at < $1 AND attempts >= max_attempts
+                RETURNING *;",
+                [locked_before.into(), error.into(), now.into()],
+            ))
+            .all(&self.db_con)
+            .await?;
+
+        let requeued = job::Entity::update_many()
+            .col_expr(job::Column::Status, Expr::value("pending"))
+            .col_expr(job::Column::LockedAt, Expr::value(Option::<DateTime>::None))
+            .col_expr(job::Column::LastError, Expr::value(error))
+            .col_expr(job::Column::UpdatedAt, Expr::value(now))
+            .filter(job::Column::Status.eq("running"))
+            .filter(job::Column::LockedAt.lt(locked_before))
+            .exec(&self.db_con)
+            .await?
+            .rows_affected;
+        Ok((requeued, failed_jobs))
+    }
+}
+
+fn new_job(kind: &str, target_id: i64, max_attempts: i32) -> job::ActiveModel {
+    let now = Utc::now().naive_utc();
+    job::ActiveModel {
+        id: NotSet,
+        kind: Set(kind.to_owned()),
+        target_id: Set(target_id),
+        status: Set(String::from("pending")),
+        attempts: Set(0),
+        max_attempts: Set(max_attempts),
+        last_error: Set(None),
+        run_at: Set(now),
+        locked_at: Set(None),
+        created_at: Set(now),
+        updated_at: Set(now),
+    }
 }
 
 #[derive(Debug, FromQueryResult)]
 struct NameResult {
     name: String,
 }
+
+/// Tables with a `status` column
+#[derive(Debug, Clone, Copy)]
+pub enum StatusTable {
+    User,
+    Profile,
+    ProfilePhoto,
+    Comment,
+    Job,
+}
+
+impl StatusTable {
+    pub const ALL: [StatusTable; 5] = [
+        StatusTable::User,
+        StatusTable::Profile,
+        StatusTable::ProfilePhoto,
+        StatusTable::Comment,
+        StatusTable::Job,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatusTable::User => "user",
+            StatusTable::Profile => "profile",
+            StatusTable::ProfilePhoto => "profile_photo",
+            StatusTable::Comment => "comment",
+            StatusTable::Job => "job",
+        }
+    }
+}
+
+#[derive(Debug, FromQueryResult)]
+struct StatusCountResult {
+    status: String,
+    count: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    use super::*;
+
+    fn profile_photo(id: i64, position: i32) -> ProfilePhotoModel {
+        ProfilePhotoModel {
+            id,
+            created_at: Utc::now().naive_utc(),
+            status: "processing".to_owned(),
+            file_name: "photo.jpg".to_owned(),
+            profile_id: Uuid::nil(),
+            size: 100,
+            thumbnail_file_name: Some("photo_thumbnail.jpg".to_owned()),
+            card_file_name: Some("photo_card.jpg".to_owned()),
+            original_file_name: Some("photo_original.jpg".to_owned()),
+            position,
+            deleted_at: None,
+        }
+    }
+
+    fn job(id: i64, status: &str, attempts: i32) -> JobModel {
+        let now = Utc::now().naive_utc();
+        JobModel {
+            id,
+            kind: "process_photo".to_owned(),
+            target_id: 7,
+            status: status.to_owned(),
+            attempts,
+            max_attempts: 3,
+            last_error: None,
+            run_at: now,
+            locked_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    async fn add_photo(db_provider: &DbProvider) -> Result<(ProfilePhotoModel, JobModel), DbErr> {
+        db_provider
+            .add_profile_photo(
+                &Uuid::nil(),
+                "photo.jpg",
+                100,
+                "photo_thumbnail.jpg",
+                "photo_card.jpg",
+                "photo_original.jpg",
+                "process_photo",
+                3,
+            )
+            .await
+    }
+
+    #[actix_rt::test]
+    async fn photo_and_its_job_are_added_in_one_transaction() {
+        let db_con = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[profile_photo(6, 2)]])
+            .append_query_results([[profile_photo(7, 3)]])
+            .append_query_results([[job(1, "pending", 0)]])
+            .into_connection();
+        let db_provider = DbProvider::new(db_con);
+
+        let (profile_photo, job) = add_photo(&db_provider).await.unwrap();
+        assert_eq!(profile_photo.id, 7);
+        assert_eq!(job.target_id, 7);
+
+        let transaction_log = db_provider.db_con.into_transaction_log();
+        assert_eq!(transaction_log.len(), 1);
+        let transaction = format!("{:?}", transaction_log[0]);
+        assert!(transaction.contains(r#"sql: "BEGIN""#));
+        assert!(transaction.contains(r#"INSERT INTO \"profile_photo\""#));
+        assert!(transaction.contains(r#"INSERT INTO \"job\""#));
+        assert!(transaction.contains(r#"sql: "COMMIT""#));
+    }
+
+    #[actix_rt::test]
+    async fn photo_is_rolled_back_when_its_job_is_not_added() {
+        let db_con = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<ProfilePhotoModel>::new()])
+            .append_query_results([[profile_photo(7, 0)]])
+            .append_query_errors([DbErr::Custom("job table is locked".to_owned())])
+            .into_connection();
+        let db_provider = DbProvider::new(db_con);
+
+        assert!(add_photo(&db_provider).await.is_err());
+
+        let transaction_log = db_provider.db_con.into_transaction_log();
+        assert_eq!(transaction_log.len(), 1);
+        let transaction = format!("{:?}", transaction_log[0]);
+        assert!(transaction.contains(r#"sql: "ROLLBACK""#));
+        assert!(!transaction.contains(r#"sql: "COMMIT""#));
+    }
+
+    #[actix_rt::test]
+    async fn next_job_is_locked_and_counted_as_an_attempt() {
+        let db_con = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[job(1, "running", 1)]])
+            .append_query_results([Vec::<JobModel>::new()])
+            .into_connection();
+        let db_provider = DbProvider::new(db_con);
+
+        let job = db_provider.take_next_job().await.unwrap().unwrap();
+        assert_eq!((job.id, job.status.as_str()), (1, "running"));
+        assert_eq!(db_provider.take_next_job().await.unwrap(), None);
+
+        let transaction_log = db_provider.db_con.into_transaction_log();
+        let statement = format!("{:?}", transaction_log[0]);
+        assert!(statement.contains("attempts = attempts + 1"));
+        assert!(statement.contains("FOR UPDATE SKIP LOCKED"));
+    }
+}
@@ -1,15 +1,21 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
 mod city;
+mod comment;
+mod job;
 mod profile;
 mod profile_photo;
+mod refresh_token;
+mod session;
 mod user;
-mod comment;
 
 mod db_provider;
 
-pub use db_provider::DbProvider;
+pub use city::Model as CityModel;
+pub use comment::Model as CommentModel;
+pub use db_provider::{DbProvider, StatusTable};
+pub use job::Model as JobModel;
 pub use profile::Model as ProfileModel;
 pub use profile_photo::Model as ProfilePhotoModel;
+pub use session::Model as SessionModel;
 pub use user::Model as UserModel;
-pub use comment::Model as CommentModel;
@@ -12,6 +12,11 @@ pub struct Model {
     pub file_name: String,
     pub profile_id: Uuid,
     pub size: i64,
+    pub thumbnail_file_name: Option<String>,
+    pub card_file_name: Option<String>,
+    pub original_file_name: Option<String>,
+    pub position: i32,
+    pub deleted_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
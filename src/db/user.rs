@@ -11,6 +11,8 @@ pub struct Model {
     pub name: String,
     pub email: String,
     pub provider: Option<String>,
+    pub role: String,
+    pub status: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,79 @@
+pub mod config;
+pub mod db;
+pub mod logging;
+pub mod web_api;
+
+use std::{env, fs, io::BufReader, sync::Arc};
+
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use sea_orm::{Database, DbConn, DbErr};
+
+use crate::{
+    config::{Config, TlsConfig},
+    web_api::{OriginalPhotoStorage, PhotoStorage},
+};
+
+rust_i18n::i18n!("locales");
+
+pub async fn establish_connection(conf: &Config) -> Result<DbConn, DbErr> {
+    let mut db_con = Database::connect(&conf.database_url).await?;
+    db_con.set_metric_callback(|info| {
+        web_api::observe_db_query(&info.statement.sql, info.elapsed, info.failed)
+    });
+    Ok(db_con)
+}
+
+/// Public and original storages from `PHOTO_STORAGE`
+pub fn init_photo_storages(conf: &Config) -> (Arc<dyn PhotoStorage>, OriginalPhotoStorage) {
+    // local folders are used only by fs storage, s3 keeps photos in buckets
+    let is_fs_photo_storage = conf.photo_storage == web_api::PHOTO_STORAGE_FS;
+    let all_photos_os_folder_opt =
+        is_fs_photo_storage.then(|| os_folder_path(&conf.all_photos_folder_name));
+    let original_photos_os_folder_opt =
+        is_fs_photo_storage.then(|| os_folder_path(&conf.original_photos_folder_name));
+
+    (
+        web_api::init_photo_storage(conf, &all_photos_os_folder_opt),
+        web_api::init_original_photo_storage(conf, &original_photos_os_folder_opt),
+    )
+}
+
+/// Rustls server config from pem files. Key may be pkcs8, rsa or ec
+pub fn load_tls_config(tls: &TlsConfig) -> Result<ServerConfig, String> {
+    let cert_file = fs::File::open(&tls.cert_path)
+        .map_err(|err| format!("TLS_CERT_PATH {} is not readable: {}", &tls.cert_path, err))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .map_err(|err| format!("TLS_CERT_PATH {} is invalid: {}", &tls.cert_path, err))?;
+
+    let key_file = fs::File::open(&tls.key_path)
+        .map_err(|err| format!("TLS_KEY_PATH {} is not readable: {}", &tls.key_path, err))?;
+    let key_opt = rustls_pemfile::read_all(&mut BufReader::new(key_file))
+        .map_err(|err| format!("TLS_KEY_PATH {} is invalid: {}", &tls.key_path, err))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        });
+    let key = key_opt.ok_or(format!("TLS_KEY_PATH {} has no private key", &tls.key_path))?;
+
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs.into_iter().map(Certificate).collect(), key)
+        .map_err(|err| format!("TLS certificate does not match the key: {}", err))
+}
+
+fn os_folder_path(folder_name: &str) -> String {
+    let mut new_file_path = env::current_exe().unwrap();
+    // remove binary name
+    new_file_path.pop();
+    // add global_folder
+    new_file_path.push(folder_name);
+    if !new_file_path.exists() {
+        fs::create_dir_all(&new_file_path).unwrap();
+    }
+
+    new_file_path.to_str().unwrap().to_owned()
+}
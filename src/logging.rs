@@ -0,0 +1,221 @@
+use std::io::Write;
+
+use chrono::{SecondsFormat, Utc};
+use env_logger::{fmt::Formatter, Builder};
+use log::{LevelFilter, Record};
+use serde_json::json;
+
+use crate::{config::Config, web_api};
+
+static REDACTED_EMAIL: &str = "[email]";
+static REDACTED_PHONE: &str = "[phone]";
+// shorter digit runs are ids, counts and dates
+static MIN_PHONE_DIGITS: usize = 10;
+// E.164 limit, longer runs are ids
+static MAX_PHONE_DIGITS: usize = 15;
+// `) ` in `(050) 123-45-67`
+static MAX_PHONE_SEPARATORS: usize = 2;
+
+/// Web server logger. Every line gets the request and user ids, emails and phones are masked
+pub fn init_logger(conf: &Config) {
+    let mut builder = Builder::new();
+    // LOG_LEVEL can turn sql back on with `sqlx::query=info`
+    builder
+        .filter_level(LevelFilter::Info)
+        .filter_module("sqlx::query", LevelFilter::Off)
+        .parse_filters(&conf.log_level);
+    if conf.log_format == "json" {
+        builder.format(write_json);
+    } else {
+        builder.format(write_text);
+    }
+    builder.init();
+}
+
+fn write_json(buf: &mut Formatter, record: &Record) -> std::io::Result<()> {
+    let line = json!({
+        "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": redact(&record.args().to_string()),
+        "request_id": web_api::current_request_id(),
+        "trace_id": web_api::current_trace_id(),
+        "user_id": web_api::current_user_id(),
+    });
+    writeln!(buf, "{}", line)
+}
+
+fn write_text(buf: &mut Formatter, record: &Record) -> std::io::Result<()> {
+    let request_id = web_api::current_request_id().unwrap_or("-".to_string());
+    let user_id = web_api::current_user_id()
+        .map(|id| id.to_string())
+        .unwrap_or("-".to_string());
+    writeln!(
+        buf,
+        "[{} {} {} request={} user={}] {}",
+        Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        record.level(),
+        record.target(),
+        request_id,
+        user_id,
+        redact(&record.args().to_string())
+    )
+}
+
+/// Masks emails and phone numbers, the only PII that lands in messages
+pub fn redact(message: &str) -> String {
+    let chars: Vec<char> = message.chars().collect();
+    let mut out = String::with_capacity(message.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some(end) = email_end(&chars, i) {
+            out.push_str(REDACTED_EMAIL);
+            i = end;
+        } else if let Some(end) = phone_end(&chars, i) {
+            out.push_str(REDACTED_PHONE);
+            i = end;
+        } else {
+            out.push(chars[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+fn is_email_local_char(c: char) -> bool {
+    c.is_alphanumeric() || ".+_%-".contains(c)
+}
+
+fn is_email_domain_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '.' || c == '-'
+}
+
+/// End of an email starting at `start`, which must be the beginning of a word
+fn email_end(chars: &[char], start: usize) -> Option<usize> {
+    if start > 0 && is_email_local_char(chars[start - 1]) {
+        return None;
+    }
+    let at = start
+        + chars[start..]
+            .iter()
+            .take_while(|c| is_email_local_char(**c))
+            .count();
+    if at == start || chars.get(at) != Some(&'@') {
+        return None;
+    }
+    let domain_len = chars[at + 1..]
+        .iter()
+        .take_while(|c| is_email_domain_char(**c))
+        .count();
+    let domain: String = chars[at + 1..at + 1 + domain_len].iter().collect();
+    let domain = domain.trim_end_matches('.');
+    if !domain.contains('.') {
+        return None;
+    }
+    Some(at + 1 + domain.chars().count())
+}
+
+// digits inside uuids and hashes are not phones
+fn is_id_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+fn is_phone_separator(c: char) -> bool {
+    c == ' ' || c == '-' || c == '(' || c == ')'
+}
+
+// `2026-10-18 12:09` is a date and a time
+fn is_phone_end(chars: &[char], end: usize) -> bool {
+    match chars.get(end) {
+        None => true,
+        Some(':') => false,
+        Some('.') => !chars.get(end + 1).is_some_and(|c| c.is_ascii_digit()),
+        Some(c) => !is_id_char(*c),
+    }
+}
+
+/// End of a phone: an optional leading plus and MIN_PHONE_DIGITS to MAX_PHONE_DIGITS digits,
+/// which spaces, dashes and brackets may split, like `+38 (050) 123-45-67`
+fn phone_end(chars: &[char], start: usize) -> Option<usize> {
+    if start > 0 && is_id_char(chars[start - 1]) {
+        return None;
+    }
+    let digits_start = if chars[start] == '+' {
+        start + 1
+    } else {
+        start
+    };
+    if !chars
+        .get(digits_start)
+        .is_some_and(|c| c.is_ascii_digit() || *c == '(')
+    {
+        return None;
+    }
+
+    let mut digits = 0;
+    let mut separators = 0;
+    let mut end = digits_start;
+    for (i, c) in chars.iter().enumerate().skip(digits_start) {
+        if c.is_ascii_digit() {
+            digits += 1;
+            separators = 0;
+            end = i + 1;
+        } else if is_phone_separator(*c) && separators < MAX_PHONE_SEPARATORS {
+            separators += 1;
+        } else {
+            break;
+        }
+    }
+    ((MIN_PHONE_DIGITS..=MAX_PHONE_DIGITS).contains(&digits) && is_phone_end(chars, end))
+        .then_some(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_emails() {
+        assert_eq!(
+            redact("Sign in of [anna.k+board@mail.example.com]."),
+            "Sign in of [[email]]."
+        );
+        assert_eq!(
+            redact("user@localhost is not an email"),
+            "user@localhost is not an email"
+        );
+    }
+
+    #[test]
+    fn masks_phones_with_separators() {
+        for phone in [
+            "0501234567",
+            "+380501234567",
+            "+38 (050) 123-45-67",
+            "050-123-45-67",
+            "050 123 45 67",
+            "(050)1234567",
+        ] {
+            assert_eq!(
+                redact(&format!("Phone: [{}].", phone)),
+                "Phone: [[phone]].",
+                "{}",
+                phone
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_ids_dates_and_counts() {
+        for message in [
+            "Profile [76bb4467-21cb-4887-80a4-944e6fff2e83] was published",
+            "Profile [12345678-1234-1234-1234-123456789012] was published",
+            "Job 1234567890123456789 is taken",
+            "Purged 12345 files",
+            "Started at 2026-10-18 12:09:33",
+            "Took 1234567890.5 ms",
+        ] {
+            assert_eq!(redact(message), message);
+        }
+    }
+}
@@ -1,105 +1,254 @@
-use std::env;
+use std::time::{Duration, Instant};
 
 use actix_files::Files;
-use actix_web::{middleware, web, App, HttpServer};
-use env_logger::Builder;
-use sea_orm::{Database, DbConn, DbErr};
-use std::fs;
+use actix_rt::signal;
+use actix_web::{dev::Service, middleware, web, App, HttpServer};
+use futures::future;
 
-mod config;
-mod db;
-mod web_api;
+use log::{error, info};
 
-use log::info;
-
-rust_i18n::i18n!("locales");
-
-use crate::{config::Config, db::DbProvider};
-
-async fn establish_connection(conf: &Config) -> Result<DbConn, DbErr> {
-    Database::connect(&conf.database_url).await
-}
+use rust_dating_board::{
+    config::Config, db::DbProvider, establish_connection, init_photo_storages, load_tls_config,
+    logging, web_api,
+};
 
 #[actix_web::main]
 async fn main() {
-    Builder::new()
-        .filter_level(log::LevelFilter::Info)
-        .filter_module("sqlx::query", log::LevelFilter::Off)
-        .init();
+    let conf = Config::init();
+    logging::init_logger(&conf);
 
     rust_i18n::set_locale("uk");
 
-    let conf = Config::init();
     let db_con = establish_connection(&conf).await.unwrap();
-    let provider = DbProvider::new(db_con);
+    let provider = web::Data::new(DbProvider::new(db_con));
+
+    let addr = format!("{}:{}", &conf.bind_address, conf.bind_port);
+    let tls_config_opt = conf.tls.as_ref().map(|tls| {
+        load_tls_config(tls)
+            .inspect_err(|err| error!("Can not load TLS config: [{}]", err))
+            .unwrap_or_else(|err| panic!("Can not load TLS config: {}", err))
+    });
+    let shutdown_timeout = Duration::from_secs(conf.shutdown_timeout_seconds);
+    let server_workers = conf.server_workers;
+    let server_keep_alive = Duration::from_secs(conf.server_keep_alive_seconds);
 
-    let port = std::env::var("PORT").unwrap_or("8080".to_string());
-    let addr = format!("localhost:{}", &port);
+    let (photo_storage, original_photo_storage) = init_photo_storages(&conf);
+    let original_photo_storage = web::Data::new(original_photo_storage);
 
-    let all_photos_os_folder = all_photos_folder_path(&conf);
+    let watermark = web::Data::new(web_api::Watermark::new(&conf));
+    // one store for all workers, otherwise every worker would count on its own
+    let rate_limiter = web::Data::new(web_api::RateLimiter::new(&conf));
+    let captcha = web::Data::new(web_api::Captcha::new(&conf));
 
-    let server = HttpServer::new(move || {
+    let job_context = web_api::JobContext {
+        db_provider: provider.clone(),
+        photo_storage: web::Data::from(photo_storage.clone()),
+        original_photo_storage: original_photo_storage.clone(),
+        watermark: watermark.clone(),
+    };
+    let background_workers = web_api::BackgroundWorkers::default();
+    if let Some(otel) = &conf.otel {
+        web_api::start_trace_export(&background_workers, otel);
+    }
+    web_api::start_job_workers(&background_workers, conf.job_workers, job_context.clone());
+    web_api::start_cleanup_task(
+        &background_workers,
+        job_context,
+        conf.cleanup_interval_hours,
+        conf.cleanup_retention_days,
+        conf.cleanup_dry_run,
+    );
+
+    let mut server = HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(provider.clone()))
+            .app_data(provider.clone())
             .app_data(web::Data::new(conf.clone()))
+            .app_data(web::Data::from(photo_storage.clone()))
+            .app_data(original_photo_storage.clone())
+            .app_data(watermark.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(captcha.clone())
+            .wrap(web_api::SecurityHeaders::new(&conf))
+            // pages rendered for a new visitor made a csrf nonce, it goes to the cookie
+            .wrap_fn(|request, service| {
+                let response_future = service.call(request);
+                async move {
+                    let mut response = response_future.await?;
+                    web_api::set_new_csrf_cookie(&mut response);
+                    Ok(response)
+                }
+            })
+            .wrap_fn(|request, service| {
+                let started_at = Instant::now();
+                let response_future = service.call(request);
+                async move {
+                    let response = response_future.await?;
+                    web_api::observe_request(&response, started_at);
+                    Ok(response)
+                }
+            })
+            // outermost, so logs and spans of every other layer carry the request id
+            .wrap_fn(|request, service| {
+                let request_id = web_api::request_id(&request);
+                let traceparent = web_api::traceparent(&request);
+                let started_at = Instant::now();
+                let response_future = service.call(request);
+                let context_request_id = request_id.clone();
+                let traced_future = web_api::in_trace(traceparent, async move {
+                    let mut span = web_api::start_span(web_api::SpanKind::Server, "HTTP");
+                    let mut response = web_api::in_span(&span, response_future).await?;
+                    web_api::describe_server_span(&mut span, &response);
+                    web_api::set_request_id_header(&mut response, &request_id);
+                    info!(
+                        "{} {} {} {}ms",
+                        response.request().method(),
+                        response.request().path(),
+                        response.status().as_u16(),
+                        started_at.elapsed().as_millis()
+                    );
+                    Ok(response)
+                });
+                web_api::in_request_context(context_request_id, traced_future)
+            })
+            .route("/healthz", web::get().to(web_api::healthz))
+            .route("/readyz", web::get().to(web_api::readyz))
+            .route("/metrics", web::get().to(web_api::metrics_endpoint))
             .route("/", web::get().to(web_api::index_page))
             .route("/404", web::get().to(web_api::p404_page))
             .route("/add_profile", web::get().to(web_api::add_profile_page))
             .route("/edit_profile", web::get().to(web_api::edit_profile_page))
             .route("/view_profile", web::get().to(web_api::view_profile_page))
             .route("/sitemap.xml", web::get().to(web_api::sitemap))
+            .service(
+                web::resource("/add_or_edit_profile")
+                    .wrap(web_api::RateLimit::html(web_api::RATE_LIMIT_PROFILE))
+                    .route(web::post().to(web_api::add_or_edit_profile_post)),
+            )
+            .service(
+                web::resource("/comment/add")
+                    .wrap(web_api::RateLimit::html(web_api::RATE_LIMIT_COMMENT))
+                    .route(web::post().to(web_api::add_comment)),
+            )
             .route(
-                "/add_or_edit_profile",
-                web::post().to(web_api::add_or_edit_profile_post),
+                "/moderation/comments",
+                web::get().to(web_api::moderation_comments_page),
             )
-            .route("/comment/add", web::post().to(web_api::add_comment))
             .route("/robots.txt", web::get().to(web_api::robots_txt))
+            .route("/sessions", web::get().to(web_api::sessions_page))
+            .route("/admin/users", web::get().to(web_api::admin_users_page))
+            .route("/admin/profiles", web::get().to(web_api::admin_profiles_page))
+            .route("/admin/profile", web::get().to(web_api::admin_profile_page))
+            .route("/admin/cities", web::get().to(web_api::admin_cities_page))
+            .service(
+                web::resource("/admin/user/role")
+                    .route(web::post().to(web_api::admin_user_role_endpoint)),
+            )
+            .service(
+                web::resource("/admin/profile/delete")
+                    .route(web::post().to(web_api::admin_profile_delete_endpoint)),
+            )
+            .service(
+                web::resource("/admin/profile_photo/delete")
+                    .route(web::post().to(web_api::admin_profile_photo_delete_endpoint)),
+            )
+            .service(
+                web::resource("/admin/city/toggle")
+                    .route(web::post().to(web_api::admin_city_toggle_endpoint)),
+            )
             .service(
                 web::resource("/profile/delete")
                     .route(web::post().to(web_api::delete_profile_endpoint)),
             )
             .service(
                 web::resource("/profile_photo/upload")
+                    .wrap(web_api::RateLimit::json(web_api::RATE_LIMIT_PHOTO_UPLOAD))
                     .route(web::post().to(web_api::add_profile_photo_endpoint)),
             )
             .service(
                 web::resource("/profile_photo/delete")
                     .route(web::post().to(web_api::delete_profile_photo_endpoint)),
             )
+            .service(
+                web::resource("/profile_photo/reorder")
+                    .route(web::post().to(web_api::reorder_profile_photos_endpoint)),
+            )
+            .service(
+                web::resource("/profile_photo/status")
+                    .route(web::get().to(web_api::profile_photo_status_endpoint)),
+            )
             .service(
                 web::resource("/comment/delete")
                     .route(web::post().to(web_api::delete_comment_endpoint)),
             )
+            .service(
+                web::resource("/moderation/comment/approve")
+                    .route(web::post().to(web_api::approve_comment_endpoint)),
+            )
+            .service(
+                web::resource("/moderation/comment/reject")
+                    .route(web::post().to(web_api::reject_comment_endpoint)),
+            )
+            .service(
+                web::resource("/session/revoke")
+                    .route(web::post().to(web_api::revoke_session_endpoint)),
+            )
+            .service(
+                web::resource("/session/revoke_all")
+                    .route(web::post().to(web_api::revoke_all_sessions_endpoint)),
+            )
             .service(
                 web::resource("/sign_in/google")
+                    .wrap(web_api::RateLimit::html(web_api::RATE_LIMIT_SIGN_IN))
                     .route(web::post().to(web_api::google_sign_in_endpoint)),
             )
             .service(web::resource("/sign_out").route(web::get().to(web_api::sign_out_endpoint)))
+            .service(web::scope("/api/v1").configure(web_api::api_v1_routes))
             // static services
             .service(
                 web::scope("")
                     .wrap(middleware::DefaultHeaders::new().add(("Cache-Control", "max-age=86400")))
                     .service(Files::new("/static", "static").index_file("not_found"))
-                    .service(Files::new("/photos", &all_photos_os_folder).index_file("not_found")),
+                    .route(
+                        "/photos/{profile_id}/{file_name}",
+                        web::get().to(web_api::photo_endpoint),
+                    ),
             )
             .default_service(web::route().to(web_api::p404_page))
     })
-    .bind(&addr)
-    .unwrap()
-    .run();
-    info!("Server live at http://{}", &addr);
-    server.await.unwrap();
-}
-
-fn all_photos_folder_path(config: &Config) -> String {
-    let mut new_file_path = env::current_exe().unwrap();
-    // remove binary name
-    new_file_path.pop();
-    // add global_folder
-    new_file_path.push(&config.all_photos_folder_name);
-    if !new_file_path.exists() {
-        fs::create_dir_all(&new_file_path).unwrap();
+    .keep_alive(server_keep_alive)
+    // own signal handling, the default one stops background threads right away
+    .disable_signals()
+    // uploads in flight get this long to finish
+    .shutdown_timeout(shutdown_timeout.as_secs());
+    if server_workers > 0 {
+        server = server.workers(server_workers);
     }
+    let (server, protocol) = match tls_config_opt {
+        Some(tls_config) => (server.bind_rustls(&addr, tls_config), "https"),
+        None => (server.bind(&addr), "http"),
+    };
+    let server = server
+        .inspect_err(|err| error!("Can not bind {}: [{}]", &addr, err))
+        .unwrap_or_else(|err| panic!("Can not bind {}: {}", &addr, err))
+        .run();
+    info!("Server live at {}://{}", protocol, &addr);
+
+    let server_handle = server.handle();
+    let server_task = actix_rt::spawn(server);
+
+    shutdown_signal().await;
+    info!("Shutting down. Waiting for requests in flight and background jobs");
+    future::join(
+        server_handle.stop(true),
+        background_workers.stop(shutdown_timeout),
+    )
+    .await;
+    server_task.await.unwrap().unwrap();
+}
 
-    new_file_path.to_str().unwrap().to_owned()
+// SIGTERM from docker or systemd, Ctrl-C locally
+async fn shutdown_signal() {
+    let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())
+        .expect("SIGTERM handler must be installed");
+    future::select(Box::pin(signal::ctrl_c()), Box::pin(terminate.recv())).await;
 }
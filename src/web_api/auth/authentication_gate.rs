@@ -1,72 +1,148 @@
-use std::future::{ready, Ready};
-
-use actix_web::{dev::Payload, error::Error as ActixWebError, web, FromRequest, HttpRequest};
+use actix_web::{
+    dev::Payload, error::Error as ActixWebError, http::header::AUTHORIZATION, web, FromRequest,
+    HttpRequest,
+};
+use futures::future::LocalBoxFuture;
 use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use uuid::Uuid;
 
-use crate::config::Config;
+use crate::{config::Config, db::DbProvider, web_api::request_context::set_current_user_id};
 
-use super::session_manager::TokenClaims;
-use log::info;
+use super::{
+    role::{is_admin, is_moderator, ROLE_USER},
+    session_manager::TokenClaims,
+};
+use log::{error, info};
 
 pub struct AuthenticationGate {
     pub is_authorized: bool,
     pub user_id: Option<i64>,
     pub user_name: Option<String>,
     pub user_email: Option<String>,
+    pub user_role: Option<String>,
+    pub session_id: Option<Uuid>,
 }
 
 impl AuthenticationGate {
-    fn empty() -> Self {
+    pub fn empty() -> Self {
         AuthenticationGate {
             is_authorized: false,
             user_id: None,
             user_name: None,
             user_email: None,
+            user_role: None,
+            session_id: None,
         }
     }
+
+    pub fn is_admin(&self) -> bool {
+        self.user_role.as_deref().map(is_admin).unwrap_or_default()
+    }
+
+    pub fn is_moderator(&self) -> bool {
+        self.user_role
+            .as_deref()
+            .map(is_moderator)
+            .unwrap_or_default()
+    }
 }
 
 impl FromRequest for AuthenticationGate {
     type Error = ActixWebError;
-    type Future = Ready<Result<Self, Self::Error>>;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        let token = req.cookie("token").map(|c| c.value().to_string());
+        let token = request_token(req);
+        let config = req.app_data::<web::Data<Config>>().unwrap().clone();
+        let db_provider = req.app_data::<web::Data<DbProvider>>().unwrap().clone();
 
-        if token.is_none() {
-            info!("Token doesn't exist. Exit");
-            return ready(Ok(AuthenticationGate::empty()));
-        }
-
-        let config = req.app_data::<web::Data<Config>>().unwrap();
-
-        let jwt_secret = config.jwt_secret.to_owned();
-        let decode = decode::<TokenClaims>(
-            token.unwrap().as_str(),
-            &DecodingKey::from_secret(jwt_secret.as_ref()),
-            &Validation::new(Algorithm::HS256),
-        );
-
-        match decode {
-            Ok(token) => {
-                let id = token.claims.sub.parse::<i64>().unwrap();
-                let name = token.claims.name;
-                let email = token.claims.email;
-                info!(
-                    "JWT token user id [{}], name: [{}], email: [{}]",
-                    &id, &name, &email
-                );
-                ready(Ok(AuthenticationGate {
-                    is_authorized: true,
-                    user_id: Some(id),
-                    user_name: Some(name),
-                    user_email: Some(email),
-                }))
+        Box::pin(async move {
+            if token.is_none() {
+                info!("Token doesn't exist. Exit");
+                return Ok(AuthenticationGate::empty());
             }
-            Err(_) => {
-                info!("Found token but wasn't able to verify it. I guess it was hoooker attack :3");
-                ready(Ok(AuthenticationGate::empty()))
+
+            let claims = match verify_token(&token.unwrap(), &config.jwt_secret) {
+                Some(claims) => claims,
+                None => {
+                    info!("Found token but wasn't able to verify it. I guess it was hoooker attack :3");
+                    return Ok(AuthenticationGate::empty());
+                }
+            };
+
+            let id = claims.sub.parse::<i64>().unwrap();
+            let session_id = match Uuid::parse_str(&claims.jti) {
+                Ok(session_id) => session_id,
+                Err(_) => {
+                    info!("Token of user [{}] has no session. Exit", &id);
+                    return Ok(AuthenticationGate::empty());
+                }
+            };
+
+            // stolen token is useless after the session was revoked
+            match db_provider.find_active_session(&session_id, id).await {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    info!(
+                        "Session [{}] of user [{}] is revoked or expired",
+                        &session_id, &id
+                    );
+                    return Ok(AuthenticationGate::empty());
+                }
+                Err(err) => {
+                    error!("[DbErr] session check failed: [{}]", &err);
+                    return Ok(AuthenticationGate::empty());
+                }
             }
-        }
+
+            let name = claims.name;
+            let email = claims.email;
+            let role = if claims.role.is_empty() {
+                ROLE_USER.to_owned()
+            } else {
+                claims.role
+            };
+            set_current_user_id(id);
+            info!("JWT token role: [{}], session: [{}]", &role, &session_id);
+            Ok(AuthenticationGate {
+                is_authorized: true,
+                user_id: Some(id),
+                user_name: Some(name),
+                user_email: Some(email),
+                user_role: Some(role),
+                session_id: Some(session_id),
+            })
+        })
     }
 }
+
+/// User of a validly signed token. Session is not checked, good enough for keys like rate limits
+pub fn token_user_id(req: &HttpRequest, jwt_secret: &str) -> Option<i64> {
+    request_token(req)
+        .and_then(|token| verify_token(&token, jwt_secret))
+        .and_then(|claims| claims.sub.parse::<i64>().ok())
+}
+
+// api clients send the header, browsers send the cookie
+fn request_token(req: &HttpRequest) -> Option<String> {
+    bearer_token(req).or_else(|| req.cookie("token").map(|c| c.value().to_string()))
+}
+
+fn verify_token(token: &str, jwt_secret: &str) -> Option<TokenClaims> {
+    decode::<TokenClaims>(
+        token,
+        &DecodingKey::from_secret(jwt_secret.as_ref()),
+        &Validation::new(Algorithm::HS256),
+    )
+    .map(|token| token.claims)
+    .ok()
+}
+
+pub fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
@@ -1,5 +1,7 @@
 mod authentication_gate;
+mod role;
 mod session_manager;
 
-pub use authentication_gate::AuthenticationGate;
+pub use authentication_gate::{bearer_token, token_user_id, AuthenticationGate};
+pub use role::*;
 pub use session_manager::SessionManager as AuthSessionManager;
@@ -0,0 +1,14 @@
+pub static ROLE_USER: &str = "user";
+pub static ROLE_MODERATOR: &str = "moderator";
+pub static ROLE_ADMIN: &str = "admin";
+
+pub static ALL_ROLES: [&str; 3] = [ROLE_USER, ROLE_MODERATOR, ROLE_ADMIN];
+
+pub fn is_admin(role: &str) -> bool {
+    role == ROLE_ADMIN
+}
+
+/// Admins are moderators as well
+pub fn is_moderator(role: &str) -> bool {
+    role == ROLE_MODERATOR || is_admin(role)
+}
@@ -1,8 +1,11 @@
 use actix_web::cookie::time::Duration as ActixWebDuration;
 use actix_web::cookie::Cookie;
-use chrono::{Duration, Utc};
+use chrono::{Duration, NaiveDateTime, Utc};
 use jsonwebtoken::{encode, EncodingKey, Header};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 
 use crate::config::Config;
 
@@ -11,6 +14,11 @@ pub struct TokenClaims {
     pub sub: String,
     pub name: String,
     pub email: String,
+    #[serde(default)]
+    pub role: String,
+    /// Id of the session row. Token without it is not accepted
+    #[serde(default)]
+    pub jti: String,
     pub iat: usize,
     pub exp: usize,
 }
@@ -32,7 +40,32 @@ impl<'cfg> SessionManager<'cfg> {
             .finish()
     }
 
-    pub async fn get_valid_jwt_token(&self, user_id: i64, name: &str, email: &str) -> Cookie {
+    pub async fn get_valid_jwt_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        email: &str,
+        role: &str,
+        session_id: &Uuid,
+    ) -> Cookie<'_> {
+        let token = self.get_access_token(user_id, name, email, role, session_id);
+
+        Cookie::build("token", token)
+            .path("/")
+            .max_age(ActixWebDuration::new(60 * self.config.jwt_max_age, 0))
+            .http_only(true)
+            .finish()
+    }
+
+    /// Same jwt as in the cookie. Api clients send it in the `Authorization: Bearer` header
+    pub fn get_access_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        email: &str,
+        role: &str,
+        session_id: &Uuid,
+    ) -> String {
         let jwt_secret = &self.config.jwt_secret;
         let now = Utc::now();
         let iat = now.timestamp() as usize;
@@ -41,21 +74,45 @@ impl<'cfg> SessionManager<'cfg> {
             sub: user_id.to_string(),
             name: name.to_string(),
             email: email.to_string(),
+            role: role.to_string(),
+            jti: session_id.to_string(),
             exp,
             iat,
         };
 
-        let token = encode(
+        encode(
             &Header::default(),
             &claims,
             &EncodingKey::from_secret(jwt_secret.as_ref()),
         )
-        .unwrap();
+        .unwrap()
+    }
 
-        Cookie::build("token", token)
-            .path("/")
-            .max_age(ActixWebDuration::new(60 * self.config.jwt_max_age, 0))
-            .http_only(true)
-            .finish()
+    /// Access token lifetime in seconds
+    pub fn access_token_expires_in(&self) -> i64 {
+        60 * self.config.jwt_max_age
+    }
+
+    /// Cookie session lives as long as the jwt
+    pub fn session_expires_at(&self) -> NaiveDateTime {
+        (Utc::now() + Duration::minutes(self.config.jwt_max_age)).naive_utc()
+    }
+
+    pub fn refresh_token_expires_at(&self) -> NaiveDateTime {
+        (Utc::now() + Duration::minutes(self.config.refresh_token_max_age)).naive_utc()
+    }
+
+    /// Returns raw refresh token for the client and its hash for the db.
+    /// Raw value is never stored
+    pub fn generate_refresh_token() -> (String, String) {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let raw_token = hex::encode(bytes);
+        let token_hash = Self::hash_refresh_token(&raw_token);
+        (raw_token, token_hash)
+    }
+
+    pub fn hash_refresh_token(raw_token: &str) -> String {
+        hex::encode(Sha256::digest(raw_token.as_bytes()))
     }
 }
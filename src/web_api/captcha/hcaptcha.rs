@@ -0,0 +1,36 @@
+use async_trait::async_trait;
+
+use super::{siteverify, CaptchaError, CaptchaVerdict, CaptchaVerifier};
+
+static HCAPTCHA_SITEVERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+
+/// hCaptcha checkbox widget, pass or fail without a score
+pub struct HCaptchaVerifier {
+    secret: String,
+}
+
+impl HCaptchaVerifier {
+    pub fn new(secret: &str) -> Self {
+        HCaptchaVerifier {
+            secret: secret.to_string(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl CaptchaVerifier for HCaptchaVerifier {
+    async fn verify(&self, token: &str) -> Result<CaptchaVerdict, CaptchaError> {
+        let response = siteverify(
+            HCAPTCHA_SITEVERIFY_URL,
+            "api.hcaptcha.com",
+            &self.secret,
+            token,
+        )
+        .await?;
+        if response.success {
+            Ok(CaptchaVerdict::Passed)
+        } else {
+            Ok(CaptchaVerdict::Rejected(response.error_codes))
+        }
+    }
+}
@@ -0,0 +1,200 @@
+mod hcaptcha;
+mod recaptcha;
+mod stub;
+mod turnstile;
+
+use core::fmt;
+use std::error::Error;
+
+use async_trait::async_trait;
+use awc::Client;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+use super::{
+    metrics::CAPTCHA_VERIFICATIONS,
+    telemetry::{start_span, SpanKind, TRACEPARENT_HEADER},
+};
+
+pub use hcaptcha::HCaptchaVerifier;
+pub use recaptcha::RecaptchaVerifier;
+pub use stub::StubCaptchaVerifier;
+pub use turnstile::TurnstileVerifier;
+
+pub static CAPTCHA_RECAPTCHA: &str = "recaptcha";
+pub static CAPTCHA_HCAPTCHA: &str = "hcaptcha";
+pub static CAPTCHA_TURNSTILE: &str = "turnstile";
+pub static CAPTCHA_PASS: &str = "pass";
+pub static CAPTCHA_FAIL: &str = "fail";
+
+#[derive(Debug)]
+pub struct CaptchaError {
+    message: String,
+}
+
+impl fmt::Display for CaptchaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for CaptchaError {}
+
+pub enum CaptchaVerdict {
+    Passed,
+    /// Token is invalid, expired or reused. Error codes of the provider are for the logs
+    Rejected(Vec<String>),
+    /// Token is valid but the score is under the configured one
+    LowScore(f64),
+}
+
+/// Server side check of the widget token sent with a form
+#[async_trait(?Send)]
+pub trait CaptchaVerifier: Send + Sync {
+    async fn verify(&self, token: &str) -> Result<CaptchaVerdict, CaptchaError>;
+}
+
+/// Bot check of the profile and comment forms. Every form passes when captcha is not configured
+pub struct Captcha {
+    verifier_opt: Option<Box<dyn CaptchaVerifier>>,
+}
+
+impl Captcha {
+    pub fn new(config: &Config) -> Self {
+        let Some(captcha) = &config.captcha else {
+            return Captcha { verifier_opt: None };
+        };
+        let verifier: Box<dyn CaptchaVerifier> = if captcha.provider == CAPTCHA_RECAPTCHA {
+            Box::new(RecaptchaVerifier::new(&captcha.secret, captcha.min_score))
+        } else if captcha.provider == CAPTCHA_HCAPTCHA {
+            Box::new(HCaptchaVerifier::new(&captcha.secret))
+        } else if captcha.provider == CAPTCHA_TURNSTILE {
+            Box::new(TurnstileVerifier::new(&captcha.secret))
+        } else if captcha.provider == CAPTCHA_PASS || captcha.provider == CAPTCHA_FAIL {
+            warn!(
+                "Captcha stub [{}] is on, it answers every form without a real check",
+                &captcha.provider
+            );
+            Box::new(StubCaptchaVerifier::new(captcha.provider == CAPTCHA_PASS))
+        } else {
+            panic!("CAPTCHA_PROVIDER must be recaptcha, hcaptcha, turnstile, pass or fail")
+        };
+        Captcha {
+            verifier_opt: Some(verifier),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn stub(is_passing: bool) -> Self {
+        Captcha {
+            verifier_opt: Some(Box::new(StubCaptchaVerifier::new(is_passing))),
+        }
+    }
+
+    pub async fn is_human(&self, token: &str) -> Result<bool, CaptchaError> {
+        let Some(verifier) = &self.verifier_opt else {
+            return Ok(true);
+        };
+        let verdict = verifier
+            .verify(token)
+            .await
+            .inspect_err(|_| CAPTCHA_VERIFICATIONS.inc(&["error"]))?;
+        match verdict {
+            CaptchaVerdict::Passed => {
+                CAPTCHA_VERIFICATIONS.inc(&["passed"]);
+                Ok(true)
+            }
+            CaptchaVerdict::Rejected(error_codes) => {
+                error!("Captcha token is rejected [{}]", error_codes.join(", "));
+                CAPTCHA_VERIFICATIONS.inc(&["rejected"]);
+                Ok(false)
+            }
+            CaptchaVerdict::LowScore(score) => {
+                error!("Captcha score is low [{}]", score);
+                CAPTCHA_VERIFICATIONS.inc(&["low_score"]);
+                Ok(false)
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SiteverifyRequest<'a> {
+    secret: &'a str,
+    response: &'a str,
+}
+
+/// Answer of the siteverify endpoints, the same for all providers
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+struct SiteverifyResponse {
+    success: bool,
+    score: Option<f64>,
+    action: Option<String>,
+    hostname: Option<String>,
+    #[serde(rename = "error-codes", default)]
+    error_codes: Vec<String>,
+}
+
+/// Posts the token as a form, so the secret and token are escaped and stay out of the url
+async fn siteverify(
+    url: &str,
+    server_address: &str,
+    secret: &str,
+    token: &str,
+) -> Result<SiteverifyResponse, CaptchaError> {
+    let mut span = start_span(SpanKind::Client, "POST captcha siteverify");
+    span.set_attribute("server.address", server_address);
+    let http_client = Client::new();
+
+    let mut request = http_client.post(url);
+    if let Some(traceparent) = span.traceparent() {
+        request = request.insert_header((TRACEPARENT_HEADER, traceparent));
+    }
+    let mut raw_response = request
+        .send_form(&SiteverifyRequest {
+            secret,
+            response: token,
+        })
+        .await
+        .map_err(|err| {
+            span.set_error();
+            CaptchaError {
+                message: err.to_string(),
+            }
+        })?;
+    span.set_attribute("http.response.status_code", raw_response.status().as_u16());
+
+    raw_response
+        .json::<SiteverifyResponse>()
+        .await
+        .inspect(|response| info!("Captcha [{}] RAW response {:?}", server_address, response))
+        .map_err(|err| CaptchaError {
+            message: err.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn pass_stub_lets_every_form_through() {
+        assert!(Captcha::stub(true).is_human("").await.unwrap());
+        assert!(Captcha::stub(true).is_human("any token").await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn fail_stub_stops_every_form() {
+        assert!(!Captcha::stub(false).is_human("").await.unwrap());
+        assert!(!Captcha::stub(false).is_human("any token").await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn forms_pass_when_captcha_is_off() {
+        let captcha = Captcha { verifier_opt: None };
+        assert!(captcha.is_human("").await.unwrap());
+    }
+}
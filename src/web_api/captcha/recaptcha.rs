@@ -0,0 +1,41 @@
+use async_trait::async_trait;
+
+use super::{siteverify, CaptchaError, CaptchaVerdict, CaptchaVerifier};
+
+static RECAPTCHA_SITEVERIFY_URL: &str = "https://www.google.com/recaptcha/api/siteverify";
+
+/// Google reCAPTCHA v3, the token has a score from 0.0 (bot) to 1.0 (human)
+pub struct RecaptchaVerifier {
+    secret: String,
+    min_score: f64,
+}
+
+impl RecaptchaVerifier {
+    pub fn new(secret: &str, min_score: f64) -> Self {
+        RecaptchaVerifier {
+            secret: secret.to_string(),
+            min_score,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl CaptchaVerifier for RecaptchaVerifier {
+    async fn verify(&self, token: &str) -> Result<CaptchaVerdict, CaptchaError> {
+        let response = siteverify(
+            RECAPTCHA_SITEVERIFY_URL,
+            "www.google.com",
+            &self.secret,
+            token,
+        )
+        .await?;
+        if !response.success {
+            return Ok(CaptchaVerdict::Rejected(response.error_codes));
+        }
+        let score = response.score.unwrap_or_default();
+        if score < self.min_score {
+            return Ok(CaptchaVerdict::LowScore(score));
+        }
+        Ok(CaptchaVerdict::Passed)
+    }
+}
@@ -0,0 +1,25 @@
+use async_trait::async_trait;
+
+use super::{CaptchaError, CaptchaVerdict, CaptchaVerifier};
+
+/// Same answer for any token without a network call, for tests and offline dev
+pub struct StubCaptchaVerifier {
+    is_passing: bool,
+}
+
+impl StubCaptchaVerifier {
+    pub fn new(is_passing: bool) -> Self {
+        StubCaptchaVerifier { is_passing }
+    }
+}
+
+#[async_trait(?Send)]
+impl CaptchaVerifier for StubCaptchaVerifier {
+    async fn verify(&self, _token: &str) -> Result<CaptchaVerdict, CaptchaError> {
+        if self.is_passing {
+            Ok(CaptchaVerdict::Passed)
+        } else {
+            Ok(CaptchaVerdict::Rejected(vec!["stub-fail".to_string()]))
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use async_trait::async_trait;
+
+use super::{siteverify, CaptchaError, CaptchaVerdict, CaptchaVerifier};
+
+static TURNSTILE_SITEVERIFY_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
+
+/// Cloudflare Turnstile, mostly invisible for people. Tokens live 5 minutes and are single use
+pub struct TurnstileVerifier {
+    secret: String,
+}
+
+impl TurnstileVerifier {
+    pub fn new(secret: &str) -> Self {
+        TurnstileVerifier {
+            secret: secret.to_string(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl CaptchaVerifier for TurnstileVerifier {
+    async fn verify(&self, token: &str) -> Result<CaptchaVerdict, CaptchaError> {
+        let response = siteverify(
+            TURNSTILE_SITEVERIFY_URL,
+            "challenges.cloudflare.com",
+            &self.secret,
+            token,
+        )
+        .await?;
+        if response.success {
+            Ok(CaptchaVerdict::Passed)
+        } else {
+            Ok(CaptchaVerdict::Rejected(response.error_codes))
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{io, time::Duration};
+
+use chrono::Utc;
+use log::{error, info};
+
+use crate::{
+    db::ProfilePhotoModel,
+    web_api::photo::{photo_key, PhotoService, PhotoStorage},
+};
+
+use super::{BackgroundWorkers, JobContext};
+
+/// What was (or would be in dry-run) removed by one cleanup pass
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    pub is_dry_run: bool,
+    pub photos: u64,
+    pub drafts: u64,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Runs the cleanup on its own thread every `interval_hours`. Zero interval turns it off
+pub fn start_cleanup_task(
+    background_workers: &BackgroundWorkers,
+    context: JobContext,
+    interval_hours: u64,
+    retention_days: i64,
+    is_dry_run: bool,
+) {
+    if interval_hours == 0 {
+        info!("Cleanup task is off");
+        return;
+    }
+
+    info!(
+        "Starting cleanup task. Every [{}] hours, retention [{}] days, dry-run [{}]",
+        interval_hours, retention_days, is_dry_run
+    );
+    background_workers.spawn(move |background_workers| async move {
+        while !background_workers.is_stopping() {
+            match run_cleanup(&context, retention_days, is_dry_run).await {
+                Ok(report) => info!(
+                    "Cleanup is done. Dry-run [{}], photos [{}], drafts [{}], files [{}], reclaimed [{}] bytes",
+                    report.is_dry_run, report.photos, report.drafts, report.files, report.bytes
+                ),
+                Err(err) => error!("Cleanup failed: [{}]", &err),
+            }
+            background_workers
+                .sleep(Duration::from_secs(interval_hours * 60 * 60))
+                .await;
+        }
+    });
+}
+
+/// Purges files of photos deleted more than `retention_days` ago and drafts nobody touched
+/// for that long. Dry-run only measures what would be purged
+pub async fn run_cleanup(
+    context: &JobContext,
+    retention_days: i64,
+    is_dry_run: bool,
+) -> Result<CleanupReport, String> {
+    let retention_start = Utc::now().naive_utc() - chrono::Duration::days(retention_days);
+    let mut report = CleanupReport {
+        is_dry_run,
+        ..Default::default()
+    };
+
+    let deleted_photos = context
+        .db_provider
+        .find_deleted_profile_photos_before(retention_start)
+        .await
+        .map_err(|err| err.to_string())?;
+    info!("Cleanup. Found [{}] deleted photos", deleted_photos.len());
+    for deleted_photo in &deleted_photos {
+        purge_photo_files(context, deleted_photo, is_dry_run, &mut report)
+            .await
+            .map_err(|err| err.to_string())?;
+        if !is_dry_run {
+            context
+                .db_provider
+                .update_profile_photo_with_purged_status(deleted_photo)
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+        report.photos += 1;
+    }
+
+    let draft_profiles = context
+        .db_provider
+        .find_abandoned_draft_profiles(retention_start)
+        .await
+        .map_err(|err| err.to_string())?;
+    info!("Cleanup. Found [{}] abandoned drafts", draft_profiles.len());
+    for draft_profile in &draft_profiles {
+        let draft_photos = context
+            .db_provider
+            .find_all_profile_photos_with_any_status_for(&draft_profile.id)
+            .await
+            .map_err(|err| err.to_string())?;
+        for draft_photo in &draft_photos {
+            purge_photo_files(context, draft_photo, is_dry_run, &mut report)
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+        if !is_dry_run {
+            context
+                .db_provider
+                .delete_draft_profile_and_photos(draft_profile)
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+        report.drafts += 1;
+    }
+
+    Ok(report)
+}
+
+async fn purge_photo_files(
+    context: &JobContext,
+    profile_photo: &ProfilePhotoModel,
+    is_dry_run: bool,
+    report: &mut CleanupReport,
+) -> Result<(), io::Error> {
+    for key in PhotoService::all_file_keys(profile_photo) {
+        purge_file(context.photo_storage.as_ref(), &key, is_dry_run, report).await?;
+    }
+    if let Some(original_name) = &profile_photo.original_file_name {
+        let original_key = photo_key(&profile_photo.profile_id, original_name);
+        purge_file(
+            context.original_photo_storage.0.as_ref(),
+            &original_key,
+            is_dry_run,
+            report,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn purge_file(
+    photo_storage: &dyn PhotoStorage,
+    key: &str,
+    is_dry_run: bool,
+    report: &mut CleanupReport,
+) -> Result<(), io::Error> {
+    let Some(size) = photo_storage.size(key).await? else {
+        return Ok(());
+    };
+
+    info!("Cleanup. Purging [{}], [{}] bytes", key, size);
+    if !is_dry_run {
+        photo_storage.delete(key).await?;
+    }
+    report.files += 1;
+    report.bytes += size;
+    Ok(())
+}
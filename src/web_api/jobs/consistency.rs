@@ -0,0 +1,240 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Duration, Utc};
+use log::info;
+
+use crate::{
+    db::ProfilePhotoModel,
+    web_api::photo::{photo_key, PhotoService, StoredFile, ALL_RENDITIONS},
+};
+
+use super::{JobContext, JOB_KIND_PROCESS_PHOTO, JOB_LOCK_TIMEOUT_MINUTES};
+
+/// Problems found by [`check_photos`]. Original keys are prefixed with `originals:`
+#[derive(Debug, Default)]
+pub struct ConsistencyReport {
+    pub checked_photos: u64,
+    pub missing_files: Vec<String>,
+    pub orphan_files: Vec<String>,
+    pub size_mismatches: Vec<String>,
+    pub fixed: u64,
+}
+
+/// Compares photo rows with files in both storages. With `is_fix`:
+/// - photo with missing renditions is processed again from its original, or deleted without one
+/// - `size` column gets the real size of the original
+/// - files that belong to no row are removed once they are older than a running upload
+pub async fn check_photos(
+    context: &JobContext,
+    is_fix: bool,
+    job_max_attempts: i32,
+) -> Result<ConsistencyReport, String> {
+    let public_files: HashMap<String, StoredFile> = context
+        .photo_storage
+        .list()
+        .await
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .map(|file| (file.key.clone(), file))
+        .collect();
+    let original_files: HashMap<String, StoredFile> = context
+        .original_photo_storage
+        .0
+        .list()
+        .await
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .map(|file| (file.key.clone(), file))
+        .collect();
+    info!(
+        "Consistency. Found [{}] public and [{}] original files",
+        public_files.len(),
+        original_files.len()
+    );
+
+    let profile_photos = context
+        .db_provider
+        .find_all_not_purged_profile_photos()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mut report = ConsistencyReport::default();
+    let mut known_public_keys = HashSet::new();
+    let mut known_original_keys = HashSet::new();
+    for profile_photo in &profile_photos {
+        known_public_keys.extend(PhotoService::all_file_keys(profile_photo));
+        let original_key_opt = original_key(profile_photo);
+        known_original_keys.extend(original_key_opt.clone());
+
+        // renditions of other photos are not made yet or already deleted
+        if profile_photo.status == "active" {
+            check_active_photo(
+                context,
+                profile_photo,
+                &public_files,
+                &original_files,
+                is_fix,
+                job_max_attempts,
+                &mut report,
+            )
+            .await?;
+        }
+    }
+
+    // upload saves the original before its row, so a younger file may belong to one in flight
+    let orphan_before = Utc::now() - Duration::minutes(JOB_LOCK_TIMEOUT_MINUTES);
+    let is_orphan = |file: &StoredFile, known_keys: &HashSet<String>| {
+        !known_keys.contains(&file.key) && file.modified_at < orphan_before
+    };
+    for key in public_files
+        .values()
+        .filter(|file| is_orphan(file, &known_public_keys))
+        .map(|file| &file.key)
+    {
+        report.orphan_files.push(key.clone());
+        if is_fix {
+            context
+                .photo_storage
+                .delete(key)
+                .await
+                .map_err(|err| err.to_string())?;
+            report.fixed += 1;
+        }
+    }
+    for key in original_files
+        .values()
+        .filter(|file| is_orphan(file, &known_original_keys))
+        .map(|file| &file.key)
+    {
+        report.orphan_files.push(format!("originals:{}", key));
+        if is_fix {
+            context
+                .original_photo_storage
+                .0
+                .delete(key)
+                .await
+                .map_err(|err| err.to_string())?;
+            report.fixed += 1;
+        }
+    }
+
+    report.checked_photos = profile_photos.len() as u64;
+    Ok(report)
+}
+
+async fn check_active_photo(
+    context: &JobContext,
+    profile_photo: &ProfilePhotoModel,
+    public_files: &HashMap<String, StoredFile>,
+    original_files: &HashMap<String, StoredFile>,
+    is_fix: bool,
+    job_max_attempts: i32,
+    report: &mut ConsistencyReport,
+) -> Result<(), String> {
+    // db keeps one name per rendition, other formats are optional and the endpoint falls back
+    let mut rendition_keys: Vec<String> = ALL_RENDITIONS
+        .iter()
+        .map(|rendition| {
+            photo_key(
+                &profile_photo.profile_id,
+                rendition.stored_file_name(profile_photo),
+            )
+        })
+        .collect();
+    rendition_keys.dedup();
+    let missing_keys: Vec<String> = rendition_keys
+        .into_iter()
+        .filter(|key| !public_files.contains_key(key))
+        .collect();
+
+    let original_key_opt = original_key(profile_photo);
+    let original_size_opt = original_key_opt
+        .as_ref()
+        .and_then(|key| original_files.get(key))
+        .map(|file| file.size);
+    if let (Some(key), None) = (&original_key_opt, original_size_opt) {
+        report.missing_files.push(format!("originals:{}", key));
+    }
+
+    if !missing_keys.is_empty() {
+        report.missing_files.extend(missing_keys);
+        if is_fix {
+            fix_missing_renditions(
+                context,
+                profile_photo,
+                original_size_opt.is_some(),
+                job_max_attempts,
+            )
+            .await?;
+            report.fixed += 1;
+        }
+        return Ok(());
+    }
+
+    // size column is the upload size, photos before renditions have no original
+    let size_opt = original_size_opt.or_else(|| {
+        public_files
+            .get(&photo_key(
+                &profile_photo.profile_id,
+                &profile_photo.file_name,
+            ))
+            .map(|file| file.size)
+    });
+    if let Some(size) = size_opt.filter(|size| *size as i64 != profile_photo.size) {
+        report.size_mismatches.push(format!(
+            "{}: db [{}], storage [{}]",
+            profile_photo.id, profile_photo.size, size
+        ));
+        if is_fix {
+            context
+                .db_provider
+                .update_profile_photo_size(profile_photo, size as i64)
+                .await
+                .map_err(|err| err.to_string())?;
+            report.fixed += 1;
+        }
+    }
+    Ok(())
+}
+
+async fn fix_missing_renditions(
+    context: &JobContext,
+    profile_photo: &ProfilePhotoModel,
+    has_original: bool,
+    job_max_attempts: i32,
+) -> Result<(), String> {
+    if has_original {
+        info!(
+            "Consistency. Photo [{}] is processed again",
+            profile_photo.id
+        );
+        context
+            .db_provider
+            .update_profile_photo_status(profile_photo, "processing")
+            .await
+            .map_err(|err| err.to_string())?;
+        context
+            .db_provider
+            .add_job(JOB_KIND_PROCESS_PHOTO, profile_photo.id, job_max_attempts)
+            .await
+            .map_err(|err| err.to_string())?;
+    } else {
+        info!(
+            "Consistency. Photo [{}] has nothing to be rebuilt from. Deleting",
+            profile_photo.id
+        );
+        context
+            .db_provider
+            .update_profile_photo_with_delete_status(profile_photo)
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+fn original_key(profile_photo: &ProfilePhotoModel) -> Option<String> {
+    profile_photo
+        .original_file_name
+        .as_ref()
+        .map(|original_name| photo_key(&profile_photo.profile_id, original_name))
+}
@@ -0,0 +1,339 @@
+mod cleanup;
+mod consistency;
+mod photo_job;
+mod trace_export;
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use actix_rt::{time::sleep, Arbiter};
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use log::{error, info, warn};
+
+use crate::db::{DbProvider, JobModel};
+
+use super::{
+    metrics::PHOTO_PROCESSING_DURATION,
+    photo::{OriginalPhotoStorage, PhotoStorage, Watermark},
+};
+
+pub use cleanup::start_cleanup_task;
+pub use consistency::check_photos;
+pub use trace_export::start_trace_export;
+
+pub static JOB_KIND_PROCESS_PHOTO: &str = "process_photo";
+
+// idle workers look into the queue this often
+static JOB_POLL_INTERVAL: Duration = Duration::from_secs(2);
+// running job older than this lost its worker
+static JOB_LOCK_TIMEOUT_MINUTES: i64 = 10;
+// 20s, 40s, 80s...
+static JOB_RETRY_BASE_SECONDS: i64 = 10;
+
+/// Everything a job may need. Cloned into every worker
+#[derive(Clone)]
+pub struct JobContext {
+    pub db_provider: web::Data<DbProvider>,
+    pub photo_storage: web::Data<dyn PhotoStorage>,
+    pub original_photo_storage: web::Data<OriginalPhotoStorage>,
+    pub watermark: web::Data<Watermark>,
+}
+
+/// Threads of background work. Stopping lets every loop finish its current job
+#[derive(Clone, Default)]
+pub struct BackgroundWorkers {
+    is_stopping: Arc<AtomicBool>,
+    running: Arc<AtomicUsize>,
+    arbiters: Arc<Mutex<Vec<Arbiter>>>,
+}
+
+impl BackgroundWorkers {
+    /// Runs the loop on its own thread. Storage futures are not `Send`,
+    /// so the loop is created on that thread
+    fn spawn<F, Fut>(&self, worker_loop: F)
+    where
+        F: FnOnce(BackgroundWorkers) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let arbiter = Arbiter::new();
+        let workers = self.clone();
+        self.running.fetch_add(1, Ordering::SeqCst);
+        arbiter.spawn_fn(move || {
+            actix_rt::spawn(async move {
+                worker_loop(workers.clone()).await;
+                workers.running.fetch_sub(1, Ordering::SeqCst);
+            });
+        });
+        self.arbiters.lock().unwrap().push(arbiter);
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.is_stopping.load(Ordering::SeqCst)
+    }
+
+    /// Waits up to `timeout` for loops to exit, the ones still busy are dropped with their threads
+    pub async fn stop(&self, timeout: Duration) {
+        self.is_stopping.store(true, Ordering::SeqCst);
+        let deadline = Instant::now() + timeout;
+        while self.running.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            sleep(Duration::from_millis(100)).await;
+        }
+
+        let running = self.running.load(Ordering::SeqCst);
+        if running > 0 {
+            warn!(
+                "[{}] background workers are still busy. Stopping anyway",
+                running
+            );
+        } else {
+            info!("Background workers are stopped");
+        }
+        self.arbiters.lock().unwrap().drain(..).for_each(|arbiter| {
+            arbiter.stop();
+        });
+    }
+
+    /// Sleeps in short steps, so stopping does not wait for the whole `duration`
+    async fn sleep(&self, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        while !self.is_stopping() && Instant::now() < deadline {
+            sleep(JOB_POLL_INTERVAL.min(deadline - Instant::now())).await;
+        }
+    }
+}
+
+/// Every worker gets its own thread, so heavy image work never blocks http workers
+pub fn start_job_workers(
+    background_workers: &BackgroundWorkers,
+    workers: usize,
+    context: JobContext,
+) {
+    info!("Starting [{}] job workers", workers);
+    for worker_id in 0..workers {
+        let worker_context = context.clone();
+        background_workers.spawn(move |background_workers| {
+            worker_loop(worker_id, worker_context, background_workers)
+        });
+    }
+}
+
+async fn worker_loop(worker_id: usize, context: JobContext, background_workers: BackgroundWorkers) {
+    while !background_workers.is_stopping() {
+        match context.db_provider.take_next_job().await {
+            Ok(Some(job)) => run_job(worker_id, &context, &job).await,
+            Ok(None) => {
+                requeue_stale_jobs(&context).await;
+                background_workers.sleep(JOB_POLL_INTERVAL).await;
+            }
+            Err(err) => {
+                error!("Worker [{}] can not take job: [{}]", worker_id, &err);
+                background_workers.sleep(JOB_POLL_INTERVAL).await;
+            }
+        }
+    }
+    info!("Worker [{}] is stopped", worker_id);
+}
+
+async fn run_job(worker_id: usize, context: &JobContext, job: &JobModel) {
+    info!(
+        "Worker [{}] took job [{}] [{}] for [{}], attempt [{}]",
+        worker_id, job.id, &job.kind, job.target_id, job.attempts
+    );
+
+    let result = if job.kind == JOB_KIND_PROCESS_PHOTO {
+        let started_at = Instant::now();
+        let result = photo_job::process_photo(context, job.target_id).await;
+        let outcome = if result.is_ok() { "done" } else { "failed" };
+        PHOTO_PROCESSING_DURATION.observe(&[outcome], started_at.elapsed());
+        result
+    } else {
+        Err(format!("Unknown job kind [{}]", &job.kind))
+    };
+
+    let update_result = match result {
+        Ok(_) => {
+            info!("Job [{}] is done", job.id);
+            context.db_provider.complete_job(job).await
+        }
+        Err(err) => {
+            error!("Job [{}] failed: [{}]", job.id, &err);
+            let retry_at_opt = retry_at(job, Utc::now().naive_utc());
+            if retry_at_opt.is_none() && job.kind == JOB_KIND_PROCESS_PHOTO {
+                photo_job::give_up(context, job.target_id).await;
+            }
+            context.db_provider.fail_job(job, &err, retry_at_opt).await
+        }
+    };
+
+    if let Err(err) = update_result {
+        error!("Job [{}] status was not saved: [{}]", job.id, &err);
+    }
+}
+
+// `attempts` already counts the failed run
+fn retry_at(job: &JobModel, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    (job.attempts < job.max_attempts).then(|| {
+        let backoff_seconds = JOB_RETRY_BASE_SECONDS * 2_i64.pow(job.attempts as u32);
+        now + chrono::Duration::seconds(backoff_seconds)
+    })
+}
+
+async fn requeue_stale_jobs(context: &JobContext) {
+    let locked_before =
+        Utc::now().naive_utc() - chrono::Duration::minutes(JOB_LOCK_TIMEOUT_MINUTES);
+    let requeue_result = context
+        .db_provider
+        .requeue_stale_jobs(locked_before, "Worker was lost while running the job")
+        .await;
+    let (requeued, failed_jobs) = match requeue_result {
+        Ok(result) => result,
+        Err(err) => {
+            error!("Stale jobs were not requeued: [{}]", &err);
+            return;
+        }
+    };
+    if requeued > 0 {
+        info!("[{}] stale jobs are back in the queue", requeued);
+    }
+    for job in failed_jobs {
+        error!(
+            "Stale job [{}] has no attempts left after [{}]. Failed",
+            job.id, job.attempts
+        );
+        if job.kind == JOB_KIND_PROCESS_PHOTO {
+            photo_job::give_up(context, job.target_id).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use sea_orm::{DatabaseBackend, MockDatabase, Transaction};
+    use uuid::Uuid;
+
+    use crate::{
+        config::Config,
+        db::ProfilePhotoModel,
+        web_api::photo::{FsPhotoStorage, PhotoStorage},
+    };
+
+    use super::*;
+
+    fn job(attempts: i32, status: &str) -> JobModel {
+        let now = Utc::now().naive_utc();
+        JobModel {
+            id: 1,
+            kind: JOB_KIND_PROCESS_PHOTO.to_owned(),
+            target_id: 7,
+            status: status.to_owned(),
+            attempts,
+            max_attempts: 3,
+            last_error: None,
+            run_at: now,
+            locked_at: Some(now),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn profile_photo(status: &str) -> ProfilePhotoModel {
+        ProfilePhotoModel {
+            id: 7,
+            created_at: Utc::now().naive_utc(),
+            status: status.to_owned(),
+            file_name: "photo.jpg".to_owned(),
+            profile_id: Uuid::new_v4(),
+            size: 100,
+            thumbnail_file_name: Some("photo_thumbnail.jpg".to_owned()),
+            card_file_name: Some("photo_card.jpg".to_owned()),
+            original_file_name: Some("photo_original.jpg".to_owned()),
+            position: 0,
+            deleted_at: None,
+        }
+    }
+
+    /// Runs the job against the mock database. Photo files are missing, so processing fails
+    async fn run(job: &JobModel, mock_database: MockDatabase) -> Vec<Transaction> {
+        let folder = env::temp_dir().join(format!("jobs-{}", Uuid::new_v4()));
+        let photo_storage: Arc<dyn PhotoStorage> =
+            Arc::new(FsPhotoStorage::new(folder.to_str().unwrap(), "photos"));
+        let db_provider = web::Data::new(DbProvider::new(mock_database.into_connection()));
+        let context = JobContext {
+            db_provider: db_provider.clone(),
+            photo_storage: web::Data::from(photo_storage.clone()),
+            original_photo_storage: web::Data::new(OriginalPhotoStorage(photo_storage)),
+            watermark: web::Data::new(Watermark::new(&Config::for_tests(&[("WATERMARK", "off")]))),
+        };
+
+        run_job(0, &context, job).await;
+
+        drop(context);
+        let db_provider = Arc::try_unwrap(db_provider.into_inner()).ok().unwrap();
+        db_provider.db_con.into_transaction_log()
+    }
+
+    fn updates_of(transaction_log: &[Transaction], table: &str) -> Vec<String> {
+        let update = format!(r#"sql: "UPDATE \"{}\""#, table);
+        transaction_log
+            .iter()
+            .map(|transaction| format!("{:?}", transaction))
+            .filter(|transaction| transaction.contains(&update))
+            .collect()
+    }
+
+    #[test]
+    fn retry_wait_doubles_after_every_attempt() {
+        let now = Utc::now().naive_utc();
+
+        assert_eq!(
+            retry_at(&job(1, "running"), now),
+            Some(now + chrono::Duration::seconds(20))
+        );
+        assert_eq!(
+            retry_at(&job(2, "running"), now),
+            Some(now + chrono::Duration::seconds(40))
+        );
+        assert_eq!(retry_at(&job(3, "running"), now), None);
+    }
+
+    #[actix_rt::test]
+    async fn failed_job_with_attempts_left_is_retried() {
+        let mock_database = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[profile_photo("processing")]])
+            .append_query_results([[job(1, "pending")]]);
+
+        let transaction_log = run(&job(1, "running"), mock_database).await;
+
+        assert!(updates_of(&transaction_log, "profile_photo").is_empty());
+        let job_updates = updates_of(&transaction_log, "job");
+        assert_eq!(job_updates.len(), 1);
+        assert!(job_updates[0].contains(r#"String(Some("pending"))"#));
+    }
+
+    #[actix_rt::test]
+    async fn last_failed_attempt_gives_up_the_photo() {
+        let mock_database = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[profile_photo("processing")]])
+            .append_query_results([[profile_photo("processing")]])
+            .append_query_results([[profile_photo("failed")]])
+            .append_query_results([[job(3, "failed")]]);
+
+        let transaction_log = run(&job(3, "running"), mock_database).await;
+
+        let photo_updates = updates_of(&transaction_log, "profile_photo");
+        assert_eq!(photo_updates.len(), 1);
+        assert!(photo_updates[0].contains(r#"String(Some("failed"))"#));
+        let job_updates = updates_of(&transaction_log, "job");
+        assert_eq!(job_updates.len(), 1);
+        assert!(job_updates[0].contains(r#"String(Some("failed"))"#));
+    }
+}
@@ -0,0 +1,62 @@
+use log::info;
+
+use crate::web_api::photo::PhotoService;
+
+use super::JobContext;
+
+/// Renditions of the uploaded photo. The photo becomes visible only after this
+pub async fn process_photo(context: &JobContext, profile_photo_id: i64) -> Result<(), String> {
+    let profile_photo_opt = context
+        .db_provider
+        .find_profile_photo_by_id(profile_photo_id)
+        .await
+        .map_err(|err| err.to_string())?;
+    // deleted while waiting in the queue
+    let Some(profile_photo) = profile_photo_opt.filter(|photo| photo.status == "processing") else {
+        info!(
+            "Photo [{}] is not processing anymore. Skipping",
+            profile_photo_id
+        );
+        return Ok(());
+    };
+
+    PhotoService::process_photo(
+        context.photo_storage.as_ref(),
+        &context.original_photo_storage,
+        &context.watermark,
+        &profile_photo,
+    )
+    .await
+    .map_err(|err| err.to_string())?;
+
+    let is_activated = context
+        .db_provider
+        .activate_processed_profile_photo(profile_photo.id)
+        .await
+        .map_err(|err| err.to_string())?;
+    // deleted while processing, its renditions were not there to be marked as deleted
+    if !is_activated {
+        info!(
+            "Photo [{}] was deleted while processing. Removing its renditions",
+            profile_photo_id
+        );
+        PhotoService::discard_renditions(context.photo_storage.as_ref(), &profile_photo)
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+/// No retries left, the owner sees the photo as failed and may upload it again
+pub async fn give_up(context: &JobContext, profile_photo_id: i64) {
+    let profile_photo_res = context
+        .db_provider
+        .find_profile_photo_by_id(profile_photo_id)
+        .await;
+    if let Ok(Some(profile_photo)) = profile_photo_res {
+        let _ = context
+            .db_provider
+            .update_profile_photo_status(&profile_photo, "failed")
+            .await;
+    }
+}
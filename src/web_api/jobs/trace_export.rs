@@ -0,0 +1,228 @@
+use std::time::Duration;
+
+use awc::Client;
+use log::{info, warn};
+use serde_json::{json, Value};
+
+use crate::{
+    config::OtelConfig,
+    web_api::telemetry::{enable_tracing, take_dropped_span_count, take_pending_spans, SpanData},
+};
+
+use super::BackgroundWorkers;
+
+static TRACE_EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+static TRACE_EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends finished spans to the collector as OTLP/HTTP json. The last batch goes out on stop
+pub fn start_trace_export(background_workers: &BackgroundWorkers, otel: &OtelConfig) {
+    let endpoint = format!("{}/v1/traces", otel.otlp_endpoint.trim_end_matches('/'));
+    let service_name = otel.service_name.clone();
+    info!("Starting trace export to [{}]", &endpoint);
+    enable_tracing();
+    background_workers.spawn(move |background_workers| async move {
+        while !background_workers.is_stopping() {
+            background_workers.sleep(TRACE_EXPORT_INTERVAL).await;
+            export_spans(&endpoint, &service_name).await;
+        }
+    });
+}
+
+async fn export_spans(endpoint: &str, service_name: &str) {
+    let dropped_span_count = take_dropped_span_count();
+    if dropped_span_count > 0 {
+        warn!(
+            "Trace export is late, [{}] spans over the pending limit are dropped",
+            dropped_span_count
+        );
+    }
+    let spans = take_pending_spans();
+    if spans.is_empty() {
+        return;
+    }
+    let result = Client::default()
+        .post(endpoint)
+        .timeout(TRACE_EXPORT_TIMEOUT)
+        .send_json(&otlp_request(service_name, &spans))
+        .await;
+    match result {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) => warn!(
+            "Trace export. Collector answered [{}], [{}] spans are dropped",
+            response.status(),
+            spans.len()
+        ),
+        Err(err) => warn!(
+            "Trace export failed: [{}], [{}] spans are dropped",
+            &err,
+            spans.len()
+        ),
+    }
+}
+
+/// `ExportTraceServiceRequest` in the json encoding, ids are hex
+fn otlp_request(service_name: &str, spans: &[SpanData]) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            let attributes: Vec<Value> = span
+                .attributes
+                .iter()
+                .map(|(key, value)| otlp_attribute(key, value))
+                .collect();
+            // 2 is error, 0 is unset
+            let status_code = if span.is_error { 2 } else { 0 };
+            json!({
+                "traceId": span.trace_id,
+                "spanId": span.span_id,
+                "parentSpanId": span.parent_span_id.clone().unwrap_or_default(),
+                "name": span.name,
+                "kind": span.kind as i32,
+                "startTimeUnixNano": span.start_unix_nanos.to_string(),
+                "endTimeUnixNano": span.end_unix_nanos.to_string(),
+                "attributes": attributes,
+                "status": { "code": status_code },
+            })
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [otlp_attribute("service.name", service_name)],
+            },
+            "scopeSpans": [{
+                "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+fn otlp_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use futures::future::join;
+
+    use crate::web_api::telemetry::{db_span, in_span, in_trace, start_span, SpanKind};
+
+    use super::*;
+
+    static REMOTE_TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    static REMOTE_PARENT_ID: &str = "00f067aa0ba902b7";
+
+    type Requests = Arc<Mutex<Vec<Value>>>;
+
+    async fn collector(requests: web::Data<Requests>, body: web::Json<Value>) -> HttpResponse {
+        requests.lock().unwrap().push(body.into_inner());
+        HttpResponse::Ok().finish()
+    }
+
+    fn span_by_name<'a>(spans: &'a [Value], name: &str) -> &'a Value {
+        spans
+            .iter()
+            .find(|span| span["name"] == name)
+            .unwrap_or_else(|| panic!("no span [{}]", name))
+    }
+
+    #[actix_rt::test]
+    async fn posts_spans_of_a_request_as_otlp_json() {
+        let requests: Requests = Arc::default();
+        let app_requests = requests.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(app_requests.clone()))
+                .route("/v1/traces", web::post().to(collector))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let endpoint = format!("http://{}/v1/traces", server.addrs()[0]);
+        let server = server.run();
+        let server_handle = server.handle();
+        actix_rt::spawn(server);
+
+        enable_tracing();
+        let traceparent = format!("00-{}-{}-01", REMOTE_TRACE_ID, REMOTE_PARENT_ID);
+        in_trace(Some(traceparent), async {
+            let mut server_span = start_span(SpanKind::Server, "GET /profile/{id}");
+            server_span.set_error();
+            in_span(&server_span, async {
+                // children of concurrent spans keep their own parents
+                let first_span = start_span(SpanKind::Internal, "first");
+                let second_span = start_span(SpanKind::Internal, "second");
+                join(
+                    in_span(&first_span, async {
+                        let _span = db_span("first_query");
+                        actix_rt::task::yield_now().await;
+                    }),
+                    in_span(&second_span, async {
+                        let _span = db_span("second_query");
+                        actix_rt::task::yield_now().await;
+                    }),
+                )
+                .await;
+            })
+            .await;
+        })
+        .await;
+        export_spans(&endpoint, "board-test").await;
+        server_handle.stop(false).await;
+
+        let requests = requests.lock().unwrap();
+        let resource_spans = &requests[0]["resourceSpans"][0];
+        assert_eq!(
+            resource_spans["resource"]["attributes"][0],
+            json!({ "key": "service.name", "value": { "stringValue": "board-test" } })
+        );
+        let spans: Vec<Value> = resource_spans["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|span| span["traceId"] == REMOTE_TRACE_ID)
+            .cloned()
+            .collect();
+        assert_eq!(spans.len(), 5);
+
+        let server_span = span_by_name(&spans, "GET /profile/{id}");
+        assert_eq!(server_span["parentSpanId"], REMOTE_PARENT_ID);
+        assert_eq!(server_span["kind"], SpanKind::Server as i32);
+        assert_eq!(server_span["status"]["code"], 2);
+        for (parent_name, child_name) in [
+            ("GET /profile/{id}", "first"),
+            ("GET /profile/{id}", "second"),
+            ("first", "db.first_query"),
+            ("second", "db.second_query"),
+        ] {
+            assert_eq!(
+                span_by_name(&spans, child_name)["parentSpanId"],
+                span_by_name(&spans, parent_name)["spanId"],
+                "{}",
+                child_name
+            );
+        }
+        let db_span = span_by_name(&spans, "db.first_query");
+        assert_eq!(db_span["kind"], SpanKind::Client as i32);
+        assert_eq!(db_span["status"]["code"], 0);
+        assert_eq!(
+            db_span["attributes"][0],
+            json!({ "key": "db.system", "value": { "stringValue": "postgresql" } })
+        );
+        let start_nanos: u128 = db_span["startTimeUnixNano"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let end_nanos: u128 = db_span["endTimeUnixNano"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(start_nanos <= end_nanos);
+    }
+}
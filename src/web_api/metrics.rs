@@ -0,0 +1,231 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::dev::ServiceResponse;
+
+// seconds, from a quick query up to a full size photo
+static DURATION_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+pub static HTTP_REQUEST_DURATION: HistogramVec = HistogramVec::new(
+    "http_request_duration_seconds",
+    "Http request latency by route pattern",
+    &["method", "route", "status"],
+);
+pub static DB_QUERY_DURATION: HistogramVec = HistogramVec::new(
+    "db_query_duration_seconds",
+    "Database query time by statement and table",
+    &["operation", "table", "outcome"],
+);
+pub static PHOTO_PROCESSING_DURATION: HistogramVec = HistogramVec::new(
+    "photo_processing_duration_seconds",
+    "Time of one photo processing job",
+    &["outcome"],
+);
+pub static CAPTCHA_VERIFICATIONS: CounterVec = CounterVec::new(
+    "captcha_verifications_total",
+    "Captcha checks by outcome",
+    &["outcome"],
+);
+
+#[derive(Clone, Default)]
+struct Histogram {
+    bucket_counts: [u64; DURATION_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+/// Histogram with one series per label values. Label values must stay few
+pub struct HistogramVec {
+    name: &'static str,
+    help: &'static str,
+    label_names: &'static [&'static str],
+    series: Mutex<BTreeMap<Vec<String>, Histogram>>,
+}
+
+impl HistogramVec {
+    const fn new(
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+    ) -> Self {
+        HistogramVec {
+            name,
+            help,
+            label_names,
+            series: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn observe(&self, label_values: &[&str], duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let key = label_values.iter().map(|value| value.to_string()).collect();
+        let mut series = self.series.lock().unwrap();
+        let histogram = series.entry(key).or_default();
+        DURATION_BUCKETS
+            .iter()
+            .zip(histogram.bucket_counts.iter_mut())
+            .filter(|(bucket, _)| seconds <= **bucket)
+            .for_each(|(_, bucket_count)| *bucket_count += 1);
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    fn render(&self, out: &mut String) {
+        render_header(out, self.name, self.help, "histogram");
+        let series = self.series.lock().unwrap().clone();
+        for (label_values, histogram) in &series {
+            let labels = format_labels(self.label_names, label_values);
+            for (bucket, bucket_count) in DURATION_BUCKETS.iter().zip(histogram.bucket_counts) {
+                let bucket_labels = append_label(&labels, "le", &bucket.to_string());
+                writeln!(
+                    out,
+                    "{}_bucket{} {}",
+                    self.name, bucket_labels, bucket_count
+                )
+                .unwrap();
+            }
+            let inf_labels = append_label(&labels, "le", "+Inf");
+            writeln!(
+                out,
+                "{}_bucket{} {}",
+                self.name, inf_labels, histogram.count
+            )
+            .unwrap();
+            writeln!(out, "{}_sum{} {}", self.name, labels, histogram.sum).unwrap();
+            writeln!(out, "{}_count{} {}", self.name, labels, histogram.count).unwrap();
+        }
+    }
+}
+
+pub struct CounterVec {
+    name: &'static str,
+    help: &'static str,
+    label_names: &'static [&'static str],
+    series: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl CounterVec {
+    const fn new(
+        name: &'static str,
+        help: &'static str,
+        label_names: &'static [&'static str],
+    ) -> Self {
+        CounterVec {
+            name,
+            help,
+            label_names,
+            series: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn inc(&self, label_values: &[&str]) {
+        let key = label_values.iter().map(|value| value.to_string()).collect();
+        *self.series.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    fn render(&self, out: &mut String) {
+        render_header(out, self.name, self.help, "counter");
+        let series = self.series.lock().unwrap().clone();
+        for (label_values, count) in &series {
+            let labels = format_labels(self.label_names, label_values);
+            writeln!(out, "{}{} {}", self.name, labels, count).unwrap();
+        }
+    }
+}
+
+/// Value known only at scrape time, e.g. rows by status
+pub struct Gauge<'a> {
+    pub name: &'a str,
+    pub help: &'a str,
+    pub label_name: &'a str,
+    pub values: Vec<(String, i64)>,
+}
+
+/// Every metric in the prometheus text format
+pub fn render_metrics(gauges: &[Gauge]) -> String {
+    let mut out = String::new();
+    HTTP_REQUEST_DURATION.render(&mut out);
+    DB_QUERY_DURATION.render(&mut out);
+    PHOTO_PROCESSING_DURATION.render(&mut out);
+    CAPTCHA_VERIFICATIONS.render(&mut out);
+    for gauge in gauges {
+        render_header(&mut out, gauge.name, gauge.help, "gauge");
+        for (label_value, value) in &gauge.values {
+            let labels = format_labels(&[gauge.label_name], std::slice::from_ref(label_value));
+            writeln!(out, "{}{} {}", gauge.name, labels, value).unwrap();
+        }
+    }
+    out
+}
+
+/// Route pattern keeps the label small, `/photos/{profile_id}/{file_name}` instead of every photo
+pub fn observe_request<B>(response: &ServiceResponse<B>, started_at: Instant) {
+    let request = response.request();
+    let route = request.match_pattern().unwrap_or("unmatched".to_string());
+    HTTP_REQUEST_DURATION.observe(
+        &[
+            request.method().as_str(),
+            &route,
+            response.status().as_str(),
+        ],
+        started_at.elapsed(),
+    );
+}
+
+/// Sea-orm metric callback. Sql text is reduced to its operation and first table
+pub fn observe_db_query(sql: &str, elapsed: Duration, is_failed: bool) {
+    let tokens: Vec<&str> = sql.split_whitespace().collect();
+    let operation = tokens
+        .first()
+        .map(|token| token.to_lowercase())
+        .unwrap_or_default();
+    let table = tokens
+        .windows(2)
+        // subqueries are skipped, their own FROM comes next
+        .find(|pair| {
+            ["FROM", "INTO", "UPDATE"].contains(&pair[0].to_uppercase().as_str())
+                && !pair[1].starts_with('(')
+        })
+        .map(|pair| pair[1].trim_matches(|c| c == '"' || c == ';'))
+        .unwrap_or_default();
+    let outcome = if is_failed { "error" } else { "ok" };
+    DB_QUERY_DURATION.observe(&[&operation, table, outcome], elapsed);
+}
+
+fn render_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+}
+
+fn format_labels(label_names: &[&str], label_values: &[String]) -> String {
+    if label_names.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = label_names
+        .iter()
+        .zip(label_values)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+fn append_label(labels: &str, name: &str, value: &str) -> String {
+    let pair = format!("{}=\"{}\"", name, value);
+    match labels.strip_suffix('}') {
+        Some(open_labels) => format!("{},{}}}", open_labels, pair),
+        None => format!("{{{}}}", pair),
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -1,7 +1,40 @@
 mod auth;
+mod captcha;
+mod jobs;
+mod metrics;
+mod moderation;
 mod photo;
-mod recaptcha;
+mod rate_limit;
+mod request_context;
 mod routes;
+mod security_headers;
 mod sign_in;
+mod telemetry;
 
+pub use auth::ALL_ROLES;
+pub use captcha::{
+    Captcha, CaptchaError, CaptchaVerdict, CaptchaVerifier, HCaptchaVerifier, RecaptchaVerifier,
+    StubCaptchaVerifier, TurnstileVerifier,
+};
+pub use jobs::{
+    check_photos, start_cleanup_task, start_job_workers, start_trace_export, BackgroundWorkers,
+    JobContext, JOB_KIND_PROCESS_PHOTO,
+};
+pub use metrics::{observe_db_query, observe_request};
+pub use photo::{
+    init_original_photo_storage, init_photo_storage, load_watermark_font, load_watermark_logo,
+    OriginalPhotoStorage, PhotoService, PhotoStorage, Watermark, PHOTO_STORAGE_FS,
+};
+pub use rate_limit::{
+    MemoryRateLimitStore, RateLimit, RateLimitStore, RateLimiter, RATE_LIMIT_COMMENT,
+    RATE_LIMIT_PHOTO_UPLOAD, RATE_LIMIT_PROFILE, RATE_LIMIT_SIGN_IN,
+};
+pub use request_context::{
+    current_request_id, current_user_id, in_request_context, request_id, set_request_id_header,
+};
 pub use routes::*;
+pub use security_headers::{current_csp_nonce, SecurityHeaders};
+pub use telemetry::{
+    current_trace_id, db_span, describe_server_span, in_span, in_trace, start_span, traceparent,
+    SpanKind,
+};
@@ -0,0 +1,140 @@
+use log::info;
+
+use crate::config::Config;
+
+pub static COMMENT_STATUS_APPROVED: &str = "approved";
+pub static COMMENT_STATUS_IN_REVIEW: &str = "in_review";
+pub static COMMENT_STATUS_REJECTED: &str = "rejected";
+
+static LINK_MARKERS: [&str; 3] = ["http://", "https://", "www."];
+
+pub struct CommentModeration<'a> {
+    review_all: bool,
+    review_links: bool,
+    // lowercase, see `Config`
+    stop_words: &'a [String],
+}
+
+impl<'a> CommentModeration<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        CommentModeration {
+            review_all: config.comment_review_all,
+            review_links: config.comment_review_links,
+            stop_words: &config.comment_review_stop_words,
+        }
+    }
+
+    /// Resolves status for the new comment using configured review rules
+    pub fn initial_status(&self, text: &str) -> &'static str {
+        let lowercase_text = text.to_lowercase();
+
+        let review_reason = if self.review_all {
+            Some("review all")
+        } else if self.review_links
+            && LINK_MARKERS
+                .iter()
+                .any(|marker| lowercase_text.contains(marker))
+        {
+            Some("link")
+        } else if self
+            .stop_words
+            .iter()
+            .any(|word| lowercase_text.contains(word))
+        {
+            Some("stop word")
+        } else {
+            None
+        };
+
+        match review_reason {
+            Some(reason) => {
+                info!("Comment goes to review. Rule: [{}]", reason);
+                COMMENT_STATUS_IN_REVIEW
+            }
+            None => COMMENT_STATUS_APPROVED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(review_all: bool, review_links: bool, stop_words: &[String]) -> CommentModeration<'_> {
+        CommentModeration {
+            review_all,
+            review_links,
+            stop_words,
+        }
+    }
+
+    #[test]
+    fn approves_when_no_rule_matches() {
+        let stop_words = vec!["casino".to_owned()];
+        let moderation = rules(false, true, &stop_words);
+        assert_eq!(
+            moderation.initial_status("Nice profile, say hi"),
+            COMMENT_STATUS_APPROVED
+        );
+        assert_eq!(
+            rules(false, false, &[]).initial_status(""),
+            COMMENT_STATUS_APPROVED
+        );
+    }
+
+    #[test]
+    fn review_all_sends_every_comment_to_review() {
+        let moderation = rules(true, false, &[]);
+        assert_eq!(
+            moderation.initial_status("Nice profile, say hi"),
+            COMMENT_STATUS_IN_REVIEW
+        );
+    }
+
+    #[test]
+    fn links_go_to_review_only_when_the_rule_is_on() {
+        for text in [
+            "see http://example.com",
+            "see https://example.com",
+            "see www.example.com",
+            "see HTTPS://EXAMPLE.COM",
+            "see WWW.Example.com",
+        ] {
+            assert_eq!(
+                rules(false, true, &[]).initial_status(text),
+                COMMENT_STATUS_IN_REVIEW,
+                "{}",
+                text
+            );
+            assert_eq!(
+                rules(false, false, &[]).initial_status(text),
+                COMMENT_STATUS_APPROVED,
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn stop_words_go_to_review_in_any_case() {
+        let stop_words = vec!["casino".to_owned(), "free money".to_owned()];
+        let moderation = rules(false, false, &stop_words);
+        for text in [
+            "best casino in town",
+            "Best CASINO in town",
+            "Get Free Money now",
+            "casinos everywhere",
+        ] {
+            assert_eq!(
+                moderation.initial_status(text),
+                COMMENT_STATUS_IN_REVIEW,
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            moderation.initial_status("free and money"),
+            COMMENT_STATUS_APPROVED
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::{io::Cursor, path::Path};
+
+use image::{
+    codecs::{avif::AvifEncoder, jpeg::JpegEncoder},
+    error::{EncodingError, ImageFormatHint},
+    DynamicImage, ImageError, ImageFormat,
+};
+
+// lossless webp of a photo is bigger than its jpeg
+static WEBP_QUALITY: f32 = 80.0;
+
+/// Encodings stored for every rendition. Jpeg is the one every browser understands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PhotoFormat {
+    Avif,
+    Webp,
+    Jpeg,
+}
+
+/// Best compression first
+pub static ALL_PHOTO_FORMATS: [PhotoFormat; 3] =
+    [PhotoFormat::Avif, PhotoFormat::Webp, PhotoFormat::Jpeg];
+
+impl PhotoFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PhotoFormat::Avif => "avif",
+            PhotoFormat::Webp => "webp",
+            PhotoFormat::Jpeg => "jpg",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            PhotoFormat::Avif => "image/avif",
+            PhotoFormat::Webp => "image/webp",
+            PhotoFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    pub fn from_file_name(file_name: &str) -> Option<PhotoFormat> {
+        let extension = Path::new(file_name).extension()?.to_str()?;
+        ALL_PHOTO_FORMATS
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+
+    /// Same file in another format, e.g. `{uuid}_card.jpg` -> `{uuid}_card.avif`
+    pub fn file_name_from(&self, file_name: &str) -> String {
+        Path::new(file_name)
+            .with_extension(self.extension())
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Every stored encoding of the rendition. Photos uploaded before formats have the single file
+    pub fn all_file_names_from(file_name: &str) -> Vec<String> {
+        match PhotoFormat::from_file_name(file_name) {
+            Some(PhotoFormat::Jpeg) => ALL_PHOTO_FORMATS
+                .iter()
+                .map(|format| format.file_name_from(file_name))
+                .collect(),
+            _ => vec![file_name.to_owned()],
+        }
+    }
+
+    pub fn encode(&self, img: &DynamicImage) -> Result<Vec<u8>, ImageError> {
+        let mut content = Cursor::new(Vec::new());
+        match self {
+            // speed 8 of 10, the default one takes seconds for a full size photo
+            PhotoFormat::Avif => DynamicImage::ImageRgba8(img.to_rgba8())
+                .write_with_encoder(AvifEncoder::new_with_speed_quality(&mut content, 8, 70))?,
+            // the image crate encodes lossless webp only, libwebp does the lossy one
+            PhotoFormat::Webp => {
+                let rgba_img = DynamicImage::ImageRgba8(img.to_rgba8());
+                let webp = webp::Encoder::from_image(&rgba_img)
+                    .map_err(|err| webp_error(err.to_owned()))?
+                    .encode_simple(false, WEBP_QUALITY)
+                    .map_err(|err| webp_error(format!("{:?}", err)))?;
+                content.get_mut().extend_from_slice(&webp);
+            }
+            // jpeg has no alpha channel
+            PhotoFormat::Jpeg => DynamicImage::ImageRgb8(img.to_rgb8())
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut content, 85))?,
+        };
+        Ok(content.into_inner())
+    }
+
+    /// Formats listed in the `Accept` header, best first. Jpeg is always the last one
+    pub fn negotiate(accept: &str) -> Vec<PhotoFormat> {
+        let accepted_mime_types: Vec<&str> = accept
+            .split(',')
+            .filter_map(|media_range| {
+                let mut parts = media_range.split(';').map(str::trim);
+                let mime_type = parts.next()?;
+                // `q=0` means the format is refused
+                let is_refused = parts.any(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|quality| quality.parse::<f32>().ok())
+                        == Some(0.0)
+                });
+                (!is_refused).then_some(mime_type)
+            })
+            .collect();
+
+        ALL_PHOTO_FORMATS
+            .into_iter()
+            .filter(|format| {
+                *format == PhotoFormat::Jpeg || accepted_mime_types.contains(&format.mime_type())
+            })
+            .collect()
+    }
+}
+
+fn webp_error(message: String) -> ImageError {
+    ImageError::Encoding(EncodingError::new(
+        ImageFormatHint::Exact(ImageFormat::WebP),
+        message,
+    ))
+}
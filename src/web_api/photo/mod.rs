@@ -1,3 +1,13 @@
+mod format;
+mod rendition;
 mod service;
+mod storage;
+mod validation;
+mod watermark;
 
-pub use service::Service as PhotoService;
+pub use format::*;
+pub use rendition::*;
+pub use service::{Service as PhotoService, DELETED_PHOTO_PREFIX};
+pub use storage::*;
+pub use validation::*;
+pub use watermark::{load_watermark_font, load_watermark_logo, Watermark};
@@ -0,0 +1,56 @@
+use crate::db::ProfilePhotoModel;
+
+/// Generated sizes of every uploaded photo
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PhotoRendition {
+    /// Small previews in admin and edit pages
+    Thumbnail,
+    /// Home grid
+    Card,
+    /// Gallery and link previews
+    Full,
+}
+
+pub static ALL_RENDITIONS: [PhotoRendition; 3] = [
+    PhotoRendition::Thumbnail,
+    PhotoRendition::Card,
+    PhotoRendition::Full,
+];
+
+impl PhotoRendition {
+    /// Max width and height of the rendition
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            PhotoRendition::Thumbnail => (180, 275),
+            PhotoRendition::Card => (360, 550),
+            PhotoRendition::Full => (1080, 1650),
+        }
+    }
+
+    /// Thumbnail and card are cropped to the same aspect ratio, full size keeps it
+    pub fn is_cropped(&self) -> bool {
+        *self != PhotoRendition::Full
+    }
+
+    pub fn has_watermark(&self) -> bool {
+        *self != PhotoRendition::Thumbnail
+    }
+
+    pub fn file_name_for(&self, file_unique_id: &str, extension: &str) -> String {
+        match self {
+            PhotoRendition::Thumbnail => format!("{}_thumb.{}", file_unique_id, extension),
+            PhotoRendition::Card => format!("{}_card.{}", file_unique_id, extension),
+            PhotoRendition::Full => format!("{}.{}", file_unique_id, extension),
+        }
+    }
+
+    /// Photos uploaded before renditions have the single file only
+    pub fn stored_file_name<'a>(&self, profile_photo: &'a ProfilePhotoModel) -> &'a str {
+        let file_name_opt = match self {
+            PhotoRendition::Thumbnail => profile_photo.thumbnail_file_name.as_ref(),
+            PhotoRendition::Card => profile_photo.card_file_name.as_ref(),
+            PhotoRendition::Full => None,
+        };
+        file_name_opt.unwrap_or(&profile_photo.file_name)
+    }
+}
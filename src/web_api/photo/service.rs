@@ -1,208 +1,434 @@
 use std::{
-    env,
-    ffi::OsStr,
-    io,
-    path::{Path, PathBuf},
+    fs,
+    io::{self, Cursor},
 };
 
-use ab_glyph::FontRef;
 use actix_multipart::form::tempfile::TempFile;
-use image::{GenericImageView, ImageError};
+use image::{GenericImageView, ImageDecoder, ImageError, ImageFormat};
 use log::info;
-use std::fs;
 use uuid::Uuid;
 
-use image::io::Reader as ImageReader;
-use image::{DynamicImage, Rgba};
-use imageproc::drawing::draw_text_mut;
+use image::DynamicImage;
+use image::ImageReader;
 
-pub static MAX_PROFILE_PHOTO_HEIGHT: &'static u32 = &550;
-pub static MAX_PROFILE_PHOTO_WIDTH: &'static u32 = &360;
+use crate::db::ProfilePhotoModel;
+
+use super::{
+    format::{PhotoFormat, ALL_PHOTO_FORMATS},
+    rendition::{PhotoRendition, ALL_RENDITIONS},
+    storage::{photo_key, OriginalPhotoStorage, PhotoStorage},
+    watermark::Watermark,
+};
+
+pub static DELETED_PHOTO_PREFIX: &'static str = "delete_";
 
 pub struct Service;
 
 #[derive(Debug)]
-pub struct PhotoOnFS {
+pub struct StoredPhoto {
     pub name: String,
+    pub thumbnail_name: String,
+    pub card_name: String,
+    pub original_name: String,
     pub size: i64,
 }
 
 impl<'a> Service {
-    pub fn save_photo_on_fs(
+    /// Keeps the upload untouched in the private storage. Renditions are made later
+    /// by [`Service::process_photo`], names for them are reserved here.
+    /// Format comes from [`super::validate_upload`]
+    pub async fn save_original(
+        original_photo_storage: &OriginalPhotoStorage,
         original_file: &TempFile,
-        all_photos_folder_name: &str,
+        image_format: ImageFormat,
         profile_id: &Uuid,
-    ) -> Result<PhotoOnFS, io::Error> {
+    ) -> Result<StoredPhoto, io::Error> {
+        // every format from the upload allow-list has an extension
+        let original_file_extension = image_format.extensions_str()[0];
+
+        let from_file_path = original_file.file.path();
+        info!(
+            "Saving original from file {}",
+            &from_file_path.to_str().unwrap()
+        );
+
+        let new_file_unique_id = Uuid::new_v4().to_string();
+        let original_name = format!("{}.{}", new_file_unique_id, original_file_extension);
+        original_photo_storage
+            .0
+            .save(
+                &photo_key(profile_id, &original_name),
+                fs::read(from_file_path)?,
+                image_format.to_mime_type(),
+            )
+            .await?;
+
+        // db keeps the jpeg names, other formats are found by the extension
+        let rendition_names: Vec<String> = ALL_RENDITIONS
+            .iter()
+            .map(|rendition| {
+                rendition.file_name_for(&new_file_unique_id, PhotoFormat::Jpeg.extension())
+            })
+            .collect();
+
+        Ok(StoredPhoto {
+            thumbnail_name: rendition_names[0].clone(),
+            card_name: rendition_names[1].clone(),
+            name: rendition_names[2].clone(),
+            original_name,
+            // dirty usize 2 i64 converting
+            size: original_file.size.to_string().parse::<i64>().unwrap(),
+        })
+    }
+
+    /// Puts every rendition in every format into the public storage. Renditions are always
+    /// re-encoded from pixels, so exif (gps included) never gets into them
+    pub async fn process_photo(
+        photo_storage: &dyn PhotoStorage,
+        original_photo_storage: &OriginalPhotoStorage,
+        watermark: &Watermark,
+        profile_photo: &ProfilePhotoModel,
+    ) -> Result<(), io::Error> {
         fn image_error_to_io_error(err: &ImageError) -> io::Error {
             io::Error::new(io::ErrorKind::Other, format!("ImageError: {:?}", err))
         }
 
         fn image_scaling_post_processing(
-            profile_photo_folder_path: &PathBuf,
-        ) -> Result<(), io::Error> {
-            let image_for_post_processing = image::open(&profile_photo_folder_path)
-                .map_err(|err| image_error_to_io_error(&err))?;
-
+            image_for_post_processing: &DynamicImage,
+            rendition: PhotoRendition,
+        ) -> DynamicImage {
             let (width, height) = image_for_post_processing.dimensions();
+            let (max_width, max_height) = rendition.dimensions();
 
-            if (width > *MAX_PROFILE_PHOTO_WIDTH) || height > *MAX_PROFILE_PHOTO_HEIGHT {
-                info!("We need scaling: width: {}, height: {} ", width, height);
-
-                let resized_img = image_for_post_processing.resize_to_fill(
-                    *MAX_PROFILE_PHOTO_WIDTH,
-                    *MAX_PROFILE_PHOTO_HEIGHT,
-                    image::imageops::FilterType::Triangle,
+            if width > max_width || height > max_height {
+                info!(
+                    "We need scaling to {:?}: width: {}, height: {} ",
+                    rendition, width, height
                 );
 
-                resized_img
-                    .save(&profile_photo_folder_path)
-                    .map_err(|err| image_error_to_io_error(&err))
+                if rendition.is_cropped() {
+                    image_for_post_processing.resize_to_fill(
+                        max_width,
+                        max_height,
+                        image::imageops::FilterType::Triangle,
+                    )
+                } else {
+                    image_for_post_processing.resize(
+                        max_width,
+                        max_height,
+                        image::imageops::FilterType::Triangle,
+                    )
+                }
             } else {
                 info!(
-                    "We do not change scaling: width: {}, height: {}",
-                    width, height
+                    "We do not change scaling for {:?}: width: {}, height: {}",
+                    rendition, width, height
                 );
-                Ok(())
+                image_for_post_processing.clone()
             }
         }
 
-        fn add_watermark_post_processing(
-            profile_photo_folder_path: &PathBuf,
-        ) -> Result<(), io::Error> {
-            info!(
-                "Adding watermark for file: {}",
-                &profile_photo_folder_path.to_str().unwrap()
-            );
-            let reader = ImageReader::open(profile_photo_folder_path)?;
-            let mut img = reader
-                .decode()
-                .map_err(|err| image_error_to_io_error(&err))?;
-
-            let color = Rgba([255, 255, 255, 255]);
-
-            let text = "Anketa.VIP";
-
-            let font = FontRef::try_from_slice(include_bytes!("microsoftsansserif.ttf")).unwrap();
-
-            let (width, height) = img.dimensions();
-            let position = (width - 100, height - 25);
-
-            draw_text_mut(
-                &mut img,
-                color,
-                position.0 as i32,
-                position.1 as i32,
-                20.0,
-                &font,
-                text,
-            );
+        let original_name = profile_photo.original_file_name.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "Photo has no original to process")
+        })?;
+        let original_key = photo_key(&profile_photo.profile_id, original_name);
+        info!("Processing original {}", &original_key);
+        let original_content = original_photo_storage
+            .0
+            .load(&original_key)
+            .await?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Original {} is missing", &original_key),
+                )
+            })?;
+
+        // format was checked on upload
+        let mut decoder = ImageReader::new(Cursor::new(original_content))
+            .with_guessed_format()?
+            .into_decoder()
+            .map_err(|err| image_error_to_io_error(&err))?;
+        // phones keep pixels as the sensor saw them and put the rotation into exif
+        let orientation = decoder
+            .orientation()
+            .map_err(|err| image_error_to_io_error(&err))?;
+        let mut img =
+            DynamicImage::from_decoder(decoder).map_err(|err| image_error_to_io_error(&err))?;
+        img.apply_orientation(orientation);
+
+        for rendition in ALL_RENDITIONS {
+            let mut rendition_img = image_scaling_post_processing(&img, rendition);
+            if rendition.has_watermark() {
+                watermark.apply(&mut rendition_img);
+            }
 
-            let output_img: DynamicImage = img.into();
-            output_img
-                .save(profile_photo_folder_path)
-                .map_err(|err| image_error_to_io_error(&err))
+            let rendition_name = rendition.stored_file_name(profile_photo);
+            for format in ALL_PHOTO_FORMATS {
+                let content = format
+                    .encode(&rendition_img)
+                    .map_err(|err| image_error_to_io_error(&err))?;
+                let key = photo_key(
+                    &profile_photo.profile_id,
+                    &format.file_name_from(rendition_name),
+                );
+                info!("Saving new file: {}", &key);
+                photo_storage
+                    .save(&key, content, format.mime_type())
+                    .await?;
+            }
         }
+        Ok(())
+    }
 
-        let original_file_name = original_file.file_name.as_ref().unwrap();
-        let original_file_extension = Path::new(&original_file_name)
-            .extension()
-            .and_then(OsStr::to_str)
-            .map(|f| f.to_lowercase())
-            .unwrap_or(String::from("jpeg"));
+    /// Removes everything that was saved for the upload, e.g. when db insert failed
+    pub async fn discard_photo(
+        photo_storage: &dyn PhotoStorage,
+        original_photo_storage: &OriginalPhotoStorage,
+        profile_id: &Uuid,
+        stored_photo: &StoredPhoto,
+    ) -> Result<(), io::Error> {
+        for rendition_name in [
+            &stored_photo.thumbnail_name,
+            &stored_photo.card_name,
+            &stored_photo.name,
+        ] {
+            for file_name in PhotoFormat::all_file_names_from(rendition_name) {
+                photo_storage
+                    .delete(&photo_key(profile_id, &file_name))
+                    .await?;
+            }
+        }
+        original_photo_storage
+            .0
+            .delete(&photo_key(profile_id, &stored_photo.original_name))
+            .await
+    }
 
-        let mut profile_photo_folder_path =
-            Self::get_path_2_profile_photos(all_photos_folder_name, profile_id);
+    /// Removes renditions made for a photo that is already deleted
+    pub async fn discard_renditions(
+        photo_storage: &dyn PhotoStorage,
+        profile_photo: &ProfilePhotoModel,
+    ) -> Result<(), io::Error> {
+        let file_names = ALL_RENDITIONS.iter().flat_map(|rendition| {
+            PhotoFormat::all_file_names_from(rendition.stored_file_name(profile_photo))
+        });
+        for file_name in file_names {
+            photo_storage
+                .delete(&photo_key(&profile_photo.profile_id, &file_name))
+                .await?;
+        }
+        Ok(())
+    }
 
-        if !profile_photo_folder_path.exists() {
-            info!(
-                "Creating folder for new file: {}",
-                &profile_photo_folder_path.to_str().unwrap()
-            );
-            fs::create_dir_all(&profile_photo_folder_path)?;
+    /// Renditions are not removed, just marked with the prefix. Original stays in the private storage
+    pub async fn delete_photo(
+        photo_storage: &dyn PhotoStorage,
+        profile_photo: &ProfilePhotoModel,
+    ) -> Result<(), io::Error> {
+        let mut rendition_names: Vec<&str> = ALL_RENDITIONS
+            .iter()
+            .map(|rendition| rendition.stored_file_name(profile_photo))
+            .collect();
+        // old photos use the same file for every rendition
+        rendition_names.dedup();
+
+        let file_names = rendition_names
+            .into_iter()
+            .flat_map(PhotoFormat::all_file_names_from);
+        for file_name in file_names {
+            let deleted_file_name = DELETED_PHOTO_PREFIX.to_owned() + &file_name;
+            photo_storage
+                .rename(
+                    &photo_key(&profile_photo.profile_id, &file_name),
+                    &photo_key(&profile_photo.profile_id, &deleted_file_name),
+                )
+                .await?;
         }
+        Ok(())
+    }
 
-        let new_file_unique_id = Uuid::new_v4().to_string();
-        let new_file_name = format!("{}.{1}", new_file_unique_id, original_file_extension);
-        // add photo name with ext
-        profile_photo_folder_path.push(&new_file_name);
-        info!(
-            "Forming path for new file: {}",
-            &profile_photo_folder_path.to_str().unwrap()
-        );
+    /// Every key the photo may have in the public storage: live renditions, deleted ones and
+    /// the folder that profiles got on deletion before photo storages
+    pub fn all_file_keys(profile_photo: &ProfilePhotoModel) -> Vec<String> {
+        let mut rendition_names: Vec<&str> = ALL_RENDITIONS
+            .iter()
+            .map(|rendition| rendition.stored_file_name(profile_photo))
+            .collect();
+        rendition_names.dedup();
+
+        let profile_id = &profile_photo.profile_id;
+        let legacy_profile_folder = format!("{}_delete", profile_id);
+        rendition_names
+            .into_iter()
+            .flat_map(PhotoFormat::all_file_names_from)
+            .flat_map(|file_name| {
+                [
+                    photo_key(profile_id, &(DELETED_PHOTO_PREFIX.to_owned() + &file_name)),
+                    format!("{}/{}", &legacy_profile_folder, &file_name),
+                    photo_key(profile_id, &file_name),
+                ]
+            })
+            .collect()
+    }
 
-        let from_file_path = original_file.file.path();
+    pub async fn delete_profile(
+        photo_storage: &dyn PhotoStorage,
+        profile_id: &Uuid,
+        profile_photos: &Vec<ProfilePhotoModel>,
+    ) -> Result<(), io::Error> {
         info!(
-            "Copying data from file {} to file {1}",
-            &from_file_path.to_str().unwrap(),
-            &profile_photo_folder_path.to_str().unwrap()
+            "Deleting [{}] photos of profile [{}]",
+            profile_photos.len(),
+            profile_id
         );
-        fs::copy(&from_file_path, &profile_photo_folder_path)?;
-
-        image_scaling_post_processing(&profile_photo_folder_path)?;
-        add_watermark_post_processing(&profile_photo_folder_path)?;
+        for profile_photo in profile_photos {
+            Self::delete_photo(photo_storage, profile_photo).await?;
+        }
+        Ok(())
+    }
+}
 
-        Ok(PhotoOnFS {
-            name: new_file_name,
-            // dirty usize 2 i64 converting
-            size: original_file.size.to_string().parse::<i64>().unwrap(),
-        })
+#[cfg(test)]
+mod tests {
+    use std::{env, sync::Arc};
+
+    use chrono::Utc;
+    use image::{codecs::jpeg::JpegEncoder, Rgb, RgbImage};
+
+    use crate::web_api::photo::storage::FsPhotoStorage;
+
+    use super::*;
+
+    static GPS_MARKER: &[u8] = b"GPS-MARKER-49.8397N";
+
+    /// Exif APP1 segment with orientation 6 (rotate 90 clockwise to show) and a gps ifd
+    fn exif_segment() -> Vec<u8> {
+        let mut tiff: Vec<u8> = b"MM\0\x2a\0\0\0\x08".to_vec();
+        // ifd0 at 8: orientation and gps pointer
+        tiff.extend([0, 2]);
+        tiff.extend([0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]);
+        tiff.extend([0x88, 0x25, 0, 4, 0, 0, 0, 1, 0, 0, 0, 38]);
+        tiff.extend([0, 0, 0, 0]);
+        // gps ifd at 38: latitude ref and map datum, the datum text follows the ifd
+        let datum_offset = 38 + 2 + 2 * 12 + 4;
+        tiff.extend([0, 2]);
+        tiff.extend([0, 0x01, 0, 2, 0, 0, 0, 2, b'N', 0, 0, 0]);
+        tiff.extend([0, 0x12, 0, 2, 0, 0, 0, GPS_MARKER.len() as u8 + 1]);
+        tiff.extend((datum_offset as u32).to_be_bytes());
+        tiff.extend([0, 0, 0, 0]);
+        tiff.extend(GPS_MARKER);
+        tiff.push(0);
+
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend(((tiff.len() + 8) as u16).to_be_bytes());
+        segment.extend(b"Exif\0\0");
+        segment.extend(tiff);
+        segment
     }
 
-    pub fn delete_photo_from_fs(
-        all_photos_folder_name: &str,
-        profile_id: &Uuid,
-        photo_name: &str,
-    ) -> Result<(), io::Error> {
-        let profile_photo_folder_path =
-            Self::get_path_2_profile_photos(all_photos_folder_name, profile_id);
+    /// Phone-like jpeg: pixels as the sensor saw them, red left half and blue right half
+    fn original_jpeg() -> Vec<u8> {
+        let img = RgbImage::from_fn(80, 40, |x, _| {
+            if x < 40 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 0, 255])
+            }
+        });
+        let mut jpeg = vec![];
+        DynamicImage::ImageRgb8(img)
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, 95))
+            .unwrap();
+        // exif goes right after the start of image marker
+        let mut content = jpeg[..2].to_vec();
+        content.extend(exif_segment());
+        content.extend(&jpeg[2..]);
+        content
+    }
 
-        // move original photo
-        let mut profile_photo_old_path = profile_photo_folder_path.clone();
-        profile_photo_old_path.push(photo_name);
+    fn contains(content: &[u8], part: &[u8]) -> bool {
+        content.windows(part.len()).any(|window| window == part)
+    }
 
-        if !profile_photo_old_path.exists() {
-            info!(
-                "Cant find file {}. Was it deleted manually? ",
-                &profile_photo_old_path.to_str().unwrap()
-            );
-            return Ok(());
+    #[actix_rt::test]
+    async fn renditions_are_rotated_and_have_no_exif() {
+        let root = env::temp_dir().join(format!("photo-service-{}", Uuid::new_v4()));
+        let photo_storage = FsPhotoStorage::new(root.join("photos").to_str().unwrap(), "photos");
+        let original_photo_storage = OriginalPhotoStorage(Arc::new(FsPhotoStorage::new(
+            root.join("originals").to_str().unwrap(),
+            "originals",
+        )));
+        let profile_id = Uuid::new_v4();
+
+        let original = original_jpeg();
+        assert!(contains(&original, b"Exif\0\0") && contains(&original, GPS_MARKER));
+        original_photo_storage
+            .0
+            .save(
+                &photo_key(&profile_id, "original.jpg"),
+                original,
+                "image/jpeg",
+            )
+            .await
+            .unwrap();
+        let profile_photo = ProfilePhotoModel {
+            id: 1,
+            created_at: Utc::now().naive_utc(),
+            status: "processing".to_owned(),
+            file_name: "photo.jpg".to_owned(),
+            profile_id,
+            size: 0,
+            thumbnail_file_name: Some("photo_thumb.jpg".to_owned()),
+            card_file_name: Some("photo_card.jpg".to_owned()),
+            original_file_name: Some("original.jpg".to_owned()),
+            position: 0,
+            deleted_at: None,
+        };
+
+        Service::process_photo(
+            &photo_storage,
+            &original_photo_storage,
+            &Watermark::off(),
+            &profile_photo,
+        )
+        .await
+        .unwrap();
+
+        let stored = photo_storage.list().await.unwrap();
+        assert_eq!(stored.len(), ALL_RENDITIONS.len() * ALL_PHOTO_FORMATS.len());
+        for file in &stored {
+            let content = photo_storage.load(&file.key).await.unwrap().unwrap();
+            assert!(!contains(&content, b"Exif"), "{} has exif", file.key);
+            assert!(!contains(&content, b"EXIF"), "{} has exif", file.key);
+            assert!(!contains(&content, GPS_MARKER), "{} has gps", file.key);
         }
 
-        let mut profile_photo_new_path = profile_photo_folder_path.clone();
-        profile_photo_new_path.push("delete_".to_owned() + photo_name);
-
-        fs::rename(profile_photo_old_path, profile_photo_new_path)
-    }
-
-    pub fn delete_profile_from_fs(
-        all_photos_folder_name: &str,
-        profile_id: &Uuid,
-    ) -> Result<(), io::Error> {
-        let profile_photo_folder_path =
-            Self::get_path_2_profile_photos(all_photos_folder_name, profile_id);
-        if profile_photo_folder_path.exists() {
-            let new_profile_photo_folder_path =
-                profile_photo_folder_path.to_str().unwrap().to_owned() + "_delete";
-            info!(
-                "Deleting profile photo folder. From [{}] to [{}]",
-                &profile_photo_folder_path.to_str().unwrap(),
-                &new_profile_photo_folder_path
+        // full size is not cropped, so the whole turned photo is there: red on top now
+        for file_name in ["photo.jpg", "photo.webp"] {
+            let content = photo_storage
+                .load(&photo_key(&profile_id, file_name))
+                .await
+                .unwrap()
+                .unwrap();
+            let img = image::load_from_memory(&content).unwrap().to_rgb8();
+            assert_eq!(img.dimensions(), (40, 80), "{} is not rotated", file_name);
+            let top = img.get_pixel(20, 10);
+            let bottom = img.get_pixel(20, 70);
+            assert!(
+                top[0] > 200 && top[2] < 60,
+                "{} top is {:?}",
+                file_name,
+                top
+            );
+            assert!(
+                bottom[2] > 200 && bottom[0] < 60,
+                "{} bottom is {:?}",
+                file_name,
+                bottom
             );
-            fs::rename(profile_photo_folder_path, new_profile_photo_folder_path)
-        } else {
-            info!("Profile folder doesn't exist. Skipping");
-            Ok(())
         }
-    }
-
-    fn get_path_2_profile_photos(all_photos_folder_name: &str, profile_id: &Uuid) -> PathBuf {
-        let mut new_file_path = env::current_exe().unwrap();
-        // remove binary name
-        new_file_path.pop();
-        // add global_folder + profile folder name
-        new_file_path.push(all_photos_folder_name);
-        new_file_path.push(profile_id.to_string());
 
-        new_file_path
+        fs::remove_dir_all(root).unwrap();
     }
 }
@@ -0,0 +1,163 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use actix_web::web::{self, Bytes};
+use async_trait::async_trait;
+use futures::stream;
+use log::info;
+use uuid::Uuid;
+
+use super::{PhotoStorage, PhotoStream, StoredFile};
+
+static STREAM_CHUNK_SIZE: usize = 64 * 1024;
+static PROBE_FILE_PREFIX: &str = ".readyz-";
+
+/// Photos in the folder next to the binary
+pub struct FsPhotoStorage {
+    root_folder: PathBuf,
+    url_prefix: String,
+}
+
+impl FsPhotoStorage {
+    pub fn new(root_folder: &str, url_prefix: &str) -> Self {
+        FsPhotoStorage {
+            root_folder: PathBuf::from(root_folder),
+            url_prefix: url_prefix.to_owned(),
+        }
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        let mut path = self.root_folder.clone();
+        key.split('/').for_each(|part| path.push(part));
+        path
+    }
+}
+
+#[async_trait(?Send)]
+impl PhotoStorage for FsPhotoStorage {
+    async fn save(
+        &self,
+        key: &str,
+        content: Vec<u8>,
+        _content_type: &str,
+    ) -> Result<(), io::Error> {
+        let path = self.path_for(key);
+        if let Some(folder) = path.parent().filter(|folder| !folder.exists()) {
+            info!(
+                "Creating folder for new file: {}",
+                &folder.to_str().unwrap()
+            );
+            fs::create_dir_all(folder)?;
+        }
+
+        info!("Writing file: {}", &path.to_str().unwrap());
+        fs::write(path, content)
+    }
+
+    async fn load(&self, key: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        match fs::read(self.path_for(key)) {
+            Ok(content) => Ok(Some(content)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn stream(&self, key: &str) -> Result<Option<(u64, PhotoStream)>, io::Error> {
+        let file = match fs::File::open(self.path_for(key)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let size = file.metadata()?.len();
+        // reads go to the blocking pool, as actix-files does
+        let chunks = stream::try_unfold(file, |mut file| async move {
+            let (file, chunk) = web::block(move || {
+                let mut chunk = vec![0; STREAM_CHUNK_SIZE];
+                let read = file.read(&mut chunk)?;
+                chunk.truncate(read);
+                Ok::<_, io::Error>((file, chunk))
+            })
+            .await
+            .map_err(io::Error::other)??;
+            Ok((!chunk.is_empty()).then(|| (Bytes::from(chunk), file)))
+        });
+        Ok(Some((size, Box::pin(chunks))))
+    }
+
+    async fn size(&self, key: &str) -> Result<Option<u64>, io::Error> {
+        match fs::metadata(self.path_for(key)) {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), io::Error> {
+        let path = self.path_for(key);
+        if !path.exists() {
+            info!("Cant find file {}. Skipping", &path.to_str().unwrap());
+            return Ok(());
+        }
+
+        fs::remove_file(&path)?;
+        // profile folder goes away with its last file, fails silently while it has others
+        if let Some(folder) = path.parent().filter(|folder| *folder != self.root_folder) {
+            let _ = fs::remove_dir(folder);
+        }
+        Ok(())
+    }
+
+    async fn rename(&self, from_key: &str, to_key: &str) -> Result<(), io::Error> {
+        let from_path = self.path_for(from_key);
+        if !from_path.exists() {
+            info!(
+                "Cant find file {}. Was it deleted manually? ",
+                &from_path.to_str().unwrap()
+            );
+            return Ok(());
+        }
+
+        fs::rename(from_path, self.path_for(to_key))
+    }
+
+    // keys are `{folder}/{file_name}`, so two levels are enough
+    async fn list(&self) -> Result<Vec<StoredFile>, io::Error> {
+        let mut files = vec![];
+        for folder_entry in fs::read_dir(&self.root_folder)? {
+            let folder_entry = folder_entry?;
+            if !folder_entry.file_type()?.is_dir() {
+                continue;
+            }
+            let folder_name = folder_entry.file_name().to_string_lossy().into_owned();
+            for file_entry in fs::read_dir(folder_entry.path())? {
+                let file_entry = file_entry?;
+                let metadata = file_entry.metadata()?;
+                if metadata.is_file() {
+                    let file_name = file_entry.file_name().to_string_lossy().into_owned();
+                    files.push(StoredFile {
+                        key: format!("{}/{}", &folder_name, &file_name),
+                        size: metadata.len(),
+                        modified_at: metadata.modified()?.into(),
+                    });
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    // probe lies in the root, out of the way of `{folder}/{file_name}` keys and `list`
+    async fn ping(&self) -> Result<(), io::Error> {
+        let probe_path = self
+            .root_folder
+            .join(format!("{}{}", PROBE_FILE_PREFIX, Uuid::new_v4()));
+        fs::write(&probe_path, [])?;
+        fs::remove_file(&probe_path)
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("/{}/{}", &self.url_prefix, key)
+    }
+}
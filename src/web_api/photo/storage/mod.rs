@@ -0,0 +1,101 @@
+mod fs;
+mod s3;
+
+use std::{io, sync::Arc};
+
+use actix_web::web::Bytes;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::LocalBoxStream;
+use uuid::Uuid;
+
+use crate::config::Config;
+
+pub use fs::FsPhotoStorage;
+pub use s3::S3PhotoStorage;
+
+pub static PHOTO_STORAGE_FS: &str = "fs";
+pub static PHOTO_STORAGE_S3: &str = "s3";
+
+pub type PhotoStream = LocalBoxStream<'static, Result<Bytes, io::Error>>;
+
+/// File found by [`PhotoStorage::list`]
+#[derive(Debug, PartialEq)]
+pub struct StoredFile {
+    pub key: String,
+    /// Bytes
+    pub size: u64,
+    pub modified_at: DateTime<Utc>,
+}
+
+/// Place where profile photos live. Files are addressed by keys like `{profile_id}/{file_name}`
+#[async_trait(?Send)]
+pub trait PhotoStorage: Send + Sync {
+    async fn save(&self, key: &str, content: Vec<u8>, content_type: &str) -> Result<(), io::Error>;
+
+    /// Missing file is `None`
+    async fn load(&self, key: &str) -> Result<Option<Vec<u8>>, io::Error>;
+
+    /// Size in bytes and content read chunk by chunk, for responses. Missing file is `None`
+    async fn stream(&self, key: &str) -> Result<Option<(u64, PhotoStream)>, io::Error>;
+
+    /// Size in bytes. Missing file is `None`
+    async fn size(&self, key: &str) -> Result<Option<u64>, io::Error>;
+
+    /// Missing file is not an error
+    async fn delete(&self, key: &str) -> Result<(), io::Error>;
+
+    /// Missing file is not an error
+    async fn rename(&self, from_key: &str, to_key: &str) -> Result<(), io::Error>;
+
+    /// Every stored file
+    async fn list(&self) -> Result<Vec<StoredFile>, io::Error>;
+
+    /// Storage answers and takes new files. Leaves nothing under photo keys
+    async fn ping(&self) -> Result<(), io::Error>;
+
+    /// Url for templates and api. Points to the photo endpoint unless s3 has a public url
+    fn url(&self, key: &str) -> String;
+}
+
+/// Untouched uploads. Lives apart from the public storage and is never served
+pub struct OriginalPhotoStorage(pub Arc<dyn PhotoStorage>);
+
+pub fn photo_key(profile_id: &Uuid, file_name: &str) -> String {
+    format!("{}/{}", profile_id, file_name)
+}
+
+pub fn init_photo_storage(
+    config: &Config,
+    all_photos_os_folder_opt: &Option<String>,
+) -> Arc<dyn PhotoStorage> {
+    if config.photo_storage == PHOTO_STORAGE_S3 {
+        Arc::new(S3PhotoStorage::new(config, &config.s3_bucket))
+    } else if config.photo_storage == PHOTO_STORAGE_FS {
+        Arc::new(FsPhotoStorage::new(
+            all_photos_os_folder_opt.as_ref().unwrap(),
+            &config.all_photos_folder_name,
+        ))
+    } else {
+        panic!("PHOTO_STORAGE must be fs or s3")
+    }
+}
+
+pub fn init_original_photo_storage(
+    config: &Config,
+    original_photos_os_folder_opt: &Option<String>,
+) -> OriginalPhotoStorage {
+    if config.photo_storage == PHOTO_STORAGE_S3 {
+        OriginalPhotoStorage(Arc::new(S3PhotoStorage::new(
+            config,
+            &config.s3_originals_bucket,
+        )))
+    } else if config.photo_storage == PHOTO_STORAGE_FS {
+        OriginalPhotoStorage(Arc::new(FsPhotoStorage::new(
+            original_photos_os_folder_opt.as_ref().unwrap(),
+            &config.original_photos_folder_name,
+        )))
+    } else {
+        panic!("PHOTO_STORAGE must be fs or s3")
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use actix_multipart::form::tempfile::TempFile;
+use image::{ImageFormat, ImageReader};
+use log::info;
+
+/// Formats we can decode. Avif is encoded only, the decoder needs native libs
+pub static ALLOWED_UPLOAD_FORMATS: [ImageFormat; 3] =
+    [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP];
+
+// longest magic number among allowed formats fits easily
+static MAGIC_BYTES_LENGTH: usize = 32;
+
+/// Why an upload was refused before decoding
+#[derive(Debug)]
+pub enum UploadError {
+    UnsupportedFormat,
+    TooLarge,
+    TooManyPixels,
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for UploadError {}
+
+/// File name and content type come from the client, so the format is taken from the content.
+/// Pixels are counted from the header, before anything is decoded
+pub fn validate_upload(
+    upload: &TempFile,
+    max_bytes: usize,
+    max_megapixels: u64,
+) -> Result<ImageFormat, UploadError> {
+    validate_image(upload.file.path(), upload.size, max_bytes, max_megapixels)
+}
+
+fn validate_image(
+    path: &Path,
+    size: usize,
+    max_bytes: usize,
+    max_megapixels: u64,
+) -> Result<ImageFormat, UploadError> {
+    if size > max_bytes {
+        info!("Upload is too large: [{}] bytes", size);
+        return Err(UploadError::TooLarge);
+    }
+
+    let mut magic_bytes = Vec::with_capacity(MAGIC_BYTES_LENGTH);
+    File::open(path)
+        .and_then(|file| {
+            file.take(MAGIC_BYTES_LENGTH as u64)
+                .read_to_end(&mut magic_bytes)
+        })
+        .map_err(|_| UploadError::UnsupportedFormat)?;
+    let format = image::guess_format(&magic_bytes)
+        .ok()
+        .filter(|format| ALLOWED_UPLOAD_FORMATS.contains(format))
+        .ok_or(UploadError::UnsupportedFormat)?;
+
+    let file = File::open(path).map_err(|_| UploadError::UnsupportedFormat)?;
+    let (width, height) = ImageReader::with_format(BufReader::new(file), format)
+        .into_dimensions()
+        .map_err(|_| UploadError::UnsupportedFormat)?;
+    if width as u64 * height as u64 > max_megapixels * 1_000_000 {
+        info!("Upload has too many pixels: [{}x{}]", width, height);
+        return Err(UploadError::TooManyPixels);
+    }
+
+    Ok(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, io::Cursor, path::PathBuf};
+
+    use image::{DynamicImage, RgbImage};
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn encoded(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Cursor::new(vec![]);
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+            .write_to(&mut bytes, format)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    fn validate(
+        file_name: &str,
+        content: &[u8],
+        max_bytes: usize,
+    ) -> Result<ImageFormat, UploadError> {
+        let path: PathBuf = env::temp_dir().join(format!("{}-{}", Uuid::new_v4(), file_name));
+        fs::write(&path, content).unwrap();
+        let result = validate_image(&path, content.len(), max_bytes, 1);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn allowed_formats_are_accepted() {
+        for format in ALLOWED_UPLOAD_FORMATS {
+            let content = encoded(40, 30, format);
+            assert_eq!(validate("photo", &content, 1_000_000).unwrap(), format);
+        }
+    }
+
+    #[test]
+    fn format_comes_from_content_not_extension() {
+        let content = encoded(40, 30, ImageFormat::Jpeg);
+
+        assert_eq!(
+            validate("photo.png", &content, 1_000_000).unwrap(),
+            ImageFormat::Jpeg
+        );
+    }
+
+    #[test]
+    fn spoofed_extension_is_rejected() {
+        let result = validate("photo.jpg", b"<?php echo 'not a photo'; ?>", 1_000_000);
+
+        assert!(matches!(result, Err(UploadError::UnsupportedFormat)));
+    }
+
+    #[test]
+    fn format_out_of_allow_list_is_rejected() {
+        let content = encoded(40, 30, ImageFormat::Gif);
+
+        assert!(matches!(
+            validate("photo.gif", &content, 1_000_000),
+            Err(UploadError::UnsupportedFormat)
+        ));
+    }
+
+    #[test]
+    fn truncated_image_is_rejected() {
+        let content = encoded(40, 30, ImageFormat::Png);
+
+        assert!(matches!(
+            validate("photo.png", &content[..20], 1_000_000),
+            Err(UploadError::UnsupportedFormat)
+        ));
+    }
+
+    #[test]
+    fn file_over_byte_limit_is_rejected() {
+        let content = encoded(40, 30, ImageFormat::Png);
+
+        assert!(matches!(
+            validate("photo.png", &content, content.len() - 1),
+            Err(UploadError::TooLarge)
+        ));
+        assert!(validate("photo.png", &content, content.len()).is_ok());
+    }
+
+    #[test]
+    fn image_over_megapixel_limit_is_rejected() {
+        let content = encoded(1001, 1000, ImageFormat::Png);
+
+        assert!(matches!(
+            validate("photo.png", &content, content.len()),
+            Err(UploadError::TooManyPixels)
+        ));
+        let content = encoded(1000, 1000, ImageFormat::Png);
+        assert!(validate("photo.png", &content, content.len()).is_ok());
+    }
+}
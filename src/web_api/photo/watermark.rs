@@ -0,0 +1,215 @@
+use std::fs;
+
+use ab_glyph::{Font, FontArc, PxScale, ScaleFont};
+use image::{imageops, DynamicImage, GenericImageView, Rgba, RgbaImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+use log::{error, info};
+use rust_i18n::t;
+
+use crate::config::Config;
+
+pub static WATERMARK_TEXT: &str = "text";
+pub static WATERMARK_LOGO: &str = "logo";
+
+// font size for measuring, the real one is derived from the image width
+static MEASURE_FONT_SIZE: f32 = 100.0;
+// gap between the watermark and image edges, part of the shorter side
+static MARGIN_RATIO: f32 = 0.02;
+
+enum WatermarkMark {
+    Text {
+        text: String,
+        font: FontArc,
+        color: [u8; 3],
+    },
+    Logo(RgbaImage),
+}
+
+enum WatermarkAnchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+/// Brand mark drawn on card and full renditions. Built once on start from `Config`
+pub struct Watermark {
+    mark_opt: Option<WatermarkMark>,
+    anchor: WatermarkAnchor,
+    opacity: f32,
+    // watermark width as part of the image width
+    scale: f32,
+}
+
+impl Watermark {
+    /// Values and files are checked by `Config::load`, so nothing here fails on start
+    pub fn new(config: &Config) -> Self {
+        let mark_opt = if config.watermark == WATERMARK_LOGO {
+            load_watermark_logo(&config.watermark_logo_path)
+                .inspect_err(|err| error!("Watermark is off: {}", err))
+                .ok()
+                .map(WatermarkMark::Logo)
+        } else if config.watermark == WATERMARK_TEXT {
+            let text = if config.watermark_text.is_empty() {
+                t!("watermark_text").to_string()
+            } else {
+                config.watermark_text.clone()
+            };
+            let font = load_watermark_font(&config.watermark_font_path)
+                .inspect_err(|err| error!("Watermark is off: {}", err))
+                .ok();
+            font.map(|font| WatermarkMark::Text {
+                text,
+                font,
+                color: parse_color(&config.watermark_color).unwrap_or([255, 255, 255]),
+            })
+        } else {
+            None
+        };
+
+        Watermark {
+            mark_opt,
+            anchor: parse_anchor(&config.watermark_anchor).unwrap_or(WatermarkAnchor::BottomRight),
+            opacity: config.watermark_opacity,
+            scale: config.watermark_scale,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn off() -> Self {
+        Watermark {
+            mark_opt: None,
+            anchor: WatermarkAnchor::BottomRight,
+            opacity: 1.0,
+            scale: 1.0,
+        }
+    }
+
+    pub fn apply(&self, img: &mut DynamicImage) {
+        let Some(mark) = &self.mark_opt else {
+            return;
+        };
+
+        let (width, height) = img.dimensions();
+        let target_width = ((width as f32 * self.scale) as u32).max(1);
+        let layer = match mark {
+            WatermarkMark::Text { text, font, color } => {
+                text_layer(text, font, *color, target_width, self.opacity)
+            }
+            WatermarkMark::Logo(logo) => logo_layer(logo, target_width, self.opacity),
+        };
+
+        let (x, y) = self.position((width, height), layer.dimensions());
+        info!("Adding watermark at x: {}, y: {}", x, y);
+        imageops::overlay(img, &layer, x, y);
+    }
+
+    // saturating math keeps the mark inside on images smaller than the mark
+    fn position(
+        &self,
+        (width, height): (u32, u32),
+        (layer_width, layer_height): (u32, u32),
+    ) -> (i64, i64) {
+        let margin = (width.min(height) as f32 * MARGIN_RATIO) as u32;
+        let left = margin;
+        let top = margin;
+        let right = width.saturating_sub(layer_width + margin);
+        let bottom = height.saturating_sub(layer_height + margin);
+        let (x, y) = match self.anchor {
+            WatermarkAnchor::TopLeft => (left, top),
+            WatermarkAnchor::TopRight => (right, top),
+            WatermarkAnchor::BottomLeft => (left, bottom),
+            WatermarkAnchor::BottomRight => (right, bottom),
+            WatermarkAnchor::Center => (
+                width.saturating_sub(layer_width) / 2,
+                height.saturating_sub(layer_height) / 2,
+            ),
+        };
+        (x as i64, y as i64)
+    }
+}
+
+fn text_layer(
+    text: &str,
+    font: &FontArc,
+    color: [u8; 3],
+    target_width: u32,
+    opacity: f32,
+) -> RgbaImage {
+    let (measured_width, _) = text_size(MEASURE_FONT_SIZE, font, text);
+    let font_size = MEASURE_FONT_SIZE * target_width as f32 / measured_width.max(1) as f32;
+    let scale = PxScale::from(font_size);
+    let (layer_width, _) = text_size(scale, font, text);
+    // glyphs sit on the ascent line, so the layer needs the full line height, not the ink one
+    let layer_height = font.as_scaled(scale).height().ceil() as u32;
+
+    // colour everywhere and only alpha drawn, so glyph edges do not get dark
+    let mut layer = RgbaImage::from_pixel(
+        layer_width.max(1),
+        layer_height.max(1),
+        Rgba([color[0], color[1], color[2], 0]),
+    );
+    let alpha = (255.0 * opacity) as u8;
+    draw_text_mut(
+        &mut layer,
+        Rgba([color[0], color[1], color[2], alpha]),
+        0,
+        0,
+        scale,
+        font,
+        text,
+    );
+    layer
+}
+
+fn logo_layer(logo: &RgbaImage, target_width: u32, opacity: f32) -> RgbaImage {
+    let target_height =
+        ((logo.height() as f32 * target_width as f32 / logo.width() as f32) as u32).max(1);
+    let mut layer = imageops::resize(
+        logo,
+        target_width,
+        target_height,
+        imageops::FilterType::Triangle,
+    );
+    layer
+        .pixels_mut()
+        .for_each(|pixel| pixel[3] = (pixel[3] as f32 * opacity) as u8);
+    layer
+}
+
+// bundled font has latin and cyrillic, other scripts need their own font
+pub fn load_watermark_font(font_path: &str) -> Result<FontArc, String> {
+    if font_path.is_empty() {
+        return Ok(FontArc::try_from_slice(include_bytes!("microsoftsansserif.ttf")).unwrap());
+    }
+    let font_data = fs::read(font_path)
+        .map_err(|err| format!("WATERMARK_FONT_PATH {} is not readable: {}", font_path, err))?;
+    FontArc::try_from_vec(font_data)
+        .map_err(|_| format!("WATERMARK_FONT_PATH {} is not a ttf or otf font", font_path))
+}
+
+pub fn load_watermark_logo(logo_path: &str) -> Result<RgbaImage, String> {
+    image::open(logo_path)
+        .map(|logo| logo.to_rgba8())
+        .map_err(|err| format!("WATERMARK_LOGO_PATH {} is not an image: {}", logo_path, err))
+}
+
+// `#RRGGBB`
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex_color = color.trim_start_matches('#');
+    hex::decode(hex_color)
+        .ok()
+        .and_then(|bytes| <[u8; 3]>::try_from(bytes).ok())
+}
+
+fn parse_anchor(anchor: &str) -> Option<WatermarkAnchor> {
+    match anchor {
+        "top_left" => Some(WatermarkAnchor::TopLeft),
+        "top_right" => Some(WatermarkAnchor::TopRight),
+        "bottom_left" => Some(WatermarkAnchor::BottomLeft),
+        "bottom_right" => Some(WatermarkAnchor::BottomRight),
+        "center" => Some(WatermarkAnchor::Center),
+        _ => None,
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use crate::config::RateLimitConfig;
+
+use super::RateLimitStore;
+
+// expired windows are dropped once the map gets this big
+static PRUNE_THRESHOLD: usize = 10_000;
+
+struct Window {
+    ends_at: Instant,
+    hits: u32,
+}
+
+/// Fixed windows in process memory. Every server process counts on its own
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+#[async_trait(?Send)]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn hit(&self, keys: &[String], limit: &RateLimitConfig) -> Result<(), Duration> {
+        self.hit_at(keys, limit, Instant::now())
+    }
+}
+
+impl MemoryRateLimitStore {
+    fn hit_at(
+        &self,
+        keys: &[String],
+        limit: &RateLimitConfig,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() >= PRUNE_THRESHOLD {
+            windows.retain(|_, window| window.ends_at > now);
+        }
+
+        let mut retry_after_opt: Option<Duration> = None;
+        for key in keys {
+            let window = windows.entry(key.to_string()).or_insert(Window {
+                ends_at: now,
+                hits: 0,
+            });
+            if window.ends_at <= now {
+                window.ends_at = now + Duration::from_secs(limit.window_seconds);
+                window.hits = 0;
+            }
+            if window.hits >= limit.requests {
+                retry_after_opt = retry_after_opt.max(Some(window.ends_at - now));
+            }
+        }
+        if let Some(retry_after) = retry_after_opt {
+            return Err(retry_after);
+        }
+
+        for key in keys {
+            if let Some(window) = windows.get_mut(key) {
+                window.hits += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static LIMIT: RateLimitConfig = RateLimitConfig {
+        requests: 2,
+        window_seconds: 60,
+    };
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn rejects_hits_over_the_limit() {
+        let store = MemoryRateLimitStore::default();
+        let now = Instant::now();
+        let ip_keys = keys(&["comment:ip:10.0.0.1"]);
+
+        assert!(store.hit_at(&ip_keys, &LIMIT, now).is_ok());
+        assert!(store.hit_at(&ip_keys, &LIMIT, now).is_ok());
+        assert_eq!(
+            store.hit_at(&ip_keys, &LIMIT, now + Duration::from_secs(15)),
+            Err(Duration::from_secs(45))
+        );
+        assert!(store
+            .hit_at(&keys(&["comment:ip:10.0.0.2"]), &LIMIT, now)
+            .is_ok());
+    }
+
+    #[test]
+    fn new_window_resets_hits() {
+        let store = MemoryRateLimitStore::default();
+        let now = Instant::now();
+        let ip_keys = keys(&["comment:ip:10.0.0.1"]);
+
+        store.hit_at(&ip_keys, &LIMIT, now).unwrap();
+        store.hit_at(&ip_keys, &LIMIT, now).unwrap();
+        assert!(store
+            .hit_at(&ip_keys, &LIMIT, now + Duration::from_secs(59))
+            .is_err());
+
+        let next_window = now + Duration::from_secs(60);
+        assert!(store.hit_at(&ip_keys, &LIMIT, next_window).is_ok());
+        assert!(store.hit_at(&ip_keys, &LIMIT, next_window).is_ok());
+        assert!(store.hit_at(&ip_keys, &LIMIT, next_window).is_err());
+    }
+
+    #[test]
+    fn rejected_hit_is_not_counted_for_any_key() {
+        let store = MemoryRateLimitStore::default();
+        let now = Instant::now();
+        let user_keys = keys(&["comment:ip:10.0.0.1", "comment:user:42"]);
+
+        store
+            .hit_at(&keys(&["comment:user:42"]), &LIMIT, now)
+            .unwrap();
+        store
+            .hit_at(&keys(&["comment:user:42"]), &LIMIT, now)
+            .unwrap();
+        assert!(store.hit_at(&user_keys, &LIMIT, now).is_err());
+        assert!(store.hit_at(&user_keys, &LIMIT, now).is_err());
+
+        // the ip was only rejected, so it still has the whole limit
+        let ip_keys = keys(&["comment:ip:10.0.0.1"]);
+        assert!(store.hit_at(&ip_keys, &LIMIT, now).is_ok());
+        assert!(store.hit_at(&ip_keys, &LIMIT, now).is_ok());
+        assert!(store.hit_at(&ip_keys, &LIMIT, now).is_err());
+    }
+
+    #[test]
+    fn retry_after_is_the_longest_wait() {
+        let store = MemoryRateLimitStore::default();
+        let now = Instant::now();
+        let short_limit = RateLimitConfig {
+            requests: 1,
+            window_seconds: 10,
+        };
+        store
+            .hit_at(&keys(&["comment:ip:10.0.0.1"]), &short_limit, now)
+            .unwrap();
+        store
+            .hit_at(&keys(&["comment:user:42"]), &LIMIT, now)
+            .unwrap();
+        store
+            .hit_at(&keys(&["comment:user:42"]), &LIMIT, now)
+            .unwrap();
+
+        let result = store.hit_at(
+            &keys(&["comment:ip:10.0.0.1", "comment:user:42"]),
+            &LIMIT,
+            now + Duration::from_secs(5),
+        );
+        assert_eq!(result, Err(Duration::from_secs(55)));
+    }
+}
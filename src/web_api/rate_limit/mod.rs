@@ -0,0 +1,382 @@
+mod memory;
+
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::Method,
+    web, Error, ResponseError,
+};
+use async_trait::async_trait;
+use futures::future::LocalBoxFuture;
+use log::warn;
+
+use crate::config::{Config, RateLimitConfig};
+
+use super::{
+    auth::token_user_id,
+    routes::{too_many_requests_page, JsonError},
+};
+
+pub use memory::MemoryRateLimitStore;
+
+pub static RATE_LIMIT_STORE_MEMORY: &str = "memory";
+
+pub static RATE_LIMIT_PROFILE: &str = "profile";
+pub static RATE_LIMIT_COMMENT: &str = "comment";
+pub static RATE_LIMIT_PHOTO_UPLOAD: &str = "photo_upload";
+pub static RATE_LIMIT_SIGN_IN: &str = "sign_in";
+
+/// Hit counters shared by all workers. Keys look like `comment:ip:10.0.0.1` or `comment:user:42`
+#[async_trait(?Send)]
+pub trait RateLimitStore: Send + Sync {
+    /// Counts a hit of every key. When any key is over the limit nothing is counted and the error
+    /// is the longest time left until a window ends
+    async fn hit(&self, keys: &[String], limit: &RateLimitConfig) -> Result<(), Duration>;
+}
+
+/// Limits per route class from the config. Created once and shared as app data
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    limits: HashMap<&'static str, RateLimitConfig>,
+    is_forwarded_trusted: bool,
+    jwt_secret: String,
+}
+
+impl RateLimiter {
+    pub fn new(config: &Config) -> Self {
+        let store: Arc<dyn RateLimitStore> = if config.rate_limit_store == RATE_LIMIT_STORE_MEMORY {
+            Arc::new(MemoryRateLimitStore::default())
+        } else {
+            panic!("RATE_LIMIT_STORE must be memory")
+        };
+        let limits = [
+            (RATE_LIMIT_PROFILE, config.rate_limit_profile),
+            (RATE_LIMIT_COMMENT, config.rate_limit_comment),
+            (RATE_LIMIT_PHOTO_UPLOAD, config.rate_limit_photo_upload),
+            (RATE_LIMIT_SIGN_IN, config.rate_limit_sign_in),
+        ]
+        .into_iter()
+        .filter_map(|(class, limit_opt)| limit_opt.map(|limit| (class, limit)))
+        .collect();
+
+        RateLimiter {
+            store,
+            limits,
+            is_forwarded_trusted: config.rate_limit_trust_forwarded,
+            jwt_secret: config.jwt_secret.clone(),
+        }
+    }
+
+    /// Both the client ip and the signed in user are counted, a hit over either limit counts for neither
+    async fn check(&self, class: &str, request: &ServiceRequest) -> Result<(), Duration> {
+        let Some(limit) = self.limits.get(class) else {
+            return Ok(());
+        };
+
+        let client_ip_opt = if self.is_forwarded_trusted {
+            request
+                .connection_info()
+                .realip_remote_addr()
+                .map(|addr| addr.to_string())
+        } else {
+            request.peer_addr().map(|addr| addr.ip().to_string())
+        };
+        let user_id_opt = token_user_id(request.request(), &self.jwt_secret);
+
+        let mut keys = vec![];
+        if let Some(client_ip) = client_ip_opt {
+            keys.push(format!("{}:ip:{}", class, client_ip));
+        }
+        if let Some(user_id) = user_id_opt {
+            keys.push(format!("{}:user:{}", class, user_id));
+        }
+
+        self.store.hit(&keys, limit).await.inspect_err(|_| {
+            warn!(
+                "Rate limit [{}] is reached. User [{}]",
+                class,
+                user_id_opt.map(|id| id.to_string()).unwrap_or_default()
+            )
+        })
+    }
+}
+
+/// Middleware for a route class. Reads pass through, writes over the limit get 429 with
+/// `Retry-After`: the message page for html routes, the json error for api ones
+pub struct RateLimit {
+    class: &'static str,
+    is_json: bool,
+}
+
+impl RateLimit {
+    pub fn html(class: &'static str) -> Self {
+        RateLimit {
+            class,
+            is_json: false,
+        }
+    }
+
+    pub fn json(class: &'static str) -> Self {
+        RateLimit {
+            class,
+            is_json: true,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            class: self.class,
+            is_json: self.is_json,
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    class: &'static str,
+    is_json: bool,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let class = self.class;
+        let is_json = self.is_json;
+
+        Box::pin(async move {
+            let is_read = [Method::GET, Method::HEAD, Method::OPTIONS].contains(request.method());
+            let rate_limiter_opt = request.app_data::<web::Data<RateLimiter>>().cloned();
+            if let (false, Some(rate_limiter)) = (is_read, rate_limiter_opt) {
+                if let Err(retry_after) = rate_limiter.check(class, &request).await {
+                    // whole seconds, never 0
+                    let retry_after_seconds = retry_after.as_secs() + 1;
+                    let response = if is_json {
+                        JsonError::TooManyRequests(retry_after_seconds).error_response()
+                    } else {
+                        too_many_requests_page(request.request(), retry_after_seconds).await
+                    };
+                    return Ok(request.into_response(response).map_into_right_body());
+                }
+            }
+
+            service
+                .call(request)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        body::to_bytes,
+        http::{
+            header::{HeaderMap, AUTHORIZATION, RETRY_AFTER},
+            StatusCode,
+        },
+        test::{self, TestRequest},
+        web::Bytes,
+        App, HttpResponse,
+    };
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    use crate::{db::DbProvider, web_api::auth::AuthSessionManager};
+
+    use super::*;
+
+    fn config() -> Config {
+        Config::for_tests(&[("RATE_LIMIT_COMMENT", "2/60")])
+    }
+
+    /// Sends the requests one by one to a route behind `rate_limit`
+    async fn send_all(
+        rate_limit: RateLimit,
+        requests: Vec<TestRequest>,
+    ) -> Vec<(StatusCode, HeaderMap, Bytes)> {
+        let config = config();
+        let db_con = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(RateLimiter::new(&config)))
+                .app_data(web::Data::new(DbProvider::new(db_con)))
+                .app_data(web::Data::new(config))
+                .service(
+                    web::resource("/comment")
+                        .wrap(rate_limit)
+                        .to(|| async { HttpResponse::Ok().finish() }),
+                ),
+        )
+        .await;
+
+        let mut responses = vec![];
+        for request in requests {
+            let response = test::call_service(&app, request.uri("/comment").to_request()).await;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = to_bytes(response.into_body()).await.unwrap();
+            responses.push((status, headers, body));
+        }
+        responses
+    }
+
+    fn post_from(ip: &str) -> TestRequest {
+        TestRequest::post().peer_addr(format!("{}:40000", ip).parse().unwrap())
+    }
+
+    fn post_as_user(ip: &str, user_id: i64) -> TestRequest {
+        let token = AuthSessionManager::new(&config()).get_access_token(
+            user_id,
+            "Anna",
+            "anna@example.com",
+            "user",
+            &Uuid::new_v4(),
+        );
+        post_from(ip).insert_header((AUTHORIZATION, format!("Bearer {}", token)))
+    }
+
+    fn statuses(responses: &[(StatusCode, HeaderMap, Bytes)]) -> Vec<StatusCode> {
+        responses.iter().map(|(status, _, _)| *status).collect()
+    }
+
+    #[actix_rt::test]
+    async fn json_route_over_the_limit_gets_json_429() {
+        let responses = send_all(
+            RateLimit::json(RATE_LIMIT_COMMENT),
+            vec![
+                post_from("10.0.0.1"),
+                post_from("10.0.0.1"),
+                post_from("10.0.0.1"),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            statuses(&responses),
+            vec![
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::TOO_MANY_REQUESTS
+            ]
+        );
+        let (_, headers, body) = &responses[2];
+        assert_eq!(headers.get(RETRY_AFTER).unwrap(), "60");
+        let body: Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(body["error"], json!("too_many_requests"));
+    }
+
+    #[actix_rt::test]
+    async fn html_route_over_the_limit_gets_the_message_page() {
+        let responses = send_all(
+            RateLimit::html(RATE_LIMIT_COMMENT),
+            vec![
+                post_from("10.0.0.1"),
+                post_from("10.0.0.1"),
+                post_from("10.0.0.1"),
+            ],
+        )
+        .await;
+
+        let (status, headers, body) = &responses[2];
+        assert_eq!(*status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(headers.get(RETRY_AFTER).unwrap(), "60");
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("<html"));
+        assert!(serde_json::from_str::<Value>(&body).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn reads_are_not_limited() {
+        let get = || TestRequest::get().peer_addr("10.0.0.1:40000".parse().unwrap());
+        let responses = send_all(
+            RateLimit::json(RATE_LIMIT_COMMENT),
+            vec![get(), get(), get()],
+        )
+        .await;
+
+        assert_eq!(statuses(&responses), vec![StatusCode::OK; 3]);
+    }
+
+    #[actix_rt::test]
+    async fn user_is_limited_from_any_ip() {
+        let responses = send_all(
+            RateLimit::json(RATE_LIMIT_COMMENT),
+            vec![
+                post_as_user("10.0.0.1", 42),
+                post_as_user("10.0.0.2", 42),
+                post_as_user("10.0.0.3", 42),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            statuses(&responses),
+            vec![
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::TOO_MANY_REQUESTS
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn rejected_user_does_not_use_up_the_ip_limit() {
+        let responses = send_all(
+            RateLimit::json(RATE_LIMIT_COMMENT),
+            vec![
+                post_as_user("10.0.0.1", 42),
+                post_as_user("10.0.0.1", 42),
+                post_as_user("10.0.0.2", 42),
+                post_as_user("10.0.0.2", 42),
+                post_from("10.0.0.2"),
+                post_from("10.0.0.2"),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            statuses(&responses),
+            vec![
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::OK,
+                StatusCode::OK,
+            ]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::{cell::Cell, future::Future};
+
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+};
+use uuid::Uuid;
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// ids from a proxy are kept only while they are short and printable
+static MAX_REQUEST_ID_LEN: usize = 64;
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+/// Request scoped values every log line carries
+struct RequestContext {
+    request_id: String,
+    user_id: Cell<Option<i64>>,
+}
+
+/// Id from the `X-Request-Id` of a proxy in front or a new one
+pub fn request_id(request: &ServiceRequest) -> String {
+    request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|header| header.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Runs the request future with its id visible to the logger
+pub async fn in_request_context<F: Future>(request_id: String, future: F) -> F::Output {
+    let context = RequestContext {
+        request_id,
+        user_id: Cell::new(None),
+    };
+    REQUEST_CONTEXT.scope(context, future).await
+}
+
+pub fn set_request_id_header<B>(response: &mut ServiceResponse<B>, request_id: &str) {
+    if let Ok(value) = HeaderValue::from_str(request_id) {
+        response
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER.clone(), value);
+    }
+}
+
+/// Called once the token is verified, later log lines of the request get the user
+pub fn set_current_user_id(user_id: i64) {
+    let _ = REQUEST_CONTEXT.try_with(|context| context.user_id.set(Some(user_id)));
+}
+
+/// None outside of a request, e.g. in background jobs
+pub fn current_request_id() -> Option<String> {
+    REQUEST_CONTEXT
+        .try_with(|context| context.request_id.clone())
+        .ok()
+}
+
+pub fn current_user_id() -> Option<i64> {
+    REQUEST_CONTEXT
+        .try_with(|context| context.user_id.get())
+        .ok()
+        .flatten()
+}
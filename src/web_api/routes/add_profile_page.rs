@@ -3,11 +3,11 @@ use core::fmt;
 use actix_web::web;
 use actix_web::{http::header::LOCATION, HttpResponse, Responder};
 use futures::future::OptionFuture;
-use log::{error, info};
+use log::info;
 use serde::Deserialize;
 use uuid::Uuid;
 
-use crate::web_api::routes::common::HeadContext;
+use crate::web_api::routes::common::{deserialize_number_as_string, get_photo_url, HeadContext};
 use crate::web_api::routes::error::HtmlError;
 use crate::web_api::routes::validator::ErrorContext;
 use crate::{
@@ -15,7 +15,8 @@ use crate::{
     db::{DbProvider, ProfileModel},
     web_api::{
         auth::AuthenticationGate,
-        recaptcha::Recaptcha,
+        captcha::Captcha,
+        photo::{PhotoRendition, PhotoStorage},
         routes::{
             common::{NavContext, ProfilePageDataContext},
             constant::{MSG_PROFILE_ADDED_CODE, MSG_PROFILE_UPDATED_CODE},
@@ -25,12 +26,15 @@ use crate::{
 };
 use rust_i18n::t;
 
+use super::csrf_gate::{CsrfForm, CsrfToken, CsrfTokenField};
 use super::validator::Validator;
 
 pub async fn add_profile_page(
     db_provider: web::Data<DbProvider>,
     auth_gate: AuthenticationGate,
+    csrf_token: CsrfToken,
     config: web::Data<Config>,
+    photo_storage: web::Data<dyn PhotoStorage>,
 ) -> Result<impl Responder, HtmlError> {
     if !auth_gate.is_authorized {
         return Err(HtmlError::NotAuthorized);
@@ -48,7 +52,7 @@ pub async fn add_profile_page(
     let draft_profile_photos = OptionFuture::from(
         draft_profile_opt
             .as_ref()
-            .map(|profile| db_provider.find_all_profile_photos_for(&profile.id)),
+            .map(|profile| db_provider.find_all_uploaded_profile_photos_for(&profile.id)),
     )
     .await
     .unwrap_or(Ok(vec![]))?;
@@ -56,23 +60,24 @@ pub async fn add_profile_page(
     let cities_names = db_provider.find_city_names().await?;
 
     let data_contex = ProfilePageDataContext::new(
-        &config.all_photos_folder_name,
+        photo_storage.as_ref(),
         &draft_profile_opt,
         &draft_profile_photos,
         false,
+        config.max_profile_photos,
     );
 
-    let user_name = auth_gate.user_name.unwrap();
+    let user_name = auth_gate.user_name.clone().unwrap();
     let nav_context = NavContext::new(
         &user_name,
         "",
-        &config.captcha_google_id,
         false,
         &Option::None,
         &cities_names,
-        &config.oauth_google_client_id,
-        &config.oauth_google_redirect_url,
-    );
+        &config,
+    )
+    .with_role(&auth_gate)
+    .with_csrf(&csrf_token);
     let error_context = ErrorContext::empty();
     let head_context = HeadContext::new(
         t!("add_profile_page_title").to_string().as_str(),
@@ -92,8 +97,11 @@ pub async fn add_profile_page(
 pub async fn add_or_edit_profile_post(
     db_provider: web::Data<DbProvider>,
     auth_gate: AuthenticationGate,
-    form_raw: web::Form<AddOrEditProfileFormRequestRaw>,
+    csrf_token: CsrfToken,
+    form_raw: CsrfForm<AddOrEditProfileFormRequestRaw>,
     config: web::Data<Config>,
+    captcha: web::Data<Captcha>,
+    photo_storage: web::Data<dyn PhotoStorage>,
 ) -> Result<impl Responder, HtmlError> {
     async fn resolve_profile(
         user_id: i64,
@@ -127,7 +135,7 @@ pub async fn add_or_edit_profile_post(
 
     fn update_profile_with_raw_data(
         profile: &mut ProfileModel,
-        form_raw: &web::Form<AddOrEditProfileFormRequestRaw>,
+        form_raw: &CsrfForm<AddOrEditProfileFormRequestRaw>,
     ) {
         profile.name = form_raw.name.clone();
         if let Ok(height) = form_raw.height.parse::<i16>() {
@@ -165,31 +173,33 @@ pub async fn add_or_edit_profile_post(
             &error_context
         );
         let user_id = auth_gate.user_id.unwrap();
-        let user_name = auth_gate.user_name.unwrap();
-        let google_captcha_id = &config.captcha_google_id.as_str();
+        let user_name = auth_gate.user_name.clone().unwrap();
         let cities = db_provider.find_city_names().await?;
         let nav_context = NavContext::new(
             &user_name,
             "",
-            google_captcha_id,
             false,
             &Option::None,
             &cities,
-            &config.oauth_google_client_id,
-            &config.oauth_google_redirect_url,
-        );
+            &config,
+        )
+        .with_role(&auth_gate)
+        .with_csrf(&csrf_token);
 
         let mut profile = resolve_profile(user_id, &form_raw.profile_id, &db_provider).await?;
         update_profile_with_raw_data(&mut profile, &form_raw);
 
-        let profile_photos = db_provider.find_all_profile_photos_for(&profile.id).await?;
+        let profile_photos = db_provider
+            .find_all_uploaded_profile_photos_for(&profile.id)
+            .await?;
         let is_edit = form_raw.profile_id.is_some();
 
         let data_context = ProfilePageDataContext::new(
-            &config.all_photos_folder_name,
+            photo_storage.as_ref(),
             &Some(profile),
             &profile_photos,
             is_edit,
+            config.max_profile_photos,
         );
 
         let title = if data_context.is_edit_mode {
@@ -206,7 +216,9 @@ pub async fn add_or_edit_profile_post(
             title.as_str(),
             description.as_str(),
             &config,
-            &profile_photos.first().cloned(),
+            &profile_photos
+                .first()
+                .map(|photo| get_photo_url(photo, PhotoRendition::Full, photo_storage.as_ref())),
         );
 
         return Ok(HtmlPage::add_or_edit_profile(
@@ -216,16 +228,10 @@ pub async fn add_or_edit_profile_post(
             &error_context,
         ));
     } else {
-        let form = form_validation.unwrap();
-        info!("Form passes validation: [{:?}]", &form);
-        form
+        form_validation.unwrap()
     };
 
-    let captcha_score =
-        Recaptcha::verify(&config.captcha_google_secret, &form.captcha_token).await?;
-
-    if captcha_score < config.captcha_google_score {
-        error!("Google captcha score is low [{}]", captcha_score);
+    if !captcha.is_human(&form.captcha_token).await? {
         return Err(HtmlError::BotDetection);
     }
 
@@ -266,7 +272,9 @@ fn redirect_response_to(path: &str) -> HttpResponse {
 #[derive(Deserialize)]
 pub struct AddOrEditProfileFormRequestRaw {
     pub name: String,
+    #[serde(deserialize_with = "deserialize_number_as_string")]
     pub height: String,
+    #[serde(deserialize_with = "deserialize_number_as_string")]
     pub weight: String,
     pub city: String,
     pub phone_number: String,
@@ -274,6 +282,14 @@ pub struct AddOrEditProfileFormRequestRaw {
     // edit mode ON
     pub profile_id: Option<Uuid>,
     pub captcha_token: String,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for AddOrEditProfileFormRequestRaw {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
 }
 
 pub struct AddOrEditProfileFormRequest {
@@ -295,7 +311,7 @@ impl fmt::Debug for AddOrEditProfileFormRequest {
         debug_struct.field("height", &self.height);
         debug_struct.field("weight", &self.weight);
         debug_struct.field("city", &self.city);
-        debug_struct.field("phone_number", &self.phone_number);
+        debug_struct.field("phone_number", &"[redacted]");
         debug_struct.field("description", &self.description);
         if let Some(profile_id) = self.profile_id {
             debug_struct.field("profile_id", &profile_id);
@@ -0,0 +1,47 @@
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use futures::future::LocalBoxFuture;
+use log::info;
+
+use crate::{
+    db::{DbProvider, UserModel},
+    web_api::auth::{is_admin, AuthenticationGate},
+};
+
+use super::error::HtmlError;
+
+/// Passes only users with admin role. Role is checked in db, not in jwt,
+/// so role downgrade works without waiting for token expiration
+pub struct AdminGate {
+    pub auth_gate: AuthenticationGate,
+    pub user: UserModel,
+}
+
+impl FromRequest for AdminGate {
+    type Error = HtmlError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let auth_gate_fut = AuthenticationGate::from_request(req, payload);
+        let db_provider = req.app_data::<web::Data<DbProvider>>().unwrap().clone();
+
+        Box::pin(async move {
+            let auth_gate = auth_gate_fut.await.map_err(|_| HtmlError::NotAuthorized)?;
+            if !auth_gate.is_authorized {
+                return Err(HtmlError::NotAuthorized);
+            }
+
+            let user_id = auth_gate.user_id.unwrap();
+            let user = db_provider
+                .find_user_by_id(user_id)
+                .await?
+                .ok_or(HtmlError::NotAuthorized)?;
+
+            if !is_admin(&user.role) {
+                info!("User [{}] is not an admin", user_id);
+                return Err(HtmlError::NotAuthorized);
+            }
+
+            Ok(AdminGate { auth_gate, user })
+        })
+    }
+}
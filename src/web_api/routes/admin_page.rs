@@ -0,0 +1,496 @@
+use actix_web::{http::header::LOCATION, web, HttpResponse, Responder};
+use log::info;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    db::{CityModel, DbProvider, ProfileModel, UserModel},
+    web_api::{
+        auth::ALL_ROLES,
+        photo::{PhotoRendition, PhotoService, PhotoStorage},
+        routes::{
+            common::{get_photo_url, HeadContext, NavContext},
+            constant::{
+                HOME_DATE_FORMAT, MSG_ADMIN_CITY_UPDATED_CODE,
+                MSG_ADMIN_PHOTO_DELETED_CODE, MSG_ADMIN_PROFILE_DELETED_CODE,
+                MSG_ADMIN_USER_UPDATED_CODE,
+            },
+            home_page::Pagination,
+            html_render::HtmlPage,
+        },
+    },
+};
+use rust_i18n::t;
+
+use super::{
+    admin_gate::AdminGate,
+    csrf_gate::{CsrfForm, CsrfToken, CsrfTokenField},
+    error::HtmlError,
+};
+
+async fn resolve_nav_context(
+    admin_gate: &AdminGate,
+    db_provider: &web::Data<DbProvider>,
+    config: &web::Data<Config>,
+) -> Result<NavContext, HtmlError> {
+    let cities_names = db_provider.find_city_names().await?;
+    Ok(NavContext::new(
+        &admin_gate.user.name,
+        "",
+        false,
+        &Option::None,
+        &cities_names,
+        config,
+    )
+    .with_role(&admin_gate.auth_gate))
+}
+
+fn resolve_head_context(config: &web::Data<Config>) -> HeadContext {
+    HeadContext::new(
+        t!("admin_page_title").to_string().as_str(),
+        t!("admin_page_description").to_string().as_str(),
+        config,
+        &Option::None,
+    )
+}
+
+fn pagination(page_opt: &Option<u64>, total_pages: u64) -> Pagination {
+    let current_page = page_opt.unwrap_or(1);
+    Pagination {
+        has_next: current_page < total_pages,
+        has_previous: current_page > 1,
+        current: current_page,
+        total: total_pages,
+    }
+}
+
+pub async fn admin_users_page(
+    admin_gate: AdminGate,
+    csrf_token: CsrfToken,
+    db_provider: web::Data<DbProvider>,
+    query: web::Query<AdminListQuery>,
+    config: web::Data<Config>,
+) -> Result<impl Responder, HtmlError> {
+    info!("Admin [{}] opens users page", admin_gate.user.id);
+
+    let search = query.search_opt();
+    let (total_pages, db_users) = db_provider
+        .users_pagination(config.admin_rows_on_page, &query.page, &search)
+        .await?;
+
+    let data_context = AdminUsersPageDataContext {
+        users: db_users
+            .iter()
+            .map(|user| AdminUserContext::new(user, admin_gate.user.id))
+            .collect(),
+        all_roles: ALL_ROLES.iter().map(|role| role.to_string()).collect(),
+        pagination: pagination(&query.page, total_pages),
+        search,
+        message_code: query.message.clone(),
+    };
+
+    let nav_context = resolve_nav_context(&admin_gate, &db_provider, &config)
+        .await?
+        .with_csrf(&csrf_token);
+    let head_context = resolve_head_context(&config);
+
+    Ok(HtmlPage::admin_users(
+        &head_context,
+        &nav_context,
+        &data_context,
+    ))
+}
+
+pub async fn admin_user_role_endpoint(
+    admin_gate: AdminGate,
+    db_provider: web::Data<DbProvider>,
+    form: CsrfForm<AdminUserRoleRequest>,
+) -> Result<impl Responder, HtmlError> {
+    if !ALL_ROLES.contains(&form.role.as_str()) || form.id == admin_gate.user.id {
+        info!(
+            "Admin [{}] sent bad role update. User: [{}], role: [{}]",
+            admin_gate.user.id, form.id, &form.role
+        );
+        return Err(HtmlError::BadParams);
+    }
+
+    let user = db_provider
+        .find_user_by_id(form.id)
+        .await?
+        .ok_or(HtmlError::NotFound)?;
+    db_provider.update_user_role(&user, &form.role).await?;
+
+    info!(
+        "Admin [{}] changed role of user [{}] from [{}] to [{}]",
+        admin_gate.user.id, user.id, &user.role, &form.role
+    );
+
+    Ok(redirect_to(&format!(
+        "/admin/users?message={}",
+        MSG_ADMIN_USER_UPDATED_CODE
+    )))
+}
+
+pub async fn admin_profiles_page(
+    admin_gate: AdminGate,
+    csrf_token: CsrfToken,
+    db_provider: web::Data<DbProvider>,
+    query: web::Query<AdminListQuery>,
+    config: web::Data<Config>,
+) -> Result<impl Responder, HtmlError> {
+    info!("Admin [{}] opens profiles page", admin_gate.user.id);
+
+    let search = query.search_opt();
+    let (total_pages, db_profiles) = db_provider
+        .not_deleted_profiles_pagination(config.admin_rows_on_page, &query.page, &search)
+        .await?;
+
+    let data_context = AdminProfilesPageDataContext {
+        profiles: db_profiles.iter().map(AdminProfileContext::new).collect(),
+        pagination: pagination(&query.page, total_pages),
+        search,
+        message_code: query.message.clone(),
+    };
+
+    let nav_context = resolve_nav_context(&admin_gate, &db_provider, &config)
+        .await?
+        .with_csrf(&csrf_token);
+    let head_context = resolve_head_context(&config);
+
+    Ok(HtmlPage::admin_profiles(
+        &head_context,
+        &nav_context,
+        &data_context,
+    ))
+}
+
+pub async fn admin_profile_page(
+    admin_gate: AdminGate,
+    csrf_token: CsrfToken,
+    db_provider: web::Data<DbProvider>,
+    query: web::Query<AdminProfileQuery>,
+    config: web::Data<Config>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+) -> Result<impl Responder, HtmlError> {
+    info!(
+        "Admin [{}] opens profile [{}]",
+        admin_gate.user.id, &query.id
+    );
+
+    let profile = db_provider
+        .find_not_deleted_profile_by(&query.id)
+        .await?
+        .ok_or(HtmlError::NotFound)?;
+    let profile_photos = db_provider.find_all_profile_photos_for(&profile.id).await?;
+
+    let data_context = AdminProfilePageDataContext {
+        profile: AdminProfileContext::new(&profile),
+        description: profile.description.clone(),
+        photos: profile_photos
+            .iter()
+            .map(|photo| AdminProfilePhotoContext {
+                id: photo.id,
+                url: get_photo_url(photo, PhotoRendition::Thumbnail, photo_storage.as_ref()),
+                size: photo.size,
+            })
+            .collect(),
+        message_code: query.message.clone(),
+    };
+
+    let nav_context = resolve_nav_context(&admin_gate, &db_provider, &config)
+        .await?
+        .with_csrf(&csrf_token);
+    let head_context = resolve_head_context(&config);
+
+    Ok(HtmlPage::admin_profile(
+        &head_context,
+        &nav_context,
+        &data_context,
+    ))
+}
+
+pub async fn admin_profile_delete_endpoint(
+    admin_gate: AdminGate,
+    db_provider: web::Data<DbProvider>,
+    form: CsrfForm<AdminProfileDeleteRequest>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+) -> Result<impl Responder, HtmlError> {
+    let profile = db_provider
+        .find_not_deleted_profile_by(&form.id)
+        .await?
+        .ok_or(HtmlError::NotFound)?;
+    let profile_photos = db_provider
+        .find_all_uploaded_profile_photos_for(&profile.id)
+        .await?;
+
+    info!(
+        "Admin [{}] force deletes profile [{}] with [{}] photos",
+        admin_gate.user.id,
+        &profile.id,
+        profile_photos.len()
+    );
+
+    db_provider
+        .delete_profile_and_photos(&profile, &profile_photos)
+        .await?;
+    PhotoService::delete_profile(photo_storage.as_ref(), &profile.id, &profile_photos).await?;
+
+    Ok(redirect_to(&format!(
+        "/admin/profiles?message={}",
+        MSG_ADMIN_PROFILE_DELETED_CODE
+    )))
+}
+
+pub async fn admin_profile_photo_delete_endpoint(
+    admin_gate: AdminGate,
+    db_provider: web::Data<DbProvider>,
+    form: CsrfForm<AdminProfilePhotoDeleteRequest>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+) -> Result<impl Responder, HtmlError> {
+    let profile_photo = db_provider
+        .find_active_profile_photo_by_id(form.id)
+        .await?
+        .ok_or(HtmlError::NotFound)?;
+
+    info!(
+        "Admin [{}] force deletes photo [{}] of profile [{}]",
+        admin_gate.user.id, profile_photo.id, &profile_photo.profile_id
+    );
+
+    db_provider
+        .update_profile_photo_with_delete_status(&profile_photo)
+        .await?;
+    PhotoService::delete_photo(photo_storage.as_ref(), &profile_photo).await?;
+
+    Ok(redirect_to(&format!(
+        "/admin/profile?id={}&message={}",
+        &profile_photo.profile_id, MSG_ADMIN_PHOTO_DELETED_CODE
+    )))
+}
+
+pub async fn admin_cities_page(
+    admin_gate: AdminGate,
+    csrf_token: CsrfToken,
+    db_provider: web::Data<DbProvider>,
+    query: web::Query<AdminListQuery>,
+    config: web::Data<Config>,
+) -> Result<impl Responder, HtmlError> {
+    info!("Admin [{}] opens cities page", admin_gate.user.id);
+
+    let db_cities = db_provider.find_all_cities().await?;
+    let data_context = AdminCitiesPageDataContext {
+        cities: db_cities.iter().map(AdminCityContext::new).collect(),
+        message_code: query.message.clone(),
+    };
+
+    let nav_context = resolve_nav_context(&admin_gate, &db_provider, &config)
+        .await?
+        .with_csrf(&csrf_token);
+    let head_context = resolve_head_context(&config);
+
+    Ok(HtmlPage::admin_cities(
+        &head_context,
+        &nav_context,
+        &data_context,
+    ))
+}
+
+pub async fn admin_city_toggle_endpoint(
+    admin_gate: AdminGate,
+    db_provider: web::Data<DbProvider>,
+    form: CsrfForm<AdminCityToggleRequest>,
+) -> Result<impl Responder, HtmlError> {
+    let city = db_provider
+        .find_city_by_id(form.id)
+        .await?
+        .ok_or(HtmlError::NotFound)?;
+    let new_status = if city.status == "on" { "off" } else { "on" };
+
+    db_provider.update_city_status(&city, new_status).await?;
+    info!(
+        "Admin [{}] switched city [{}] to [{}]",
+        admin_gate.user.id, &city.name, new_status
+    );
+
+    Ok(redirect_to(&format!(
+        "/admin/cities?message={}",
+        MSG_ADMIN_CITY_UPDATED_CODE
+    )))
+}
+
+fn redirect_to(path: &str) -> HttpResponse {
+    HttpResponse::Found()
+        .append_header((LOCATION, path))
+        .finish()
+}
+
+pub struct AdminUsersPageDataContext {
+    pub users: Vec<AdminUserContext>,
+    pub all_roles: Vec<String>,
+    pub pagination: Pagination,
+    pub search: Option<String>,
+    pub message_code: Option<String>,
+}
+
+pub struct AdminUserContext {
+    pub id: i64,
+    pub name: String,
+    pub email: String,
+    pub role: String,
+    pub date_create: String,
+    pub is_current_admin: bool,
+}
+
+impl AdminUserContext {
+    fn new(user: &UserModel, current_admin_id: i64) -> Self {
+        AdminUserContext {
+            id: user.id,
+            name: user.name.clone(),
+            email: user.email.clone(),
+            role: user.role.clone(),
+            date_create: user.created_at.format(HOME_DATE_FORMAT).to_string(),
+            is_current_admin: user.id == current_admin_id,
+        }
+    }
+}
+
+pub struct AdminProfilesPageDataContext {
+    pub profiles: Vec<AdminProfileContext>,
+    pub pagination: Pagination,
+    pub search: Option<String>,
+    pub message_code: Option<String>,
+}
+
+pub struct AdminProfileContext {
+    pub id: Uuid,
+    pub user_id: i64,
+    pub name: String,
+    pub phone_number: String,
+    pub city: String,
+    pub status: String,
+    pub date_update: String,
+}
+
+impl AdminProfileContext {
+    fn new(profile: &ProfileModel) -> Self {
+        AdminProfileContext {
+            id: profile.id,
+            user_id: profile.user_id,
+            name: profile.name.clone(),
+            phone_number: profile.phone_number.clone(),
+            city: profile.city.clone(),
+            status: profile.status.clone(),
+            date_update: profile.updated_at.format(HOME_DATE_FORMAT).to_string(),
+        }
+    }
+}
+
+pub struct AdminProfilePageDataContext {
+    pub profile: AdminProfileContext,
+    pub description: String,
+    pub photos: Vec<AdminProfilePhotoContext>,
+    pub message_code: Option<String>,
+}
+
+pub struct AdminProfilePhotoContext {
+    pub id: i64,
+    pub url: String,
+    pub size: i64,
+}
+
+pub struct AdminCitiesPageDataContext {
+    pub cities: Vec<AdminCityContext>,
+    pub message_code: Option<String>,
+}
+
+pub struct AdminCityContext {
+    pub id: i64,
+    pub name: String,
+    pub is_on: bool,
+}
+
+impl AdminCityContext {
+    fn new(city: &CityModel) -> Self {
+        AdminCityContext {
+            id: city.id,
+            name: city.name.clone(),
+            is_on: city.status == "on",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AdminListQuery {
+    pub message: Option<String>,
+    pub search: Option<String>,
+    pub page: Option<u64>,
+}
+
+impl AdminListQuery {
+    fn search_opt(&self) -> Option<String> {
+        self.search
+            .as_ref()
+            .map(|search| search.trim().to_owned())
+            .filter(|search| !search.is_empty())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AdminProfileQuery {
+    pub id: Uuid,
+    pub message: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AdminUserRoleRequest {
+    pub id: i64,
+    pub role: String,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for AdminUserRoleRequest {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AdminProfileDeleteRequest {
+    pub id: Uuid,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for AdminProfileDeleteRequest {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AdminProfilePhotoDeleteRequest {
+    pub id: i64,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for AdminProfilePhotoDeleteRequest {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AdminCityToggleRequest {
+    pub id: i64,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for AdminCityToggleRequest {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+}
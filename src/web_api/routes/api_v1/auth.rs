@@ -0,0 +1,218 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    db::{DbProvider, UserModel},
+    web_api::{
+        auth::AuthSessionManager,
+        routes::{
+            authorization_endpoint::{resolve_google_user, start_session},
+            error::JsonError,
+        },
+    },
+};
+
+pub async fn google_sign_in(
+    db_provider: web::Data<DbProvider>,
+    body: web::Json<GoogleSignInJsonRequest>,
+    config: web::Data<Config>,
+    request: HttpRequest,
+) -> Result<impl Responder, JsonError> {
+    let oauth_google = config.oauth_google.as_ref().ok_or(JsonError::NotFound)?;
+    if body.id_token.is_empty() {
+        return Err(JsonError::BadParams);
+    }
+
+    let user = resolve_google_user::<JsonError>(
+        &body.id_token,
+        &oauth_google.client_id,
+        &config,
+        &db_provider,
+    )
+    .await?;
+    if user.status == "disabled" {
+        info!("Api. User [{}] is disabled. Sign in is refused", user.id);
+        return Err(JsonError::NotAuthorized);
+    }
+    info!("Api. User [{}] signed in with google", user.id);
+
+    let session_manager = AuthSessionManager::new(&config);
+    let session = start_session(
+        &request,
+        user.id,
+        session_manager.refresh_token_expires_at(),
+        &db_provider,
+    )
+    .await?;
+
+    let response = issue_tokens(&user, &session.id, Uuid::new_v4(), &db_provider, &config).await?;
+    Ok(web::Json(response))
+}
+
+/// Rotates refresh token and prolongs its session. Reuse of the revoked token
+/// means it was stolen, so every session of the user is revoked
+pub async fn refresh(
+    db_provider: web::Data<DbProvider>,
+    body: web::Json<RefreshTokenJsonRequest>,
+    config: web::Data<Config>,
+) -> Result<impl Responder, JsonError> {
+    let token_hash = AuthSessionManager::hash_refresh_token(&body.refresh_token);
+    let refresh_token = db_provider
+        .find_refresh_token_by_hash(&token_hash)
+        .await?
+        .ok_or(JsonError::NotAuthorized)?;
+
+    if refresh_token.revoked_at.is_some() {
+        let revoked = db_provider
+            .revoke_all_user_sessions(refresh_token.user_id)
+            .await?;
+        info!(
+            "Api. Revoked refresh token [{}] was reused. Revoking [{}] sessions of user [{}]",
+            refresh_token.id, revoked, refresh_token.user_id
+        );
+        return Err(JsonError::NotAuthorized);
+    }
+
+    if refresh_token.expires_at < Utc::now().naive_utc() {
+        info!("Api. Refresh token [{}] is expired", refresh_token.id);
+        return Err(JsonError::NotAuthorized);
+    }
+
+    let session = match refresh_token.session_id {
+        Some(session_id) => {
+            db_provider
+                .find_active_session(&session_id, refresh_token.user_id)
+                .await?
+        }
+        None => None,
+    }
+    .ok_or(JsonError::NotAuthorized)?;
+
+    let user = db_provider
+        .find_user_by_id(refresh_token.user_id)
+        .await?
+        .ok_or(JsonError::NotAuthorized)?;
+
+    // only one of concurrent requests with the same token rotates it, others are reuse
+    let new_refresh_token_id = Uuid::new_v4();
+    let is_rotated = db_provider
+        .revoke_refresh_token(&refresh_token, Some(new_refresh_token_id))
+        .await?;
+    if !is_rotated {
+        let revoked = db_provider
+            .revoke_all_user_sessions(refresh_token.user_id)
+            .await?;
+        info!(
+            "Api. Refresh token [{}] was rotated by another request. Revoking [{}] sessions of user [{}]",
+            refresh_token.id, revoked, refresh_token.user_id
+        );
+        return Err(JsonError::NotAuthorized);
+    }
+
+    let session_manager = AuthSessionManager::new(&config);
+    db_provider
+        .prolong_session(&session, session_manager.refresh_token_expires_at())
+        .await?;
+
+    let response = issue_tokens(
+        &user,
+        &session.id,
+        new_refresh_token_id,
+        &db_provider,
+        &config,
+    )
+    .await?;
+    info!(
+        "Api. Refresh token [{}] was rotated to [{}]",
+        refresh_token.id, new_refresh_token_id
+    );
+
+    Ok(web::Json(response))
+}
+
+pub async fn revoke(
+    db_provider: web::Data<DbProvider>,
+    body: web::Json<RefreshTokenJsonRequest>,
+) -> Result<impl Responder, JsonError> {
+    let token_hash = AuthSessionManager::hash_refresh_token(&body.refresh_token);
+    let refresh_token_opt = db_provider.find_refresh_token_by_hash(&token_hash).await?;
+
+    // unknown token gets the same response, nothing to leak
+    if let Some(refresh_token) = refresh_token_opt.filter(|token| token.revoked_at.is_none()) {
+        let session_opt = match refresh_token.session_id {
+            Some(session_id) => {
+                db_provider
+                    .find_active_session(&session_id, refresh_token.user_id)
+                    .await?
+            }
+            None => None,
+        };
+        // as sign out does, the access tokens of the session stop working too
+        if let Some(session) = session_opt {
+            info!(
+                "Api. Revoking session [{}] of refresh token [{}]",
+                session.id, refresh_token.id
+            );
+            db_provider.revoke_session(&session).await?;
+        } else {
+            info!("Api. Revoking refresh token [{}]", refresh_token.id);
+            db_provider
+                .revoke_refresh_token(&refresh_token, None)
+                .await?;
+        }
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn issue_tokens(
+    user: &UserModel,
+    session_id: &Uuid,
+    refresh_token_id: Uuid,
+    db_provider: &web::Data<DbProvider>,
+    config: &web::Data<Config>,
+) -> Result<TokenJsonResponse, JsonError> {
+    let session_manager = AuthSessionManager::new(config);
+    let access_token =
+        session_manager.get_access_token(user.id, &user.name, &user.email, &user.role, session_id);
+
+    let (refresh_token, token_hash) = AuthSessionManager::generate_refresh_token();
+    db_provider
+        .add_refresh_token(
+            refresh_token_id,
+            user.id,
+            session_id,
+            &token_hash,
+            session_manager.refresh_token_expires_at(),
+        )
+        .await?;
+
+    Ok(TokenJsonResponse {
+        access_token,
+        token_type: "Bearer".to_owned(),
+        expires_in: session_manager.access_token_expires_in(),
+        refresh_token,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct GoogleSignInJsonRequest {
+    pub id_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct RefreshTokenJsonRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Serialize)]
+pub struct TokenJsonResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub refresh_token: String,
+}
@@ -0,0 +1,14 @@
+use actix_web::{web, Responder};
+use serde::Serialize;
+
+use crate::{db::DbProvider, web_api::routes::error::JsonError};
+
+pub async fn all_cities(db_provider: web::Data<DbProvider>) -> Result<impl Responder, JsonError> {
+    let cities = db_provider.find_city_names().await?;
+    Ok(web::Json(CitiesJsonResponse { cities }))
+}
+
+#[derive(Serialize)]
+pub struct CitiesJsonResponse {
+    pub cities: Vec<String>,
+}
@@ -0,0 +1,170 @@
+use actix_web::{web, HttpResponse, Responder};
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    db::DbProvider,
+    web_api::{
+        auth::AuthenticationGate,
+        captcha::Captcha,
+        moderation::CommentModeration,
+        routes::{
+            error::JsonError,
+            validator::Validator,
+            view_profile_page::{
+                AddCommentFormRequest, AddCommentFormRequestRaw, ProfileCommentResponse,
+            },
+        },
+    },
+};
+
+pub async fn all_comments(
+    db_provider: web::Data<DbProvider>,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, JsonError> {
+    let profile_id = path.into_inner();
+    db_provider
+        .find_active_profile_by(&profile_id)
+        .await?
+        .ok_or(JsonError::NotFound)?;
+
+    let db_comments = db_provider.all_profile_comments(&profile_id).await?;
+    Ok(web::Json(CommentsJsonResponse {
+        comments: db_comments
+            .iter()
+            .map(ProfileCommentResponse::from_db_comment_and_user)
+            .collect(),
+    }))
+}
+
+pub async fn add_comment(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    path: web::Path<Uuid>,
+    body: web::Json<AddCommentJsonRequest>,
+    config: web::Data<Config>,
+    captcha: web::Data<Captcha>,
+) -> Result<impl Responder, JsonError> {
+    if !auth_gate.is_authorized {
+        return Err(JsonError::NotAuthorized);
+    }
+
+    let profile_id = path.into_inner();
+    let user_id = auth_gate.user_id.unwrap();
+    db_provider
+        .find_active_profile_by(&profile_id)
+        .await?
+        .ok_or(JsonError::NotFound)?;
+
+    let user_has_left_comment = db_provider
+        .find_comment_by_profile_user_ids(&profile_id, &user_id)
+        .await?;
+    if user_has_left_comment.is_some() {
+        info!(
+            "Api. User [{}] already has comment for profile [{}]",
+            user_id, &profile_id
+        );
+        return Err(JsonError::BadParams);
+    }
+
+    let body = body.into_inner();
+    let request_raw = AddCommentFormRequestRaw {
+        profile_id: profile_id.to_string(),
+        text: body.text,
+        captcha_token: body.captcha_token,
+        // api clients send a bearer token, there is no form to protect
+        csrf_token: String::new(),
+    };
+    let request = checked_comment_request(&request_raw, &captcha).await?;
+
+    let comment_status = CommentModeration::new(&config).initial_status(&request.text);
+    let new_db_comment = db_provider
+        .add_comment(&profile_id, &user_id, &request.text, comment_status)
+        .await?;
+    info!(
+        "Api. New comment [{}] was added to profile [{}] as [{}]",
+        &new_db_comment.id, &new_db_comment.profile_id, &new_db_comment.status
+    );
+
+    let user_name = auth_gate.user_name.unwrap_or_default();
+    Ok(
+        HttpResponse::Created().json(ProfileCommentResponse::from_db_comment(
+            &new_db_comment,
+            &user_name,
+        )),
+    )
+}
+
+/// Valid comment of a human, ready to be stored
+async fn checked_comment_request(
+    request_raw: &AddCommentFormRequestRaw,
+    captcha: &Captcha,
+) -> Result<AddCommentFormRequest, JsonError> {
+    let request = request_raw.validate()?;
+    if !captcha.is_human(&request.captcha_token).await? {
+        return Err(JsonError::BotDetection);
+    }
+    Ok(request)
+}
+
+pub async fn delete_comment(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    path: web::Path<Uuid>,
+) -> Result<impl Responder, JsonError> {
+    if !auth_gate.is_authorized {
+        return Err(JsonError::NotAuthorized);
+    }
+
+    let profile_id = path.into_inner();
+    let comment = db_provider
+        .find_comment_by_profile_user_ids(&profile_id, &auth_gate.user_id.unwrap())
+        .await?
+        .ok_or(JsonError::NotFound)?;
+
+    info!("Api. Deleting comment: [{}]", &comment.id);
+    db_provider.delete_comment(&comment).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize)]
+pub struct AddCommentJsonRequest {
+    pub text: String,
+    pub captcha_token: String,
+}
+
+#[derive(Serialize)]
+pub struct CommentsJsonResponse {
+    pub comments: Vec<ProfileCommentResponse>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment_request_raw() -> AddCommentFormRequestRaw {
+        AddCommentFormRequestRaw {
+            profile_id: Uuid::new_v4().to_string(),
+            text: "Nice profile, say hi".to_owned(),
+            captcha_token: "token".to_owned(),
+            csrf_token: String::new(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn comment_of_a_human_passes_the_captcha() {
+        let request = checked_comment_request(&comment_request_raw(), &Captcha::stub(true))
+            .await
+            .unwrap();
+        assert_eq!(request.text, "Nice profile, say hi");
+    }
+
+    #[actix_rt::test]
+    async fn comment_of_a_bot_is_rejected() {
+        let result = checked_comment_request(&comment_request_raw(), &Captcha::stub(false)).await;
+        assert!(matches!(result, Err(JsonError::BotDetection)));
+    }
+}
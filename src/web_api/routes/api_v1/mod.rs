@@ -0,0 +1,61 @@
+mod auth;
+mod cities;
+mod comments;
+mod profiles;
+
+use actix_web::web;
+use log::info;
+
+use crate::web_api::rate_limit::{
+    RateLimit, RATE_LIMIT_COMMENT, RATE_LIMIT_PROFILE, RATE_LIMIT_SIGN_IN,
+};
+
+use super::error::JsonError;
+
+/// Json mirror of the html routes. Mounted under `/api/v1`
+pub fn api_v1_routes(cfg: &mut web::ServiceConfig) {
+    // malformed body, path or query gets the same error payload as other api errors
+    cfg.app_data(web::JsonConfig::default().error_handler(|err, _| {
+        info!("Api. Bad json payload: [{}]", err);
+        JsonError::BadParams.into()
+    }))
+    .app_data(web::PathConfig::default().error_handler(|err, _| {
+        info!("Api. Bad path: [{}]", err);
+        JsonError::NotFound.into()
+    }))
+    .app_data(web::QueryConfig::default().error_handler(|err, _| {
+        info!("Api. Bad query: [{}]", err);
+        JsonError::BadParams.into()
+    }))
+    .service(
+        web::resource("/auth/google")
+            .wrap(RateLimit::json(RATE_LIMIT_SIGN_IN))
+            .route(web::post().to(auth::google_sign_in)),
+    )
+    .route("/auth/refresh", web::post().to(auth::refresh))
+    .route("/auth/revoke", web::post().to(auth::revoke))
+    .route("/cities", web::get().to(cities::all_cities))
+    .service(
+        web::resource("/profiles")
+            .wrap(RateLimit::json(RATE_LIMIT_PROFILE))
+            .route(web::get().to(profiles::all_profiles))
+            .route(web::post().to(profiles::add_profile)),
+    )
+    .service(
+        web::resource("/profiles/{id}")
+            .wrap(RateLimit::json(RATE_LIMIT_PROFILE))
+            .route(web::get().to(profiles::view_profile))
+            .route(web::put().to(profiles::edit_profile))
+            .route(web::delete().to(profiles::delete_profile)),
+    )
+    .service(
+        web::resource("/profiles/{id}/comments")
+            .wrap(RateLimit::json(RATE_LIMIT_COMMENT))
+            .route(web::get().to(comments::all_comments))
+            .route(web::post().to(comments::add_comment)),
+    )
+    .service(
+        web::resource("/profiles/{id}/comments/mine")
+            .route(web::delete().to(comments::delete_comment)),
+    );
+}
@@ -0,0 +1,326 @@
+use actix_web::{web, HttpResponse, Responder};
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    db::{DbProvider, ProfileModel},
+    web_api::{
+        auth::AuthenticationGate,
+        captcha::Captcha,
+        photo::{PhotoRendition, PhotoService, PhotoStorage},
+        routes::{
+            add_profile_page::{AddOrEditProfileFormRequest, AddOrEditProfileFormRequestRaw},
+            bot_detector_gate::BotDetector,
+            common::get_photo_url,
+            constant::{HOME_DATE_FORMAT, SEARCH_RESULTS_ON_PAGE},
+            error::JsonError,
+            home_page::{HomePageProfileDataContext, Pagination},
+            validator::Validator,
+            view_profile_page::ProfileCommentResponse,
+        },
+    },
+};
+
+pub async fn all_profiles(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    query: web::Query<ProfilesQuery>,
+    config: web::Data<Config>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+) -> Result<impl Responder, JsonError> {
+    let is_user_profiles = query.show_my.unwrap_or_default();
+    if is_user_profiles && !auth_gate.is_authorized {
+        return Err(JsonError::NotAuthorized);
+    }
+
+    let (total_pages, profiles) = if let Some(search) = &query.search {
+        let profiles = db_provider
+            .search_profiles(search, *SEARCH_RESULTS_ON_PAGE)
+            .await?;
+        (0, profiles)
+    } else if is_user_profiles {
+        let profiles = db_provider
+            .all_user_profiles(auth_gate.user_id.unwrap())
+            .await?;
+        (0, profiles)
+    } else {
+        db_provider
+            .profiles_pagination(config.profiles_on_page, &query.page, &query.filter_city)
+            .await?
+    };
+    info!(
+        "Api profiles. Search: [{}], user profiles: [{}], found: [{}]",
+        query.search.is_some(),
+        is_user_profiles,
+        profiles.len()
+    );
+
+    let profile_ids = profiles.iter().map(|profile| profile.id).collect();
+    let profile_id_and_profile_photo_map = db_provider
+        .find_first_profile_photos_for(&profile_ids)
+        .await?;
+
+    let current_page = query.page.unwrap_or(1);
+    Ok(web::Json(ProfilesJsonResponse {
+        profiles: profiles
+            .iter()
+            .map(|profile| {
+                let profile_photo_opt = profile_id_and_profile_photo_map
+                    .get(&profile.id)
+                    .cloned()
+                    .flatten();
+                HomePageProfileDataContext::new(profile, &profile_photo_opt, photo_storage.as_ref())
+            })
+            .collect(),
+        pagination: Pagination {
+            has_next: current_page < total_pages,
+            has_previous: current_page > 1,
+            current: current_page,
+            total: total_pages,
+        },
+    }))
+}
+
+pub async fn view_profile(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    bot_detector: BotDetector,
+    path: web::Path<Uuid>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+) -> Result<impl Responder, JsonError> {
+    let profile_id = path.into_inner();
+    let profile = db_provider
+        .find_active_profile_by(&profile_id)
+        .await?
+        .ok_or(JsonError::NotFound)?;
+
+    let is_author = auth_gate.user_id == Some(profile.user_id);
+    if is_author || bot_detector.is_bot {
+        info!(
+            "Is user profile owner [{}] or bot [{}]. Do not increase view counter",
+            is_author, bot_detector.is_bot
+        )
+    } else {
+        db_provider
+            .increase_view_for_profiles(&vec![profile.id])
+            .await?;
+    }
+
+    let response =
+        resolve_profile_json(&profile, is_author, &db_provider, photo_storage.as_ref()).await?;
+    Ok(web::Json(response))
+}
+
+pub async fn add_profile(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    body: web::Json<AddOrEditProfileFormRequestRaw>,
+    captcha: web::Data<Captcha>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+) -> Result<impl Responder, JsonError> {
+    let mut request_raw = body.into_inner();
+    // new profile is always built from the draft
+    request_raw.profile_id = None;
+
+    let profile = publish_profile(&request_raw, &auth_gate, &db_provider, &captcha).await?;
+    let response =
+        resolve_profile_json(&profile, true, &db_provider, photo_storage.as_ref()).await?;
+    Ok(HttpResponse::Created().json(response))
+}
+
+pub async fn edit_profile(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    path: web::Path<Uuid>,
+    body: web::Json<AddOrEditProfileFormRequestRaw>,
+    captcha: web::Data<Captcha>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+) -> Result<impl Responder, JsonError> {
+    let mut request_raw = body.into_inner();
+    request_raw.profile_id = Some(path.into_inner());
+
+    let profile = publish_profile(&request_raw, &auth_gate, &db_provider, &captcha).await?;
+    let response =
+        resolve_profile_json(&profile, true, &db_provider, photo_storage.as_ref()).await?;
+    Ok(web::Json(response))
+}
+
+pub async fn delete_profile(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    path: web::Path<Uuid>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+) -> Result<impl Responder, JsonError> {
+    if !auth_gate.is_authorized {
+        return Err(JsonError::NotAuthorized);
+    }
+
+    let profile_id = path.into_inner();
+    let profile = db_provider
+        .find_active_profile_by_id_and_user_id(&profile_id, auth_gate.user_id.unwrap())
+        .await?
+        .ok_or(JsonError::NotFound)?;
+    let profile_photos = db_provider
+        .find_all_uploaded_profile_photos_for(&profile.id)
+        .await?;
+
+    info!("Api. Deleting profile: [{}]. Starting IO", &profile_id);
+
+    db_provider
+        .delete_profile_and_photos(&profile, &profile_photos)
+        .await?;
+    PhotoService::delete_profile(photo_storage.as_ref(), &profile_id, &profile_photos).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn publish_profile(
+    request_raw: &AddOrEditProfileFormRequestRaw,
+    auth_gate: &AuthenticationGate,
+    db_provider: &web::Data<DbProvider>,
+    captcha: &web::Data<Captcha>,
+) -> Result<ProfileModel, JsonError> {
+    if !auth_gate.is_authorized {
+        return Err(JsonError::NotAuthorized);
+    }
+    let user_id = auth_gate.user_id.unwrap();
+
+    let request = checked_profile_request(request_raw, captcha).await?;
+
+    let profile_model = match request.profile_id {
+        Some(profile_id) => db_provider
+            .find_active_profile_by_id_and_user_id(&profile_id, user_id)
+            .await?
+            .ok_or(JsonError::NotFound)?,
+        None => match db_provider.find_draft_profile_for(user_id).await? {
+            Some(draft_profile) => draft_profile,
+            None => db_provider.add_draft_profile_for(user_id).await?,
+        },
+    };
+
+    let profile = db_provider
+        .publish_profie(
+            &profile_model,
+            &request.name,
+            request.height,
+            request.weight,
+            &request.city,
+            &request.description,
+            &request.phone_number,
+        )
+        .await?;
+    info!(
+        "Api. Profile [{}] was published. Edit mode: {}",
+        profile.id,
+        request.profile_id.is_some()
+    );
+    Ok(profile)
+}
+
+/// Valid profile of a human, ready to be published
+async fn checked_profile_request(
+    request_raw: &AddOrEditProfileFormRequestRaw,
+    captcha: &Captcha,
+) -> Result<AddOrEditProfileFormRequest, JsonError> {
+    let request = request_raw.validate()?;
+    if !captcha.is_human(&request.captcha_token).await? {
+        return Err(JsonError::BotDetection);
+    }
+    Ok(request)
+}
+
+async fn resolve_profile_json(
+    profile: &ProfileModel,
+    is_author: bool,
+    db_provider: &web::Data<DbProvider>,
+    photo_storage: &dyn PhotoStorage,
+) -> Result<ProfileJsonResponse, JsonError> {
+    let profile_photos = db_provider.find_all_profile_photos_for(&profile.id).await?;
+    let db_comments = db_provider.all_profile_comments(&profile.id).await?;
+
+    Ok(ProfileJsonResponse {
+        id: profile.id,
+        name: profile.name.clone(),
+        phone_number: profile.phone_number.clone(),
+        height: profile.height,
+        weight: profile.weight,
+        city: profile.city.clone(),
+        description: profile.description.clone(),
+        photo_urls: profile_photos
+            .iter()
+            .map(|photo| get_photo_url(photo, PhotoRendition::Full, photo_storage))
+            .collect(),
+        date_create: profile.created_at.format(HOME_DATE_FORMAT).to_string(),
+        view_count: profile.view_count,
+        is_author,
+        comments: db_comments
+            .iter()
+            .map(ProfileCommentResponse::from_db_comment_and_user)
+            .collect(),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ProfilesQuery {
+    pub show_my: Option<bool>,
+    pub search: Option<String>,
+    pub filter_city: Option<String>,
+    pub page: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ProfilesJsonResponse {
+    pub profiles: Vec<HomePageProfileDataContext>,
+    pub pagination: Pagination,
+}
+
+#[derive(Serialize)]
+pub struct ProfileJsonResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub phone_number: String,
+    pub height: i16,
+    pub weight: i16,
+    pub city: String,
+    pub description: String,
+    pub photo_urls: Vec<String>,
+    pub date_create: String,
+    pub view_count: i64,
+    pub is_author: bool,
+    pub comments: Vec<ProfileCommentResponse>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile_request_raw() -> AddOrEditProfileFormRequestRaw {
+        AddOrEditProfileFormRequestRaw {
+            name: "Anna".to_owned(),
+            height: "170".to_owned(),
+            weight: "55".to_owned(),
+            city: "kyiv".to_owned(),
+            phone_number: "501234567".to_owned(),
+            description: "Likes long walks".to_owned(),
+            profile_id: None,
+            captcha_token: "token".to_owned(),
+            csrf_token: String::new(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn profile_of_a_human_passes_the_captcha() {
+        let request = checked_profile_request(&profile_request_raw(), &Captcha::stub(true))
+            .await
+            .unwrap();
+        assert_eq!(request.name, "Anna");
+    }
+
+    #[actix_rt::test]
+    async fn profile_of_a_bot_is_rejected() {
+        let result = checked_profile_request(&profile_request_raw(), &Captcha::stub(false)).await;
+        assert!(matches!(result, Err(JsonError::BotDetection)));
+    }
+}
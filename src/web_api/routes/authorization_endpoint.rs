@@ -1,39 +1,49 @@
 use actix_web::{
     cookie::Cookie,
-    http::{header::LOCATION, StatusCode},
+    http::{
+        header::{LOCATION, USER_AGENT},
+        StatusCode,
+    },
     web, HttpRequest, HttpResponse, Responder,
 };
+use chrono::NaiveDateTime;
+use jsonwebtoken_google::ParserError;
 use log::info;
+use sea_orm::DbErr;
 use serde::Deserialize;
 
 use crate::{
     config::Config,
     db::DbProvider,
-    db::UserModel,
+    db::{SessionModel, UserModel},
     web_api::{
-        auth::{AuthSessionManager, AuthenticationGate},
-        routes::constant::{MSG_SIGN_IN_CODE, MSG_SIGN_OUT_CODE},
+        auth::{AuthSessionManager, AuthenticationGate, ROLE_ADMIN},
+        routes::constant::{MSG_SIGN_IN_CODE, MSG_SIGN_OUT_CODE, USER_AGENT_MAX_LENGTH},
         sign_in::get_google_user,
     },
 };
 
 use super::error::HtmlError;
 
-pub async fn sign_out_endpoint(auth_gate: AuthenticationGate) -> impl Responder {
+pub async fn sign_out_endpoint(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+) -> Result<impl Responder, HtmlError> {
+    // cookie is cleared anyway, it may keep the already revoked session
     let empty_cookie = AuthSessionManager::get_empty_jwt_token();
     if auth_gate.is_authorized {
-        info!(
-            "Auth user {} is loging out. Token exists.",
-            auth_gate.user_id.unwrap()
-        );
-        homepage(Some(empty_cookie), MSG_SIGN_OUT_CODE)
+        let user_id = auth_gate.user_id.unwrap();
+        info!("Auth user {} is loging out. Token exists.", user_id);
+        let session_opt = db_provider
+            .find_active_session(&auth_gate.session_id.unwrap(), user_id)
+            .await?;
+        if let Some(session) = session_opt {
+            db_provider.revoke_session(&session).await?;
+        }
     } else {
-        info!(
-            "Auth user {} is loging out. Session expired",
-            auth_gate.user_id.unwrap()
-        );
-        homepage(None, MSG_SIGN_OUT_CODE)
+        info!("User is loging out. Session expired");
     }
+    Ok(homepage(Some(empty_cookie), MSG_SIGN_OUT_CODE))
 }
 
 pub async fn google_sign_in_endpoint(
@@ -42,34 +52,7 @@ pub async fn google_sign_in_endpoint(
     callback_payload: web::Form<GoogleSignInFormRequest>,
     request: HttpRequest,
 ) -> Result<impl Responder, HtmlError> {
-    async fn fetch_and_save_user(
-        oauth_google_client_id: &str,
-        jwt_credentials: &str,
-        db_provider: &web::Data<DbProvider>,
-    ) -> Result<UserModel, HtmlError> {
-        let oauth_user = get_google_user(jwt_credentials, oauth_google_client_id).await?;
-        let db_user_opt = db_provider.find_user_by_email(&oauth_user.email).await?;
-        match db_user_opt {
-            Some(db_user) => {
-                info!(
-                    "Email [{}] exists. Just reusing",
-                    &db_user.email
-                );
-                Ok(db_user)
-            }
-            None => {
-                info!(
-                    "Email [{}] is new. Creating new user",
-                    &oauth_user.email
-                );
-                db_provider
-                    .add_user(None, &oauth_user.name, &oauth_user.email, Some("Google"))
-                    .await
-                    .map_err(|err| err.into())
-            }
-        }
-    }
-
+    let oauth_google = config.oauth_google.as_ref().ok_or(HtmlError::NotFound)?;
     let cookie_gsrf_token = request
         .cookie("g_csrf_token")
         .map(|f| f.value().to_string())
@@ -81,26 +64,107 @@ pub async fn google_sign_in_endpoint(
         || callback_payload.g_csrf_token.is_empty()
         || !is_gsrf_token_matches
     {
-        info!("Sign in error: credential [{}], g_csrf_token [{}], gsrf_token_matches [{}]",
-        &callback_payload.credential.is_empty(), &callback_payload.g_csrf_token.is_empty(), is_gsrf_token_matches
-    );
+        info!(
+            "Sign in error: credential [{}], g_csrf_token [{}], gsrf_token_matches [{}]",
+            &callback_payload.credential.is_empty(),
+            &callback_payload.g_csrf_token.is_empty(),
+            is_gsrf_token_matches
+        );
         return Err(HtmlError::BadParams);
     }
 
-    let user = fetch_and_save_user(
-        &config.oauth_google_client_id,
+    let user = resolve_google_user::<HtmlError>(
         &callback_payload.credential,
+        &oauth_google.client_id,
+        &config,
         &db_provider,
     )
     .await?;
+    if user.status == "disabled" {
+        info!("User [{}] is disabled. Sign in is refused", user.id);
+        return Err(HtmlError::NotAuthorized);
+    }
 
     let session_manager = AuthSessionManager::new(&config);
+    let session = start_session(
+        &request,
+        user.id,
+        session_manager.session_expires_at(),
+        &db_provider,
+    )
+    .await?;
     let jwt_cookie = session_manager
-        .get_valid_jwt_token(user.id, &user.name, &user.name)
+        .get_valid_jwt_token(user.id, &user.name, &user.email, &user.role, &session.id)
         .await;
     Ok(homepage(Some(jwt_cookie), MSG_SIGN_IN_CODE))
 }
 
+/// Finds or creates user for the google id token. Shared by html sign in and api.
+/// Emails from ADMIN_EMAILS are promoted to admins
+pub async fn resolve_google_user<E: From<ParserError> + From<DbErr>>(
+    jwt_credentials: &str,
+    oauth_google_client_id: &str,
+    config: &Config,
+    db_provider: &DbProvider,
+) -> Result<UserModel, E> {
+    let oauth_user = get_google_user(jwt_credentials, oauth_google_client_id).await?;
+    let db_user_opt = db_provider.find_user_by_email(&oauth_user.email).await?;
+    let mut user = match db_user_opt {
+        Some(db_user) => {
+            info!("User [{}] with this email exists. Just reusing", &db_user.id);
+            db_user
+        }
+        None => {
+            info!("Email is new. Creating new user");
+            db_provider
+                .add_user(None, &oauth_user.name, &oauth_user.email, Some("Google"))
+                .await?
+        }
+    };
+
+    let is_configured_admin = config.admin_emails.contains(&user.email.to_lowercase());
+    if is_configured_admin && user.role != ROLE_ADMIN {
+        info!("User [{}] is configured as admin. Updating role", user.id);
+        user = db_provider.update_user_role(&user, ROLE_ADMIN).await?;
+    }
+
+    Ok(user)
+}
+
+/// New session with the device and ip of the request. Its id goes to the `jti` claim
+pub async fn start_session(
+    request: &HttpRequest,
+    user_id: i64,
+    expires_at: NaiveDateTime,
+    db_provider: &DbProvider,
+) -> Result<SessionModel, DbErr> {
+    let user_agent_opt = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|header| header.to_str().ok())
+        .map(|user_agent| {
+            user_agent
+                .chars()
+                .take(*USER_AGENT_MAX_LENGTH)
+                .collect::<String>()
+        });
+    let ip_address_opt = request
+        .connection_info()
+        .realip_remote_addr()
+        .map(|ip_address| ip_address.to_owned());
+
+    let session = db_provider
+        .add_session(
+            user_id,
+            user_agent_opt.as_deref(),
+            ip_address_opt.as_deref(),
+            expires_at,
+        )
+        .await?;
+    info!("New session [{}] for user [{}]", &session.id, user_id);
+    Ok(session)
+}
+
 #[derive(Deserialize)]
 pub struct GoogleSignInFormRequest {
     pub credential: String,
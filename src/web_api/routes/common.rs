@@ -1,9 +1,15 @@
-use super::constant::NO_PHOTO_URL;
+use super::constant::{NO_PHOTO_URL, PROCESSING_PHOTO_URL};
+use super::csrf_gate::CsrfToken;
 use crate::{
     config::Config,
     db::{ProfileModel, ProfilePhotoModel},
+    web_api::{
+        auth::AuthenticationGate,
+        photo::{photo_key, PhotoRendition, PhotoStorage},
+        security_headers::current_csp_nonce,
+    },
 };
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 
 pub struct HeadContext {
@@ -17,16 +23,19 @@ impl HeadContext {
         title: &str,
         description: &str,
         config: &Config,
-        profile_photo_opt: &Option<ProfilePhotoModel>,
+        photo_url_opt: &Option<String>,
     ) -> Self {
-        let site_photo_url = profile_photo_opt
+        let site_photo_url = photo_url_opt
             .as_ref()
-            .map(|profile_photo| {
-                let relative_path =
-                    get_relative_photo_url(&profile_photo, &config.all_photos_folder_name);
-                get_absolute_url(&config, &relative_path)
+            .map(|photo_url| {
+                // photo storage can give absolute url already
+                if photo_url.starts_with("http") {
+                    photo_url.to_owned()
+                } else {
+                    get_absolute_url(config, photo_url)
+                }
             })
-            .unwrap_or(get_absolute_url(&config, &NO_PHOTO_URL.to_string()));
+            .unwrap_or(get_absolute_url(config, NO_PHOTO_URL));
         HeadContext {
             title: title.to_owned(),
             description: description.to_owned(),
@@ -41,33 +50,70 @@ pub struct NavContext {
     pub current_city: String,
     pub is_user_profiles: bool,
     pub search: Option<String>,
-    pub google_captcha_id: String,
+    /// Widget of the profile and comment forms, `recaptcha`, `hcaptcha` or `turnstile`
+    pub captcha_provider: String,
+    pub captcha_site_key: String,
     pub google_oauth_client_id: String,
     pub google_oauth_sign_in_url: String,
+    pub is_moderator: bool,
+    pub is_admin: bool,
+    /// Hidden `csrf_token` field of every post form and header of ajax calls
+    pub csrf_token: String,
+    /// `nonce` attribute of inline scripts, the CSP blocks them without it
+    pub csp_nonce: String,
 }
 
 impl NavContext {
+    /// Captcha and Google ids are empty when captcha or sign in is not configured
     pub fn new(
         name: &str,
         current_city: &str,
-        google_captcha_id: &str,
         is_user_profiles: bool,
         search: &Option<String>,
         cities: &Vec<String>,
-        google_oauth_client_id: &str,
-        google_oauth_sign_in_url: &str,
+        config: &Config,
     ) -> Self {
+        let oauth_google_opt = config.oauth_google.as_ref();
+        // the stubs have no widget
+        let captcha_opt = config
+            .captcha
+            .as_ref()
+            .filter(|captcha| !captcha.site_key.is_empty());
         NavContext {
             name: name.to_owned(),
             all_cities: cities.to_owned(),
             current_city: current_city.to_owned(),
             is_user_profiles,
             search: search.clone(),
-            google_captcha_id: google_captcha_id.to_owned(),
-            google_oauth_client_id: google_oauth_client_id.to_owned(),
-            google_oauth_sign_in_url: google_oauth_sign_in_url.to_owned(),
+            captcha_provider: captcha_opt
+                .map(|captcha| captcha.provider.clone())
+                .unwrap_or_default(),
+            captcha_site_key: captcha_opt
+                .map(|captcha| captcha.site_key.clone())
+                .unwrap_or_default(),
+            google_oauth_client_id: oauth_google_opt
+                .map(|oauth_google| oauth_google.client_id.clone())
+                .unwrap_or_default(),
+            google_oauth_sign_in_url: oauth_google_opt
+                .map(|oauth_google| oauth_google.redirect_url.clone())
+                .unwrap_or_default(),
+            is_moderator: false,
+            is_admin: false,
+            csrf_token: String::new(),
+            csp_nonce: current_csp_nonce(),
         }
     }
+
+    pub fn with_role(mut self, auth_gate: &AuthenticationGate) -> Self {
+        self.is_moderator = auth_gate.is_moderator();
+        self.is_admin = auth_gate.is_admin();
+        self
+    }
+
+    pub fn with_csrf(mut self, csrf_token: &CsrfToken) -> Self {
+        self.csrf_token = csrf_token.0.clone();
+        self
+    }
 }
 
 pub struct ProfilePageDataContext {
@@ -80,17 +126,19 @@ pub struct ProfilePageDataContext {
     pub city: String,
     pub init_photos: AddProfilePhotoContext,
     pub is_edit_mode: bool,
+    pub max_photos: u64,
 }
 
 impl ProfilePageDataContext {
     pub fn new(
-        all_photos_folder: &str,
+        photo_storage: &dyn PhotoStorage,
         profile_opt: &Option<ProfileModel>,
         db_photos: &Vec<ProfilePhotoModel>,
         is_edit_mode: bool,
+        max_photos: u64,
     ) -> Self {
         let profile_photo_response =
-            AddProfilePhotoContext::new_with_payload(all_photos_folder, db_photos);
+            AddProfilePhotoContext::new_with_payload(photo_storage, db_photos);
 
         let id = profile_opt.as_ref().map(|f| f.id);
         let name = profile_opt
@@ -121,6 +169,7 @@ impl ProfilePageDataContext {
             city,
             init_photos: profile_photo_response,
             is_edit_mode,
+            max_photos,
         }
     }
 }
@@ -146,26 +195,37 @@ pub fn get_absolute_url(config: &Config, path: &str) -> String {
     }
 }
 
-pub fn get_relative_photo_url(
+pub fn get_photo_url(
     profile_photo: &ProfilePhotoModel,
-    all_photos_folder: &str,
+    rendition: PhotoRendition,
+    photo_storage: &dyn PhotoStorage,
 ) -> String {
-    format!(
-        "/{}/{}/{}",
-        &all_photos_folder,
-        &profile_photo.profile_id.to_string(),
-        &profile_photo.file_name
-    )
+    photo_storage.url(&photo_key(
+        &profile_photo.profile_id,
+        rendition.stored_file_name(profile_photo),
+    ))
+}
+
+/// Renditions of a processing photo do not exist yet, the owner sees a placeholder
+pub fn get_preview_photo_url(
+    profile_photo: &ProfilePhotoModel,
+    photo_storage: &dyn PhotoStorage,
+) -> String {
+    if profile_photo.status == "processing" {
+        PROCESSING_PHOTO_URL.to_owned()
+    } else {
+        get_photo_url(profile_photo, PhotoRendition::Card, photo_storage)
+    }
 }
 
 impl<'a> AddProfilePhotoContext {
     pub fn new_with_payload(
-        all_photos_folder: &'a str,
+        photo_storage: &'a dyn PhotoStorage,
         db_photos: &Vec<ProfilePhotoModel>,
     ) -> Self {
         let photo_urls = db_photos
             .iter()
-            .map(|db_photo| get_relative_photo_url(db_photo, all_photos_folder))
+            .map(|db_photo| get_preview_photo_url(db_photo, photo_storage))
             .collect();
 
         let photo_confings = db_photos
@@ -205,3 +265,21 @@ impl<'a> ProfilePhotoPreviewContext {
         }
     }
 }
+
+/// Accepts both `"170"` and `170`, so one raw request type serves html forms and json clients
+pub fn deserialize_number_as_string<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(i64),
+    }
+
+    match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(value) => Ok(value),
+        StringOrNumber::Number(value) => Ok(value.to_string()),
+    }
+}
@@ -1,9 +1,17 @@
-pub static PROFILES_ON_PAGE: &'static u64 = &12;
-pub static MAX_PROFILE_PHOTOS: &'static u64 = &5;
-
+pub static USER_AGENT_MAX_LENGTH: &'static usize = &255;
+pub static SEARCH_RESULTS_ON_PAGE: &'static i64 = &20;
 
 pub static MSG_COMMENT_ADDED_CODE: &'static str = "comment_added";
 pub static MSG_COMMENT_REMOVED_CODE: &'static str = "comment_removed";
+pub static MSG_COMMENT_IN_REVIEW_CODE: &'static str = "comment_in_review";
+pub static MSG_COMMENT_APPROVED_CODE: &'static str = "comment_approved";
+pub static MSG_COMMENT_REJECTED_CODE: &'static str = "comment_rejected";
+pub static MSG_ADMIN_USER_UPDATED_CODE: &'static str = "admin_user_updated";
+pub static MSG_ADMIN_PROFILE_DELETED_CODE: &'static str = "admin_profile_deleted";
+pub static MSG_ADMIN_PHOTO_DELETED_CODE: &'static str = "admin_photo_deleted";
+pub static MSG_ADMIN_CITY_UPDATED_CODE: &'static str = "admin_city_updated";
+pub static MSG_SESSION_REVOKED_CODE: &'static str = "session_revoked";
+pub static MSG_ALL_SESSIONS_REVOKED_CODE: &'static str = "all_sessions_revoked";
 pub static MSG_PROFILE_ADDED_CODE: &'static str = "profile_added";
 pub static MSG_PROFILE_UPDATED_CODE: &'static str = "profile_updated";
 pub static MSG_SIGN_IN_CODE: &'static str = "sign_in_ok";
@@ -12,6 +20,15 @@ pub static MSG_SERVER_ERROR_CODE: &'static str = "server_error";
 pub static MSG_UNAUTHORIZED_ERROR_CODE: &'static str = "unauthorized";
 pub static MSG_BAD_REQUEST_ERROR_CODE: &'static str = "bad_request";
 pub static MSG_BOT_DETECTED_ERROR_CODE: &'static str = "bot_detected";
+pub static MSG_TOO_MANY_REQUESTS_ERROR_CODE: &'static str = "too_many_requests";
+pub static MSG_CSRF_ERROR_CODE: &'static str = "csrf_error";
+pub static MSG_NOT_FOUND_ERROR_CODE: &'static str = "not_found";
+pub static MSG_VALIDATION_ERROR_CODE: &'static str = "validation_error";
+pub static MSG_PHOTO_UNSUPPORTED_FORMAT_ERROR_CODE: &'static str = "photo_unsupported_format";
+pub static MSG_PHOTO_TOO_LARGE_ERROR_CODE: &'static str = "photo_too_large";
+pub static MSG_PHOTO_TOO_MANY_PIXELS_ERROR_CODE: &'static str = "photo_too_many_pixels";
 
 pub static HOME_DATE_FORMAT: &'static str = "%Y-%m-%d";
+pub static SESSION_DATE_FORMAT: &'static str = "%Y-%m-%d %H:%M";
 pub static NO_PHOTO_URL: &'static str = "/static/img/no_photo.jpg";
+pub static PROCESSING_PHOTO_URL: &'static str = "/static/img/loading.gif";
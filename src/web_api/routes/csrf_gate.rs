@@ -0,0 +1,309 @@
+use std::{
+    future::{ready, Ready},
+    ops::Deref,
+};
+
+use actix_web::{
+    cookie::{time::Duration as CookieDuration, Cookie, SameSite},
+    dev::{Payload, ServiceResponse},
+    error::Error as ActixWebError,
+    web, FromRequest, HttpMessage, HttpRequest,
+};
+use futures::future::LocalBoxFuture;
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use rand::{thread_rng, RngCore};
+use serde::de::DeserializeOwned;
+use sha2::Sha256;
+
+use crate::config::Config;
+
+use super::error::{HtmlError, JsonError};
+
+static CSRF_COOKIE: &str = "csrf_token";
+pub static CSRF_HEADER: &str = "X-CSRF-Token";
+static CSRF_COOKIE_MAX_AGE_DAYS: i64 = 30;
+
+/// Nonce made for this request, the cookie is set on its response
+#[derive(Clone)]
+struct NewCsrfNonce(String);
+
+/// Token for forms of the page. Signed nonce of the `csrf_token` cookie, so a cookie planted
+/// by someone else is useless without the secret
+pub struct CsrfToken(pub String);
+
+impl FromRequest for CsrfToken {
+    type Error = ActixWebError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let config = req.app_data::<web::Data<Config>>().unwrap();
+        let new_nonce_opt = req
+            .extensions()
+            .get::<NewCsrfNonce>()
+            .map(|new_nonce| new_nonce.0.clone());
+        let nonce = match cookie_nonce(req).or(new_nonce_opt) {
+            Some(nonce) => nonce,
+            None => {
+                let mut bytes = [0u8; 32];
+                thread_rng().fill_bytes(&mut bytes);
+                let nonce = hex::encode(bytes);
+                req.extensions_mut().insert(NewCsrfNonce(nonce.clone()));
+                nonce
+            }
+        };
+        ready(Ok(CsrfToken(sign_nonce(&config.jwt_secret, &nonce))))
+    }
+}
+
+/// Url encoded form with a valid `csrf_token` field. Derefs to the form like `web::Form`
+pub struct CsrfForm<T>(pub T);
+
+impl<T> CsrfForm<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for CsrfForm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Form posted by a page: it carries the `csrf_token` field, `#[serde(default)]` so a missing token
+/// is a mismatch and not a bad form
+pub trait CsrfTokenField {
+    fn csrf_token(&self) -> &str;
+}
+
+impl<T: DeserializeOwned + CsrfTokenField + 'static> FromRequest for CsrfForm<T> {
+    type Error = HtmlError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let form_future = web::Form::<T>::from_request(req, payload);
+        let req = req.clone();
+
+        Box::pin(async move {
+            let form = form_future.await.map_err(|err| {
+                info!("Form is invalid: [{}]", err);
+                HtmlError::BadParams
+            })?;
+            if !is_valid_token(&req, form.csrf_token()) {
+                warn!("Form post to [{}] has no valid csrf token", req.path());
+                return Err(HtmlError::CsrfTokenMismatch);
+            }
+            Ok(CsrfForm(form.into_inner()))
+        })
+    }
+}
+
+/// Ajax calls of the pages send the token in the `X-CSRF-Token` header
+pub struct CsrfHeader;
+
+impl FromRequest for CsrfHeader {
+    type Error = JsonError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let csrf_token = req
+            .headers()
+            .get(CSRF_HEADER)
+            .and_then(|header| header.to_str().ok())
+            .unwrap_or_default();
+        if is_valid_token(req, csrf_token) {
+            ready(Ok(CsrfHeader))
+        } else {
+            warn!("Ajax call to [{}] has no valid csrf token", req.path());
+            ready(Err(JsonError::CsrfTokenMismatch))
+        }
+    }
+}
+
+/// Sets the cookie for the nonce `CsrfToken` made during the request, if any
+pub fn set_new_csrf_cookie<B>(response: &mut ServiceResponse<B>) {
+    let new_nonce_opt = response
+        .request()
+        .extensions()
+        .get::<NewCsrfNonce>()
+        .cloned();
+    if let Some(new_nonce) = new_nonce_opt {
+        let cookie = Cookie::build(CSRF_COOKIE, new_nonce.0)
+            .path("/")
+            .max_age(CookieDuration::days(CSRF_COOKIE_MAX_AGE_DAYS))
+            .same_site(SameSite::Lax)
+            .http_only(true)
+            .finish();
+        if let Err(err) = response.response_mut().add_cookie(&cookie) {
+            warn!("Csrf cookie can not be set: [{}]", err);
+        }
+    }
+}
+
+fn cookie_nonce(req: &HttpRequest) -> Option<String> {
+    req.cookie(CSRF_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|nonce| !nonce.is_empty())
+}
+
+fn csrf_mac(jwt_secret: &str, nonce: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(jwt_secret.as_bytes()).unwrap();
+    mac.update(b"csrf:");
+    mac.update(nonce.as_bytes());
+    mac
+}
+
+fn sign_nonce(jwt_secret: &str, nonce: &str) -> String {
+    hex::encode(csrf_mac(jwt_secret, nonce).finalize().into_bytes())
+}
+
+/// Constant time check of the submitted token against the cookie nonce
+fn is_valid_token(req: &HttpRequest, csrf_token: &str) -> bool {
+    let config = req.app_data::<web::Data<Config>>().unwrap();
+    let (Some(nonce), Ok(signature)) = (cookie_nonce(req), hex::decode(csrf_token)) else {
+        return false;
+    };
+    csrf_mac(&config.jwt_secret, &nonce)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::header::ContentType, test::TestRequest, HttpResponse};
+    use serde::Deserialize;
+
+    use super::*;
+
+    static NONCE: &str = "a1b2c3";
+
+    #[derive(Deserialize)]
+    struct DeleteRequest {
+        id: i64,
+        #[serde(default)]
+        csrf_token: String,
+    }
+
+    impl CsrfTokenField for DeleteRequest {
+        fn csrf_token(&self) -> &str {
+            &self.csrf_token
+        }
+    }
+
+    fn config() -> Config {
+        Config::for_tests(&[])
+    }
+
+    fn valid_token() -> String {
+        sign_nonce(&config().jwt_secret, NONCE)
+    }
+
+    fn tampered_token() -> String {
+        let mut token = valid_token();
+        let last = if token.ends_with('0') { "1" } else { "0" };
+        token.replace_range(token.len() - 1.., last);
+        token
+    }
+
+    fn request(has_cookie: bool) -> TestRequest {
+        let request = TestRequest::post().app_data(web::Data::new(config()));
+        if has_cookie {
+            request.cookie(Cookie::new(CSRF_COOKIE, NONCE))
+        } else {
+            request
+        }
+    }
+
+    async fn post_form(has_cookie: bool, body: &str) -> Result<CsrfForm<DeleteRequest>, HtmlError> {
+        let (req, mut payload) = request(has_cookie)
+            .insert_header(ContentType::form_url_encoded())
+            .set_payload(body.to_string())
+            .to_http_parts();
+        CsrfForm::<DeleteRequest>::from_request(&req, &mut payload).await
+    }
+
+    async fn call_with_header(has_cookie: bool, csrf_token_opt: Option<&str>) -> bool {
+        let mut request = request(has_cookie);
+        if let Some(csrf_token) = csrf_token_opt {
+            request = request.insert_header((CSRF_HEADER, csrf_token));
+        }
+        let (req, mut payload) = request.to_http_parts();
+        CsrfHeader::from_request(&req, &mut payload).await.is_ok()
+    }
+
+    #[actix_rt::test]
+    async fn form_with_valid_token_is_read() {
+        let form = post_form(true, &format!("id=42&csrf_token={}", valid_token()))
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(form.id, 42);
+    }
+
+    #[actix_rt::test]
+    async fn form_with_tampered_token_is_rejected() {
+        let result = post_form(true, &format!("id=42&csrf_token={}", tampered_token())).await;
+
+        assert!(matches!(result, Err(HtmlError::CsrfTokenMismatch)));
+    }
+
+    #[actix_rt::test]
+    async fn form_without_cookie_is_rejected() {
+        let result = post_form(false, &format!("id=42&csrf_token={}", valid_token())).await;
+
+        assert!(matches!(result, Err(HtmlError::CsrfTokenMismatch)));
+    }
+
+    #[actix_rt::test]
+    async fn form_without_token_field_is_rejected() {
+        let result = post_form(true, "id=42").await;
+
+        assert!(matches!(result, Err(HtmlError::CsrfTokenMismatch)));
+    }
+
+    #[actix_rt::test]
+    async fn invalid_form_is_bad_params() {
+        let result = post_form(true, &format!("id=first&csrf_token={}", valid_token())).await;
+
+        assert!(matches!(result, Err(HtmlError::BadParams)));
+    }
+
+    #[actix_rt::test]
+    async fn header_token_is_checked() {
+        assert!(call_with_header(true, Some(&valid_token())).await);
+        assert!(!call_with_header(true, Some(&tampered_token())).await);
+        assert!(!call_with_header(false, Some(&valid_token())).await);
+        assert!(!call_with_header(true, None).await);
+    }
+
+    #[actix_rt::test]
+    async fn token_signs_the_cookie_nonce() {
+        let (req, mut payload) = request(true).to_http_parts();
+        let csrf_token = CsrfToken::from_request(&req, &mut payload).await.unwrap();
+
+        assert_eq!(csrf_token.0, valid_token());
+    }
+
+    #[actix_rt::test]
+    async fn token_without_cookie_sets_a_new_one() {
+        let (req, mut payload) = request(false).to_http_parts();
+        let csrf_token = CsrfToken::from_request(&req, &mut payload).await.unwrap();
+        let mut response = ServiceResponse::new(req, HttpResponse::Ok().finish());
+        set_new_csrf_cookie(&mut response);
+
+        let cookie = response
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == CSRF_COOKIE)
+            .unwrap();
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(
+            csrf_token.0,
+            sign_nonce(&config().jwt_secret, cookie.value())
+        );
+    }
+}
@@ -1,13 +1,14 @@
 use actix_web::{web, Responder};
+use log::info;
 use serde::Deserialize;
 use uuid::Uuid;
-use log::info;
 
 use crate::{
     config::Config,
     db::DbProvider,
     web_api::{
         auth::AuthenticationGate,
+        photo::PhotoStorage,
         routes::{
             common::{HeadContext, NavContext, ProfilePageDataContext},
             html_render::HtmlPage,
@@ -17,13 +18,15 @@ use crate::{
 };
 use rust_i18n::t;
 
-use super::error::HtmlError;
+use super::{csrf_gate::CsrfToken, error::HtmlError};
 
 pub async fn edit_profile_page(
     auth_gate: AuthenticationGate,
+    csrf_token: CsrfToken,
     db_provider: web::Data<DbProvider>,
     query: web::Query<EditProfileRequest>,
     config: web::Data<Config>,
+    photo_storage: web::Data<dyn PhotoStorage>,
 ) -> Result<impl Responder, HtmlError> {
     if !auth_gate.is_authorized {
         return Err(HtmlError::NotAuthorized);
@@ -42,27 +45,30 @@ pub async fn edit_profile_page(
         .await?;
     let profile = profile_opt.ok_or(HtmlError::NotFound)?;
 
-    let profile_photos = db_provider.find_all_profile_photos_for(&profile.id).await?;
+    let profile_photos = db_provider
+        .find_all_uploaded_profile_photos_for(&profile.id)
+        .await?;
 
     let cities_names = db_provider.find_city_names().await?;
 
     let data_contex = ProfilePageDataContext::new(
-        &config.all_photos_folder_name,
+        photo_storage.as_ref(),
         &Some(profile),
         &profile_photos,
         true,
+        config.max_profile_photos,
     );
 
     let nav_context = NavContext::new(
-        &auth_gate.user_name.unwrap(),
+        &auth_gate.user_name.clone().unwrap(),
         "",
-        &config.captcha_google_id,
         false,
         &Option::None,
         &cities_names,
-        &config.oauth_google_client_id,
-        &config.oauth_google_redirect_url,
-    );
+        &config,
+    )
+    .with_role(&auth_gate)
+    .with_csrf(&csrf_token);
     let error_context = ErrorContext::empty();
     let head_context = HeadContext::new(
         t!("edit_profile_page_title").to_string().as_str(),
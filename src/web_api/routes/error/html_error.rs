@@ -10,10 +10,10 @@ use log::error;
 use sea_orm::DbErr;
 
 use crate::web_api::{
-    recaptcha::CaptchaError,
+    captcha::CaptchaError,
     routes::constant::{
-        MSG_BAD_REQUEST_ERROR_CODE, MSG_BOT_DETECTED_ERROR_CODE, MSG_SERVER_ERROR_CODE,
-        MSG_UNAUTHORIZED_ERROR_CODE,
+        MSG_BAD_REQUEST_ERROR_CODE, MSG_BOT_DETECTED_ERROR_CODE, MSG_CSRF_ERROR_CODE,
+        MSG_SERVER_ERROR_CODE, MSG_UNAUTHORIZED_ERROR_CODE,
     },
 };
 
@@ -26,6 +26,7 @@ pub enum HtmlError {
     BadParams,
     NotFound,
     BotDetection,
+    CsrfTokenMismatch,
 }
 
 impl Display for HtmlError {
@@ -76,6 +77,7 @@ impl error::ResponseError for HtmlError {
             HtmlError::NotAuthorized => homepage(MSG_UNAUTHORIZED_ERROR_CODE),
             HtmlError::BadParams => homepage(MSG_BAD_REQUEST_ERROR_CODE),
             HtmlError::BotDetection => homepage(MSG_BOT_DETECTED_ERROR_CODE),
+            HtmlError::CsrfTokenMismatch => homepage(MSG_CSRF_ERROR_CODE),
             HtmlError::NotFound => page_404(),
         }
     }
@@ -86,6 +88,7 @@ impl error::ResponseError for HtmlError {
             HtmlError::NotAuthorized => StatusCode::UNAUTHORIZED,
             HtmlError::BadParams => StatusCode::BAD_REQUEST,
             HtmlError::BotDetection => StatusCode::FORBIDDEN,
+            HtmlError::CsrfTokenMismatch => StatusCode::FORBIDDEN,
             HtmlError::NotFound => StatusCode::NOT_FOUND,
         }
     }
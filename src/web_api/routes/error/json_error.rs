@@ -1,12 +1,28 @@
-use std::{error::Error, fmt::Display, io};
+use std::{collections::HashMap, error::Error, fmt::Display, io};
 
-use actix_web::{error, http::StatusCode, web, HttpResponse};
-use log::error;
+use actix_web::{
+    error,
+    http::{header::RETRY_AFTER, StatusCode},
+    web, HttpResponse,
+};
+use jsonwebtoken_google::ParserError;
+use log::{error, info};
 use sea_orm::DbErr;
 use serde::Serialize;
 
-use crate::web_api::routes::constant::{
-    MSG_BAD_REQUEST_ERROR_CODE, MSG_SERVER_ERROR_CODE, MSG_UNAUTHORIZED_ERROR_CODE,
+use crate::web_api::{
+    captcha::CaptchaError,
+    photo::UploadError,
+    routes::{
+        constant::{
+            MSG_BAD_REQUEST_ERROR_CODE, MSG_BOT_DETECTED_ERROR_CODE, MSG_CSRF_ERROR_CODE,
+            MSG_NOT_FOUND_ERROR_CODE,
+            MSG_PHOTO_TOO_LARGE_ERROR_CODE, MSG_PHOTO_TOO_MANY_PIXELS_ERROR_CODE,
+            MSG_PHOTO_UNSUPPORTED_FORMAT_ERROR_CODE, MSG_SERVER_ERROR_CODE,
+            MSG_TOO_MANY_REQUESTS_ERROR_CODE, MSG_UNAUTHORIZED_ERROR_CODE, MSG_VALIDATION_ERROR_CODE,
+        },
+        validator::ErrorContext,
+    },
 };
 
 impl Error for JsonError {}
@@ -14,6 +30,9 @@ impl Error for JsonError {}
 #[derive(Serialize, Debug)]
 struct JsonErrorPayload<'a> {
     error: &'a str,
+    // field name -> validation code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fields: Option<&'a HashMap<String, String>>,
 }
 
 #[derive(Debug)]
@@ -21,6 +40,15 @@ pub enum JsonError {
     ServerError,
     NotAuthorized,
     BadParams,
+    NotFound,
+    BotDetection,
+    CsrfTokenMismatch,
+    Validation(ErrorContext),
+    PhotoUnsupportedFormat,
+    PhotoTooLarge,
+    PhotoTooManyPixels,
+    // seconds until the next try
+    TooManyRequests(u64),
 }
 
 impl Display for JsonError {
@@ -36,6 +64,44 @@ impl From<DbErr> for JsonError {
     }
 }
 
+impl From<uuid::Error> for JsonError {
+    fn from(err: uuid::Error) -> Self {
+        error!("[uuid::Error] Uuid error happens. Hacks?: [{}]", &err);
+        JsonError::BadParams
+    }
+}
+
+impl From<CaptchaError> for JsonError {
+    fn from(err: CaptchaError) -> Self {
+        error!("[CaptchaError] captcha exception: [{}]", &err);
+        JsonError::BadParams
+    }
+}
+
+impl From<ParserError> for JsonError {
+    fn from(err: ParserError) -> Self {
+        error!("[ParserError] parse exception: [{}]", &err);
+        JsonError::NotAuthorized
+    }
+}
+
+impl From<ErrorContext> for JsonError {
+    fn from(err: ErrorContext) -> Self {
+        JsonError::Validation(err)
+    }
+}
+
+impl From<UploadError> for JsonError {
+    fn from(err: UploadError) -> Self {
+        info!("[UploadError] photo rejected: [{}]", &err);
+        match err {
+            UploadError::UnsupportedFormat => JsonError::PhotoUnsupportedFormat,
+            UploadError::TooLarge => JsonError::PhotoTooLarge,
+            UploadError::TooManyPixels => JsonError::PhotoTooManyPixels,
+        }
+    }
+}
+
 impl From<io::Error> for JsonError {
     fn from(err: io::Error) -> Self {
         error!("[io::Error] io exception: [{}]", &err);
@@ -45,26 +111,73 @@ impl From<io::Error> for JsonError {
 
 impl error::ResponseError for JsonError {
     fn error_response(&self) -> HttpResponse {
-        match *self {
-            JsonError::ServerError => error_json(MSG_SERVER_ERROR_CODE, &self.status_code()),
+        match self {
+            JsonError::ServerError => error_json(MSG_SERVER_ERROR_CODE, &self.status_code(), None),
             JsonError::NotAuthorized => {
-                error_json(MSG_UNAUTHORIZED_ERROR_CODE, &self.status_code())
+                error_json(MSG_UNAUTHORIZED_ERROR_CODE, &self.status_code(), None)
+            }
+            JsonError::BadParams => {
+                error_json(MSG_BAD_REQUEST_ERROR_CODE, &self.status_code(), None)
+            }
+            JsonError::NotFound => error_json(MSG_NOT_FOUND_ERROR_CODE, &self.status_code(), None),
+            JsonError::BotDetection => {
+                error_json(MSG_BOT_DETECTED_ERROR_CODE, &self.status_code(), None)
+            }
+            JsonError::CsrfTokenMismatch => {
+                error_json(MSG_CSRF_ERROR_CODE, &self.status_code(), None)
+            }
+            JsonError::Validation(error_context) => error_json(
+                MSG_VALIDATION_ERROR_CODE,
+                &self.status_code(),
+                Some(&error_context.data),
+            ),
+            JsonError::PhotoUnsupportedFormat => error_json(
+                MSG_PHOTO_UNSUPPORTED_FORMAT_ERROR_CODE,
+                &self.status_code(),
+                None,
+            ),
+            JsonError::PhotoTooLarge => {
+                error_json(MSG_PHOTO_TOO_LARGE_ERROR_CODE, &self.status_code(), None)
+            }
+            JsonError::PhotoTooManyPixels => error_json(
+                MSG_PHOTO_TOO_MANY_PIXELS_ERROR_CODE,
+                &self.status_code(),
+                None,
+            ),
+            JsonError::TooManyRequests(retry_after_seconds) => {
+                let mut response =
+                    error_json(MSG_TOO_MANY_REQUESTS_ERROR_CODE, &self.status_code(), None);
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, (*retry_after_seconds).into());
+                response
             }
-            JsonError::BadParams => error_json(MSG_BAD_REQUEST_ERROR_CODE, &self.status_code()),
         }
     }
 
     fn status_code(&self) -> StatusCode {
-        match *self {
+        match self {
             JsonError::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
             JsonError::NotAuthorized => StatusCode::UNAUTHORIZED,
             JsonError::BadParams => StatusCode::BAD_REQUEST,
+            JsonError::NotFound => StatusCode::NOT_FOUND,
+            JsonError::BotDetection => StatusCode::FORBIDDEN,
+            JsonError::CsrfTokenMismatch => StatusCode::FORBIDDEN,
+            JsonError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            JsonError::PhotoUnsupportedFormat => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            JsonError::PhotoTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            JsonError::PhotoTooManyPixels => StatusCode::PAYLOAD_TOO_LARGE,
+            JsonError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
         }
     }
 }
 
-fn error_json(msg: &str, status: &StatusCode) -> HttpResponse {
-    let response = JsonErrorPayload { error: msg };
+fn error_json(
+    msg: &str,
+    status: &StatusCode,
+    fields: Option<&HashMap<String, String>>,
+) -> HttpResponse {
+    let response = JsonErrorPayload { error: msg, fields };
     let data = web::Json(response);
 
     HttpResponse::build(status.to_owned())
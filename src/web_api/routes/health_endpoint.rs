@@ -0,0 +1,203 @@
+use actix_web::{http::header::ContentType, web, HttpRequest, HttpResponse, Responder};
+use log::error;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::Config,
+    db::{DbProvider, StatusTable},
+    web_api::{
+        auth::bearer_token,
+        metrics::{render_metrics, Gauge},
+        photo::{OriginalPhotoStorage, PhotoStorage},
+    },
+};
+
+use super::error::JsonError;
+
+/// Process is up. Says nothing about the database
+pub async fn healthz() -> impl Responder {
+    web::Json(HealthResponse {
+        status: "ok",
+        db: None,
+        photo_storage: None,
+        original_photo_storage: None,
+    })
+}
+
+/// Ready for traffic: database answers and both photo storages take writes
+pub async fn readyz(
+    db_provider: web::Data<DbProvider>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+    original_photo_storage: web::Data<OriginalPhotoStorage>,
+) -> impl Responder {
+    let db_status = match db_provider.ping().await {
+        Ok(_) => "ok",
+        Err(err) => {
+            error!("Readiness. Database is not available: [{}]", &err);
+            "error"
+        }
+    };
+    readiness(
+        db_status,
+        photo_storage.as_ref(),
+        original_photo_storage.0.as_ref(),
+    )
+    .await
+}
+
+async fn readiness(
+    db_status: &'static str,
+    photo_storage: &dyn PhotoStorage,
+    original_photo_storage: &dyn PhotoStorage,
+) -> HttpResponse {
+    let photo_storage_status = check_storage("photo", photo_storage).await;
+    let original_photo_storage_status =
+        check_storage("original photo", original_photo_storage).await;
+
+    let is_ready = [
+        db_status,
+        photo_storage_status,
+        original_photo_storage_status,
+    ]
+    .iter()
+    .all(|status| *status == "ok");
+    let response = HealthResponse {
+        status: if is_ready { "ok" } else { "error" },
+        db: Some(db_status),
+        photo_storage: Some(photo_storage_status),
+        original_photo_storage: Some(original_photo_storage_status),
+    };
+    if is_ready {
+        HttpResponse::Ok().json(response)
+    } else {
+        HttpResponse::ServiceUnavailable().json(response)
+    }
+}
+
+/// Prometheus scrape target. Off without a token in the config
+pub async fn metrics_endpoint(
+    req: HttpRequest,
+    db_provider: web::Data<DbProvider>,
+    config: web::Data<Config>,
+) -> Result<impl Responder, JsonError> {
+    let metrics_token = config
+        .metrics_token_opt
+        .as_ref()
+        .ok_or(JsonError::NotFound)?;
+    // digests keep the comparison from leaking how much of the token matched
+    let is_authorized = bearer_token(&req)
+        .is_some_and(|token| Sha256::digest(token) == Sha256::digest(metrics_token));
+    if !is_authorized {
+        return Err(JsonError::NotAuthorized);
+    }
+
+    let gauges = [
+        Gauge {
+            name: "board_profiles",
+            help: "Profiles by status",
+            label_name: "status",
+            values: db_provider.count_by_status(StatusTable::Profile).await?,
+        },
+        Gauge {
+            name: "board_comments",
+            help: "Comments by status",
+            label_name: "status",
+            values: db_provider.count_by_status(StatusTable::Comment).await?,
+        },
+    ];
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body(render_metrics(&gauges)))
+}
+
+async fn check_storage(name: &str, storage: &dyn PhotoStorage) -> &'static str {
+    match storage.ping().await {
+        Ok(_) => "ok",
+        Err(err) => {
+            error!("Readiness. {} storage is not writable: [{}]", name, &err);
+            "error"
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub db: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub photo_storage: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_photo_storage: Option<&'static str>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+    use actix_web::{body::to_bytes, http::StatusCode};
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    use crate::web_api::photo::FsPhotoStorage;
+
+    use super::*;
+
+    fn photo_folder(permissions: u32) -> PathBuf {
+        let folder = env::temp_dir().join(format!("readyz-{}", Uuid::new_v4()));
+        fs::create_dir_all(folder.join("profile")).unwrap();
+        fs::write(folder.join("profile").join("photo.jpg"), b"photo").unwrap();
+        fs::set_permissions(&folder, fs::Permissions::from_mode(permissions)).unwrap();
+        folder
+    }
+
+    async fn readiness_of(photo_folder: &PathBuf) -> (StatusCode, Value) {
+        let photo_storage = FsPhotoStorage::new(photo_folder.to_str().unwrap(), "photos");
+        let original_folder = photo_folder.with_extension("originals");
+        fs::create_dir_all(&original_folder).unwrap();
+        let original_photo_storage =
+            FsPhotoStorage::new(original_folder.to_str().unwrap(), "originals");
+
+        let response = readiness("ok", &photo_storage, &original_photo_storage).await;
+        let status = response.status();
+        let body = to_bytes(response.into_body()).await.unwrap();
+        fs::remove_dir_all(&original_folder).unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[actix_rt::test]
+    async fn writable_photo_folder_is_ready_and_keeps_no_probe() {
+        let folder = photo_folder(0o755);
+
+        let (status, body) = readiness_of(&folder).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["photo_storage"], json!("ok"));
+        let files: Vec<_> = fs::read_dir(&folder)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, vec!["profile"]);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn read_only_photo_folder_is_not_ready() {
+        let folder = photo_folder(0o555);
+        // root writes through the permissions, so there is nothing to check
+        if fs::write(folder.join("root-check"), b"").is_ok() {
+            fs::remove_dir_all(&folder).unwrap();
+            return;
+        }
+
+        let (status, body) = readiness_of(&folder).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], json!("error"));
+        assert_eq!(body["photo_storage"], json!("error"));
+        assert_eq!(body["original_photo_storage"], json!("ok"));
+
+        fs::set_permissions(&folder, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
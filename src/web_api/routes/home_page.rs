@@ -1,5 +1,5 @@
 use actix_web::{web, Responder};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
@@ -7,9 +7,9 @@ use crate::{
     db::{DbProvider, ProfileModel, ProfilePhotoModel},
     web_api::{
         auth::AuthenticationGate,
+        photo::{PhotoRendition, PhotoStorage},
         routes::{
             common::{HeadContext, NavContext},
-            constant::PROFILES_ON_PAGE,
             html_render::HtmlPage,
         },
     },
@@ -18,16 +18,21 @@ use log::info;
 use rust_i18n::t;
 
 use super::{
-    bot_detector_gate::BotDetector, common::get_relative_photo_url, constant::HOME_DATE_FORMAT,
+    bot_detector_gate::BotDetector,
+    common::get_photo_url,
+    constant::{HOME_DATE_FORMAT, SEARCH_RESULTS_ON_PAGE},
+    csrf_gate::CsrfToken,
     error::HtmlError,
 };
 
 pub async fn index_page(
     db_provider: web::Data<DbProvider>,
     auth_gate: AuthenticationGate,
+    csrf_token: CsrfToken,
     bot_detector: BotDetector,
     query: web::Query<QueryRequest>,
     config: web::Data<Config>,
+    photo_storage: web::Data<dyn PhotoStorage>,
 ) -> Result<impl Responder, HtmlError> {
     async fn get_nav_context(
         auth_gate: &AuthenticationGate,
@@ -52,28 +57,27 @@ pub async fn index_page(
         Ok(NavContext::new(
             user_name,
             current_city,
-            &config.captcha_google_id,
             is_user_profiles,
             &query.search,
             &city_names,
-            &config.oauth_google_client_id,
-            &config.oauth_google_redirect_url,
-        ))
+            config,
+        )
+        .with_role(auth_gate))
     }
 
     async fn get_data_context(
         db_provider: &web::Data<DbProvider>,
-        config: &web::Data<Config>,
+        photo_storage: &dyn PhotoStorage,
         query: &web::Query<QueryRequest>,
         auth_gate: &AuthenticationGate,
+        profiles_on_page: u64,
     ) -> Result<HomePageDataContext, HtmlError> {
         let is_user_profiles = auth_gate.is_authorized && query.show_my.unwrap_or_default();
         let is_search = query.search.is_some();
-        let search_result = 20;
 
         let all_profiles = if is_search {
             let profiles = db_provider
-                .search_profiles(query.search.as_ref().unwrap(), search_result)
+                .search_profiles(query.search.as_ref().unwrap(), *SEARCH_RESULTS_ON_PAGE)
                 .await?;
             (0, profiles)
         } else if is_user_profiles {
@@ -84,7 +88,7 @@ pub async fn index_page(
         } else {
             // regular page
             db_provider
-                .profiles_pagination(PROFILES_ON_PAGE.to_owned(), &query.page, &query.filter_city)
+                .profiles_pagination(profiles_on_page, &query.page, &query.filter_city)
                 .await?
         };
         let all_profiles_ids = all_profiles.1.iter().map(|profile| profile.id).collect();
@@ -97,7 +101,7 @@ pub async fn index_page(
             .iter()
             .map(|profile| {
                 let profile_photo_opt = profile_id_and_profile_photo_map.get(&profile.id).unwrap();
-                HomePageProfileDataContext::new(&profile, profile_photo_opt, config)
+                HomePageProfileDataContext::new(profile, profile_photo_opt, photo_storage)
             })
             .collect();
 
@@ -122,9 +126,13 @@ pub async fn index_page(
     async fn get_head_context(
         db_provider: &web::Data<DbProvider>,
         config: &web::Data<Config>,
+        photo_storage: &dyn PhotoStorage,
         search: &Option<String>,
     ) -> Result<HeadContext, HtmlError> {
-        let photo = &db_provider.find_any_active_profile_photo().await?;
+        let photo_url_opt = db_provider
+            .find_any_active_profile_photo()
+            .await?
+            .map(|photo| get_photo_url(&photo, PhotoRendition::Full, photo_storage));
         let is_search = search.is_some();
         let title = if is_search {
             format!(
@@ -140,7 +148,7 @@ pub async fn index_page(
             title.as_str(),
             t!("main_page_description").to_string().as_str(),
             &config,
-            photo,
+            &photo_url_opt,
         ))
     }
 
@@ -149,9 +157,19 @@ pub async fn index_page(
         auth_gate.is_authorized, bot_detector.is_bot
     );
 
-    let nav_context = get_nav_context(&auth_gate, &query, &config, &db_provider).await?;
-    let data_context = get_data_context(&db_provider, &config, &query, &auth_gate).await?;
-    let head_context = get_head_context(&db_provider, &config, &query.search).await?;
+    let nav_context = get_nav_context(&auth_gate, &query, &config, &db_provider)
+        .await?
+        .with_csrf(&csrf_token);
+    let data_context = get_data_context(
+        &db_provider,
+        photo_storage.as_ref(),
+        &query,
+        &auth_gate,
+        config.profiles_on_page,
+    )
+    .await?;
+    let head_context =
+        get_head_context(&db_provider, &config, photo_storage.as_ref(), &query.search).await?;
     Ok(HtmlPage::homepage(
         &head_context,
         &nav_context,
@@ -159,6 +177,7 @@ pub async fn index_page(
     ))
 }
 
+#[derive(Serialize)]
 pub struct Pagination {
     pub has_next: bool,
     pub has_previous: bool,
@@ -173,7 +192,7 @@ pub struct HomePageDataContext {
     pub pagination: Pagination,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize)]
 pub struct HomePageProfileDataContext {
     pub id: Uuid,
     pub name: String,
@@ -185,15 +204,15 @@ pub struct HomePageProfileDataContext {
 }
 
 impl HomePageProfileDataContext {
-    fn new(
+    pub fn new(
         profile: &ProfileModel,
         profile_photo_opt: &Option<ProfilePhotoModel>,
-        config: &web::Data<Config>,
+        photo_storage: &dyn PhotoStorage,
     ) -> Self {
         let short_description: String = profile.description.chars().take(50).collect();
-        let photo_url_opt = profile_photo_opt.as_ref().map(|profile_photo| {
-            get_relative_photo_url(profile_photo, &config.all_photos_folder_name)
-        });
+        let photo_url_opt = profile_photo_opt
+            .as_ref()
+            .map(|profile_photo| get_photo_url(profile_photo, PhotoRendition::Card, photo_storage));
 
         let date_create = profile.created_at.format(HOME_DATE_FORMAT).to_string();
         HomePageProfileDataContext {
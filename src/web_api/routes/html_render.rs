@@ -1,9 +1,15 @@
-use actix_web::http::header::ContentType;
+use actix_web::http::header::{ContentType, RETRY_AFTER};
 use actix_web::HttpResponse;
 use sailfish::TemplateOnce;
 
+use super::admin_page::{
+    AdminCitiesPageDataContext, AdminProfilePageDataContext, AdminProfilesPageDataContext,
+    AdminUsersPageDataContext,
+};
 use super::common::{HeadContext, NavContext, ProfilePageDataContext};
 use super::home_page::HomePageDataContext;
+use super::moderation_page::ModerationPageDataContext;
+use super::sessions_page::SessionsPageDataContext;
 use super::sitemap_page::SitemapContext;
 use super::validator::ErrorContext;
 use super::view_profile_page::ViewProfilePageDataContext;
@@ -15,6 +21,14 @@ struct P404<'a> {
     nav_context: &'a NavContext,
 }
 
+#[derive(TemplateOnce)]
+#[template(path = "too_many_requests.stpl")]
+struct TooManyRequests<'a> {
+    head_context: &'a HeadContext,
+    nav_context: &'a NavContext,
+    retry_after_minutes: u64,
+}
+
 #[derive(TemplateOnce)]
 #[template(path = "home.stpl")]
 struct Home<'a> {
@@ -41,6 +55,54 @@ struct ViewProfile<'a> {
     error_context: &'a ErrorContext,
 }
 
+#[derive(TemplateOnce)]
+#[template(path = "moderation_comments.stpl")]
+struct ModerationComments<'a> {
+    head_context: &'a HeadContext,
+    nav_context: &'a NavContext,
+    data_context: &'a ModerationPageDataContext,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "sessions.stpl")]
+struct Sessions<'a> {
+    head_context: &'a HeadContext,
+    nav_context: &'a NavContext,
+    data_context: &'a SessionsPageDataContext,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "admin_users.stpl")]
+struct AdminUsers<'a> {
+    head_context: &'a HeadContext,
+    nav_context: &'a NavContext,
+    data_context: &'a AdminUsersPageDataContext,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "admin_profiles.stpl")]
+struct AdminProfiles<'a> {
+    head_context: &'a HeadContext,
+    nav_context: &'a NavContext,
+    data_context: &'a AdminProfilesPageDataContext,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "admin_profile.stpl")]
+struct AdminProfile<'a> {
+    head_context: &'a HeadContext,
+    nav_context: &'a NavContext,
+    data_context: &'a AdminProfilePageDataContext,
+}
+
+#[derive(TemplateOnce)]
+#[template(path = "admin_cities.stpl")]
+struct AdminCities<'a> {
+    head_context: &'a HeadContext,
+    nav_context: &'a NavContext,
+    data_context: &'a AdminCitiesPageDataContext,
+}
+
 #[derive(TemplateOnce)]
 #[template(path = "sitemap.stpl")]
 struct Sitemap<'a> {
@@ -102,6 +164,102 @@ impl HtmlPage {
         )
     }
 
+    pub fn moderation_comments(
+        head_context: &HeadContext,
+        nav_context: &NavContext,
+        data_context: &ModerationPageDataContext,
+    ) -> HttpResponse {
+        HttpResponse::Ok().body(
+            ModerationComments {
+                head_context,
+                nav_context,
+                data_context,
+            }
+            .render_once()
+            .unwrap(),
+        )
+    }
+
+    pub fn sessions(
+        head_context: &HeadContext,
+        nav_context: &NavContext,
+        data_context: &SessionsPageDataContext,
+    ) -> HttpResponse {
+        HttpResponse::Ok().body(
+            Sessions {
+                head_context,
+                nav_context,
+                data_context,
+            }
+            .render_once()
+            .unwrap(),
+        )
+    }
+
+    pub fn admin_users(
+        head_context: &HeadContext,
+        nav_context: &NavContext,
+        data_context: &AdminUsersPageDataContext,
+    ) -> HttpResponse {
+        HttpResponse::Ok().body(
+            AdminUsers {
+                head_context,
+                nav_context,
+                data_context,
+            }
+            .render_once()
+            .unwrap(),
+        )
+    }
+
+    pub fn admin_profiles(
+        head_context: &HeadContext,
+        nav_context: &NavContext,
+        data_context: &AdminProfilesPageDataContext,
+    ) -> HttpResponse {
+        HttpResponse::Ok().body(
+            AdminProfiles {
+                head_context,
+                nav_context,
+                data_context,
+            }
+            .render_once()
+            .unwrap(),
+        )
+    }
+
+    pub fn admin_profile(
+        head_context: &HeadContext,
+        nav_context: &NavContext,
+        data_context: &AdminProfilePageDataContext,
+    ) -> HttpResponse {
+        HttpResponse::Ok().body(
+            AdminProfile {
+                head_context,
+                nav_context,
+                data_context,
+            }
+            .render_once()
+            .unwrap(),
+        )
+    }
+
+    pub fn admin_cities(
+        head_context: &HeadContext,
+        nav_context: &NavContext,
+        data_context: &AdminCitiesPageDataContext,
+    ) -> HttpResponse {
+        HttpResponse::Ok().body(
+            AdminCities {
+                head_context,
+                nav_context,
+                data_context,
+            }
+            .render_once()
+            .unwrap(),
+        )
+    }
+
     pub fn p404(head_context: &HeadContext, nav_context: &NavContext) -> HttpResponse {
         HttpResponse::NotFound().body(
             P404 {
@@ -113,6 +271,24 @@ impl HtmlPage {
         )
     }
 
+    pub fn too_many_requests(
+        head_context: &HeadContext,
+        nav_context: &NavContext,
+        retry_after_seconds: u64,
+    ) -> HttpResponse {
+        HttpResponse::TooManyRequests()
+            .insert_header((RETRY_AFTER, retry_after_seconds))
+            .body(
+                TooManyRequests {
+                    head_context,
+                    nav_context,
+                    retry_after_minutes: retry_after_seconds.div_ceil(60),
+                }
+                .render_once()
+                .unwrap(),
+            )
+    }
+
     pub fn sitemap(context: &SitemapContext) -> HttpResponse {
         HttpResponse::Ok()
             .content_type(ContentType::xml())
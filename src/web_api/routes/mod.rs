@@ -1,35 +1,75 @@
 mod add_profile_page;
+mod admin_gate;
+mod admin_page;
+mod api_v1;
 mod authorization_endpoint;
+mod bot_detector_gate;
 mod common;
 mod constant;
+mod csrf_gate;
 mod edit_profile_page;
 mod error;
+mod health_endpoint;
 mod home_page;
 mod html_render;
+mod moderation_page;
 mod p404_page;
+mod photo_endpoint;
 mod profile_endpoints;
+mod robots_page;
+mod sessions_page;
 mod sitemap_page;
+mod too_many_requests_page;
 mod validator;
 mod view_profile_page;
-mod robots_page;
-mod bot_detector_gate;
+
+pub(crate) use error::JsonError;
+
+pub use csrf_gate::set_new_csrf_cookie;
 
 pub use robots_page::robots_txt;
 pub use sitemap_page::sitemap;
 
+pub use health_endpoint::healthz;
+pub use health_endpoint::metrics_endpoint;
+pub use health_endpoint::readyz;
+
+pub use api_v1::api_v1_routes;
+
 pub use home_page::index_page;
 pub use p404_page::p404_page;
+pub use too_many_requests_page::too_many_requests_page;
+pub use photo_endpoint::photo_endpoint;
 
 pub use add_profile_page::add_or_edit_profile_post;
 pub use add_profile_page::add_profile_page;
 pub use edit_profile_page::edit_profile_page;
-pub use view_profile_page::view_profile_page;
 pub use view_profile_page::add_comment;
+pub use view_profile_page::view_profile_page;
 
 pub use profile_endpoints::add_profile_photo_endpoint;
+pub use profile_endpoints::delete_comment_endpoint;
 pub use profile_endpoints::delete_profile_endpoint;
 pub use profile_endpoints::delete_profile_photo_endpoint;
-pub use profile_endpoints::delete_comment_endpoint;
+pub use profile_endpoints::profile_photo_status_endpoint;
+pub use profile_endpoints::reorder_profile_photos_endpoint;
+
+pub use admin_page::admin_cities_page;
+pub use admin_page::admin_city_toggle_endpoint;
+pub use admin_page::admin_profile_delete_endpoint;
+pub use admin_page::admin_profile_page;
+pub use admin_page::admin_profile_photo_delete_endpoint;
+pub use admin_page::admin_profiles_page;
+pub use admin_page::admin_user_role_endpoint;
+pub use admin_page::admin_users_page;
+
+pub use moderation_page::approve_comment_endpoint;
+pub use moderation_page::moderation_comments_page;
+pub use moderation_page::reject_comment_endpoint;
+
+pub use sessions_page::revoke_all_sessions_endpoint;
+pub use sessions_page::revoke_session_endpoint;
+pub use sessions_page::sessions_page;
 
 pub use authorization_endpoint::google_sign_in_endpoint;
 pub use authorization_endpoint::sign_out_endpoint;
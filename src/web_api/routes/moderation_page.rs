@@ -0,0 +1,256 @@
+use actix_web::{http::header::LOCATION, web, HttpResponse, Responder};
+use log::info;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    db::{CommentModel, DbProvider, UserModel},
+    web_api::{
+        auth::{is_moderator, AuthenticationGate},
+        moderation::{COMMENT_STATUS_APPROVED, COMMENT_STATUS_REJECTED},
+        routes::{
+            common::{HeadContext, NavContext},
+            constant::{HOME_DATE_FORMAT, MSG_COMMENT_APPROVED_CODE, MSG_COMMENT_REJECTED_CODE},
+            home_page::Pagination,
+            html_render::HtmlPage,
+            validator::Validator,
+        },
+    },
+};
+use rust_i18n::t;
+
+use super::{
+    csrf_gate::{CsrfForm, CsrfToken, CsrfTokenField},
+    error::HtmlError,
+};
+
+// role is re-checked in db, jwt could be issued before the role was changed
+async fn resolve_moderator(
+    auth_gate: &AuthenticationGate,
+    db_provider: &web::Data<DbProvider>,
+) -> Result<UserModel, HtmlError> {
+    if !auth_gate.is_authorized {
+        return Err(HtmlError::NotAuthorized);
+    }
+
+    let user_id = auth_gate.user_id.unwrap();
+    let user = db_provider
+        .find_user_by_id(user_id)
+        .await?
+        .ok_or(HtmlError::NotAuthorized)?;
+
+    if !is_moderator(&user.role) {
+        info!("User [{}] is not a moderator", user_id);
+        return Err(HtmlError::NotAuthorized);
+    }
+
+    Ok(user)
+}
+
+pub async fn moderation_comments_page(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    csrf_token: CsrfToken,
+    query: web::Query<ModerationQuery>,
+    config: web::Data<Config>,
+) -> Result<impl Responder, HtmlError> {
+    let moderator = resolve_moderator(&auth_gate, &db_provider).await?;
+
+    info!("Moderator [{}] opens comments in review", moderator.id);
+
+    let (total_pages, db_comments) = db_provider
+        .comments_in_review_pagination(config.comments_on_moderation_page, &query.page)
+        .await?;
+
+    let comments = db_comments
+        .iter()
+        .map(ModerationCommentContext::from_db_comment_and_user)
+        .collect();
+
+    let current_page = query.page.unwrap_or(1);
+    let data_context = ModerationPageDataContext {
+        comments,
+        pagination: Pagination {
+            has_next: current_page < total_pages,
+            has_previous: current_page > 1,
+            current: current_page,
+            total: total_pages,
+        },
+        message_code: query.message.clone(),
+    };
+
+    let cities_names = db_provider.find_city_names().await?;
+    let nav_context = NavContext::new(
+        &auth_gate.user_name.clone().unwrap_or_default(),
+        "",
+        false,
+        &Option::None,
+        &cities_names,
+        &config,
+    )
+    .with_role(&auth_gate)
+    .with_csrf(&csrf_token);
+    let head_context = HeadContext::new(
+        t!("moderation_page_title").to_string().as_str(),
+        t!("moderation_page_description").to_string().as_str(),
+        &config,
+        &Option::None,
+    );
+
+    Ok(HtmlPage::moderation_comments(
+        &head_context,
+        &nav_context,
+        &data_context,
+    ))
+}
+
+pub async fn approve_comment_endpoint(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    form: CsrfForm<ApproveCommentRequest>,
+) -> Result<impl Responder, HtmlError> {
+    let moderator = resolve_moderator(&auth_gate, &db_provider).await?;
+
+    let comment = db_provider
+        .find_comment_in_review_by(&form.id)
+        .await?
+        .ok_or(HtmlError::NotFound)?;
+
+    db_provider
+        .review_comment(&comment, COMMENT_STATUS_APPROVED, None, moderator.id)
+        .await?;
+    info!(
+        "Comment [{}] was approved by moderator [{}]",
+        &comment.id, moderator.id
+    );
+
+    Ok(moderation_page(MSG_COMMENT_APPROVED_CODE))
+}
+
+pub async fn reject_comment_endpoint(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    form_raw: CsrfForm<RejectCommentRequestRaw>,
+) -> Result<impl Responder, HtmlError> {
+    let moderator = resolve_moderator(&auth_gate, &db_provider).await?;
+
+    let form = form_raw.validate().map_err(|error_context| {
+        info!(
+            "Reject comment form includes errors: [{:?}]",
+            &error_context
+        );
+        HtmlError::BadParams
+    })?;
+
+    let comment = db_provider
+        .find_comment_in_review_by(&form.id)
+        .await?
+        .ok_or(HtmlError::NotFound)?;
+
+    db_provider
+        .review_comment(
+            &comment,
+            COMMENT_STATUS_REJECTED,
+            Some(form.reason.trim()),
+            moderator.id,
+        )
+        .await?;
+    info!(
+        "Comment [{}] was rejected by moderator [{}]",
+        &comment.id, moderator.id
+    );
+
+    Ok(moderation_page(MSG_COMMENT_REJECTED_CODE))
+}
+
+fn moderation_page(message: &str) -> HttpResponse {
+    let path = format!("/moderation/comments?message={}", message);
+    HttpResponse::Found()
+        .append_header((LOCATION, path))
+        .finish()
+}
+
+pub struct ModerationPageDataContext {
+    pub comments: Vec<ModerationCommentContext>,
+    pub pagination: Pagination,
+    pub message_code: Option<String>,
+}
+
+pub struct ModerationCommentContext {
+    pub id: Uuid,
+    pub profile_id: Uuid,
+    pub user_name: String,
+    pub user_email: String,
+    pub date_create: String,
+    pub text: String,
+}
+
+impl ModerationCommentContext {
+    fn from_db_comment_and_user(input: &(CommentModel, Option<UserModel>)) -> Self {
+        ModerationCommentContext {
+            id: input.0.id,
+            profile_id: input.0.profile_id,
+            user_name: input
+                .1
+                .as_ref()
+                .map(|user| user.name.clone())
+                .unwrap_or_default(),
+            user_email: input
+                .1
+                .as_ref()
+                .map(|user| user.email.clone())
+                .unwrap_or_default(),
+            date_create: input.0.created_at.format(HOME_DATE_FORMAT).to_string(),
+            text: input.0.text.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ModerationQuery {
+    pub message: Option<String>,
+    pub page: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct ApproveCommentRequest {
+    pub id: Uuid,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for ApproveCommentRequest {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RejectCommentRequestRaw {
+    pub id: Uuid,
+    pub reason: String,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for RejectCommentRequestRaw {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+}
+
+#[derive(Debug)]
+pub struct RejectCommentRequest {
+    pub id: Uuid,
+    pub reason: String,
+}
+
+impl RejectCommentRequest {
+    pub fn from_raw(raw: &RejectCommentRequestRaw) -> Self {
+        RejectCommentRequest {
+            id: raw.id,
+            reason: raw.reason.clone(),
+        }
+    }
+}
@@ -14,10 +14,11 @@ use crate::{
 use log::info;
 use rust_i18n::t;
 
-use super::error::HtmlError;
+use super::{csrf_gate::CsrfToken, error::HtmlError};
 
 pub async fn p404_page(
     auth_gate: AuthenticationGate,
+    csrf_token: CsrfToken,
     config: web::Data<Config>,
     db_provider: web::Data<DbProvider>,
 ) -> Result<impl Responder, HtmlError> {
@@ -36,21 +37,19 @@ pub async fn p404_page(
         Ok(NavContext::new(
             user_name,
             "",
-            &config.captcha_google_id,
             false,
             &Option::None,
             &city_names,
-            &config.oauth_google_client_id,
-            &config.oauth_google_redirect_url,
-        ))
+            config,
+        )
+        .with_role(auth_gate))
     }
 
-    info!(
-        " User auth status: [{}]. 404 page",
-        auth_gate.is_authorized,
-    );
+    info!(" User auth status: [{}]. 404 page", auth_gate.is_authorized,);
 
-    let nav_context = get_nav_context(&auth_gate, &config, &db_provider).await?;
+    let nav_context = get_nav_context(&auth_gate, &config, &db_provider)
+        .await?
+        .with_csrf(&csrf_token);
     let head_context = HeadContext::new(
         t!("404_page_title").to_string().as_str(),
         t!("404_page_description").to_string().as_str(),
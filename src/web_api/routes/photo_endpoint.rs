@@ -0,0 +1,79 @@
+use actix_web::{
+    body::SizedStream,
+    http::header::{ACCEPT, ETAG, IF_NONE_MATCH, VARY},
+    web, HttpRequest, HttpResponse, Responder,
+};
+use image::ImageFormat;
+use log::info;
+use uuid::Uuid;
+
+use crate::web_api::photo::{photo_key, PhotoFormat, PhotoStorage, DELETED_PHOTO_PREFIX};
+
+use super::error::HtmlError;
+
+/// Serves the best format the browser accepts. Links always point to the jpeg file
+pub async fn photo_endpoint(
+    req: HttpRequest,
+    path: web::Path<(Uuid, String)>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+) -> Result<impl Responder, HtmlError> {
+    let (profile_id, file_name) = path.into_inner();
+    if file_name.starts_with(DELETED_PHOTO_PREFIX) || file_name.starts_with('.') {
+        return Err(HtmlError::NotFound);
+    }
+
+    let accept = req
+        .headers()
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    // photos uploaded before formats exist only under the requested name
+    let candidates: Vec<(String, &str)> = match PhotoFormat::from_file_name(&file_name) {
+        Some(PhotoFormat::Jpeg) => PhotoFormat::negotiate(accept)
+            .into_iter()
+            .map(|format| (format.file_name_from(&file_name), format.mime_type()))
+            .collect(),
+        _ => vec![(
+            file_name.clone(),
+            ImageFormat::from_path(&file_name)
+                .map(|format| format.to_mime_type())
+                .unwrap_or("application/octet-stream"),
+        )],
+    };
+
+    let if_none_match = req
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    for (candidate_name, mime_type) in candidates {
+        let key = photo_key(&profile_id, &candidate_name);
+        // every upload gets new file names and a stored file never changes,
+        // so the name is the version of the content
+        let etag = format!("\"{}\"", &candidate_name);
+        if if_none_match.contains(&etag) {
+            if photo_storage.size(&key).await?.is_none() {
+                continue;
+            }
+            return Ok(HttpResponse::NotModified()
+                .insert_header((ETAG, etag))
+                .insert_header((VARY, "Accept"))
+                .finish());
+        }
+
+        let Some((size, content)) = photo_storage.stream(&key).await? else {
+            continue;
+        };
+        return Ok(HttpResponse::Ok()
+            .content_type(mime_type)
+            .insert_header((ETAG, etag))
+            .insert_header((VARY, "Accept"))
+            .body(SizedStream::new(size, content)));
+    }
+
+    info!(
+        "Photo [{}] of profile [{}] not found",
+        &file_name, &profile_id
+    );
+    Err(HtmlError::NotFound)
+}
@@ -1,12 +1,16 @@
-use crate::web_api::photo::PhotoService;
-use crate::web_api::routes::constant::MAX_PROFILE_PHOTOS;
+use crate::config::Config;
+use crate::web_api::jobs::JOB_KIND_PROCESS_PHOTO;
+use crate::web_api::photo::{validate_upload, OriginalPhotoStorage, PhotoService, PhotoStorage};
 use crate::web_api::routes::constant::MSG_COMMENT_REMOVED_CODE;
+use crate::web_api::routes::csrf_gate::{CsrfForm, CsrfHeader, CsrfTokenField};
 use crate::web_api::routes::error::HtmlError;
 use crate::web_api::routes::error::JsonError;
 use crate::{
-    config::Config,
     db::{DbProvider, ProfileModel, ProfilePhotoModel},
-    web_api::{auth::AuthenticationGate, routes::common::AddProfilePhotoContext},
+    web_api::{
+        auth::AuthenticationGate,
+        routes::common::{get_preview_photo_url, AddProfilePhotoContext},
+    },
 };
 use actix_multipart::form::tempfile::TempFile;
 use actix_multipart::form::text::Text;
@@ -15,14 +19,15 @@ use actix_web::http::header::LOCATION;
 use actix_web::http::StatusCode;
 use actix_web::{web, HttpResponse, Responder};
 use futures::future;
-use log::info;
+use image::ImageFormat;
+use log::{error, info};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 pub async fn delete_comment_endpoint(
     db_provider: web::Data<DbProvider>,
     auth_gate: AuthenticationGate,
-    form: web::Form<DeleteCommentRequest>,
+    form: CsrfForm<DeleteCommentRequest>,
 ) -> Result<impl Responder, HtmlError> {
     if !auth_gate.is_authorized {
         return Err(HtmlError::NotAuthorized);
@@ -57,8 +62,8 @@ pub async fn delete_comment_endpoint(
 pub async fn delete_profile_endpoint(
     db_provider: web::Data<DbProvider>,
     auth_gate: AuthenticationGate,
-    form: web::Form<DeleteProfileRequest>,
-    config: web::Data<Config>,
+    form: CsrfForm<DeleteProfileRequest>,
+    photo_storage: web::Data<dyn PhotoStorage>,
 ) -> Result<impl Responder, HtmlError> {
     if !auth_gate.is_authorized {
         return Err(HtmlError::NotAuthorized);
@@ -75,7 +80,9 @@ pub async fn delete_profile_endpoint(
         .find_active_profile_by_id_and_user_id(&profile_id, auth_gate.user_id.unwrap())
         .await?;
     let profile = profile_opt.ok_or(HtmlError::NotFound)?;
-    let profile_photos = db_provider.find_all_profile_photos_for(&profile.id).await?;
+    let profile_photos = db_provider
+        .find_all_uploaded_profile_photos_for(&profile.id)
+        .await?;
 
     info!("Deleting profile: [{}]. Starting IO", &profile_id);
 
@@ -83,49 +90,53 @@ pub async fn delete_profile_endpoint(
         .delete_profile_and_photos(&profile, &profile_photos)
         .await?;
 
-    PhotoService::delete_profile_from_fs(&config.all_photos_folder_name, &profile_id)?;
+    PhotoService::delete_profile(photo_storage.as_ref(), &profile_id, &profile_photos).await?;
 
     Ok(HttpResponse::build(StatusCode::FOUND)
         .append_header((LOCATION, "/?show_my=true"))
         .finish())
 }
 
+// photos of a new profile are attached to the user draft
+async fn resolve_profile(
+    user_id: i64,
+    profile_id_opt: &Option<Uuid>,
+    db_provider: &web::Data<DbProvider>,
+) -> Result<ProfileModel, JsonError> {
+    if profile_id_opt.is_some() {
+        info!("Edit flow. Searching active profile");
+        let profile_id = profile_id_opt.unwrap_or_default();
+        db_provider
+            .find_active_profile_by_id_and_user_id(&profile_id, user_id)
+            .await?
+            .ok_or(JsonError::BadParams)
+    } else {
+        let draft_profile_opt = db_provider.find_draft_profile_for(user_id).await?;
+        match draft_profile_opt {
+            Some(draft_profile) => {
+                info!("Draft profile flow. Found draft profile. Re-useing");
+                Ok(draft_profile)
+            }
+            None => {
+                info!("Draft profile flow. Creating new draft profile");
+                db_provider
+                    .add_draft_profile_for(user_id)
+                    .await
+                    .map_err(|op| op.into())
+            }
+        }
+    }
+}
+
 pub async fn add_profile_photo_endpoint(
     db_provider: web::Data<DbProvider>,
     auth_gate: AuthenticationGate,
+    photo_storage: web::Data<dyn PhotoStorage>,
+    original_photo_storage: web::Data<OriginalPhotoStorage>,
     config: web::Data<Config>,
+    _csrf: CsrfHeader,
     form: MultipartForm<AddProfilePhotoMultipartRequest>,
 ) -> Result<impl Responder, JsonError> {
-    async fn resolve_profile(
-        user_id: i64,
-        profile_id_opt: &Option<Uuid>,
-        db_provider: &web::Data<DbProvider>,
-    ) -> Result<ProfileModel, JsonError> {
-        if profile_id_opt.is_some() {
-            info!("Edit flow. Searching active profile");
-            let profile_id = profile_id_opt.unwrap_or_default();
-            db_provider
-                .find_active_profile_by_id_and_user_id(&profile_id, user_id)
-                .await?
-                .ok_or(JsonError::BadParams)
-        } else {
-            let draft_profile_opt = db_provider.find_draft_profile_for(user_id).await?;
-            match draft_profile_opt {
-                Some(draft_profile) => {
-                    info!("Draft profile flow. Found draft profile. Re-useing");
-                    Ok(draft_profile)
-                }
-                None => {
-                    info!("Draft profile flow. Creating new draft profile");
-                    db_provider
-                        .add_draft_profile_for(user_id)
-                        .await
-                        .map_err(|op| op.into())
-                }
-            }
-        }
-    }
-
     if !auth_gate.is_authorized {
         return Err(JsonError::NotAuthorized);
     }
@@ -140,72 +151,166 @@ pub async fn add_profile_photo_endpoint(
     let profile_id_opt = form.0.profile_id.map(|f| f.0);
     let profile = resolve_profile(user_id, &profile_id_opt, &db_provider).await?;
     let profile_photos = db_provider.count_profile_photos(&profile.id).await?;
-    if profile_photos > MAX_PROFILE_PHOTOS.to_owned() {
+    if profile_photos > config.max_profile_photos {
         return Err(JsonError::BadParams);
     }
 
+    // renditions are made by a job worker, the request only keeps the original
     async fn process_image(
         new_profile_photo: &TempFile,
-        config: &Config,
+        image_format: ImageFormat,
+        photo_storage: &dyn PhotoStorage,
+        original_photo_storage: &OriginalPhotoStorage,
         profile_id: &Uuid,
         db_provider: &web::Data<DbProvider>,
+        job_max_attempts: i32,
     ) -> Result<ProfilePhotoModel, JsonError> {
-        let photo_fs_save_result = PhotoService::save_photo_on_fs(
+        let stored_photo = PhotoService::save_original(
+            original_photo_storage,
             new_profile_photo,
-            &config.all_photos_folder_name,
+            image_format,
             profile_id,
-        )?;
+        )
+        .await?;
 
         info!(
-            "Photo saved into fs with name: [{:?}]",
-            &photo_fs_save_result
+            "Original saved into storage with name: [{:?}]",
+            &stored_photo
         );
         let save_result = db_provider
             .add_profile_photo(
                 profile_id,
-                &photo_fs_save_result.name.as_str(),
-                photo_fs_save_result.size,
+                &stored_photo.name,
+                stored_photo.size,
+                &stored_photo.thumbnail_name,
+                &stored_photo.card_name,
+                &stored_photo.original_name,
+                JOB_KIND_PROCESS_PHOTO,
+                job_max_attempts,
             )
             .await;
-
-        info!("Photo saved into database with id: [{:?}]", &save_result);
-        save_result.map_err(|err| err.into())
+        let (profile_photo, job) = match save_result {
+            Ok(saved) => saved,
+            Err(err) => {
+                // files without db row are never shown, no need to keep them
+                PhotoService::discard_photo(
+                    photo_storage,
+                    original_photo_storage,
+                    profile_id,
+                    &stored_photo,
+                )
+                .await?;
+                return Err(err.into());
+            }
+        };
+        info!(
+            "Photo saved into database with id: [{}], queued as job [{}]",
+            profile_photo.id, job.id
+        );
+        Ok(profile_photo)
     }
 
-    let db_photos =
-        future::try_join_all(form.0.new_profile_photos.iter().map(|new_profile_photo| {
-            process_image(new_profile_photo, &config, &profile.id, &db_provider)
-        }))
-        .await
-        .unwrap();
+    // whole batch is rejected before anything is saved
+    let image_formats = form
+        .0
+        .new_profile_photos
+        .iter()
+        .map(|new_profile_photo| {
+            validate_upload(
+                new_profile_photo,
+                config.photo_max_bytes,
+                config.photo_max_megapixels,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let results = future::join_all(form.0.new_profile_photos.iter().zip(image_formats).map(
+        |(new_profile_photo, image_format)| {
+            process_image(
+                new_profile_photo,
+                image_format,
+                photo_storage.as_ref(),
+                &original_photo_storage,
+                &profile.id,
+                &db_provider,
+                config.job_max_attempts,
+            )
+        },
+    ))
+    .await;
+
+    // one broken file does not throw away the rest of the batch
+    let mut db_photos = vec![];
+    let mut last_error_opt = None;
+    for result in results {
+        match result {
+            Ok(db_photo) => db_photos.push(db_photo),
+            Err(err) => {
+                error!("Photo was not uploaded: [{}]", &err);
+                last_error_opt = Some(err);
+            }
+        }
+    }
+    if let (true, Some(err)) = (db_photos.is_empty(), last_error_opt) {
+        return Err(err);
+    }
 
-    let response =
-        AddProfilePhotoContext::new_with_payload(&config.all_photos_folder_name, &db_photos);
+    let response = AddProfilePhotoContext::new_with_payload(photo_storage.as_ref(), &db_photos);
     Ok(web::Json(response))
 }
 
+/// Upload page asks until every photo leaves the `processing` status
+pub async fn profile_photo_status_endpoint(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    query: web::Query<ProfilePhotoStatusQuery>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+) -> Result<impl Responder, JsonError> {
+    if !auth_gate.is_authorized {
+        return Err(JsonError::NotAuthorized);
+    }
+
+    let profile_photo_ids = query
+        .keys
+        .split(',')
+        .map(|key| key.trim().parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| JsonError::BadParams)?;
+    let profile_photos = db_provider
+        .find_profile_photos_by_ids_and_user_id(&profile_photo_ids, auth_gate.user_id.unwrap())
+        .await?;
+
+    Ok(web::Json(ProfilePhotoStatusJsonResponse {
+        photos: profile_photos
+            .iter()
+            .map(|profile_photo| ProfilePhotoStatusResponse {
+                key: profile_photo.id,
+                status: profile_photo.status.clone(),
+                url: get_preview_photo_url(profile_photo, photo_storage.as_ref()),
+            })
+            .collect(),
+    }))
+}
+
 pub async fn delete_profile_photo_endpoint(
     db_provider: web::Data<DbProvider>,
     auth_gate: AuthenticationGate,
+    _csrf: CsrfHeader,
     form: web::Form<DeleteProfilePhotoFormRequest>,
-    config: web::Data<Config>,
+    photo_storage: web::Data<dyn PhotoStorage>,
 ) -> Result<impl Responder, JsonError> {
     async fn process_deleting(
-        profile_id: &Uuid,
         profile_photo: &ProfilePhotoModel,
         db_provider: &web::Data<DbProvider>,
-        config: &web::Data<Config>,
+        photo_storage: &dyn PhotoStorage,
     ) -> Result<(), JsonError> {
         db_provider
             .update_profile_photo_with_delete_status(profile_photo)
             .await?;
 
-        PhotoService::delete_photo_from_fs(
-            &config.all_photos_folder_name,
-            &profile_id,
-            &profile_photo.file_name,
-        )
-        .map_err(|_| JsonError::BadParams)
+        PhotoService::delete_photo(photo_storage, profile_photo)
+            .await
+            .map_err(|_| JsonError::BadParams)
     }
 
     if !auth_gate.is_authorized {
@@ -222,11 +327,11 @@ pub async fn delete_profile_photo_endpoint(
 
     let profile_photo_id: i64 = form.0.key.parse().unwrap();
     let profile_photo_profile_opt = db_provider
-        .find_active_profile_photo_with_profile_by_id_and_user_id(profile_photo_id, user_id)
+        .find_uploaded_profile_photo_with_profile_by_id_and_user_id(profile_photo_id, user_id)
         .await?;
-    let (profile_photo, profile) = profile_photo_profile_opt.ok_or(JsonError::BadParams)?;
+    let (profile_photo, _) = profile_photo_profile_opt.ok_or(JsonError::BadParams)?;
 
-    process_deleting(&profile.id, &profile_photo, &db_provider, &config)
+    process_deleting(&profile_photo, &db_provider, photo_storage.as_ref())
         .await
         .map(|_| {
             info!("IO actions were done. Deleted: OK!");
@@ -234,6 +339,61 @@ pub async fn delete_profile_photo_endpoint(
         })
 }
 
+/// Drag-and-drop on the edit page sends photo keys in the new order
+pub async fn reorder_profile_photos_endpoint(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    _csrf: CsrfHeader,
+    body: web::Json<ReorderProfilePhotosRequest>,
+) -> Result<impl Responder, JsonError> {
+    if !auth_gate.is_authorized {
+        return Err(JsonError::NotAuthorized);
+    }
+
+    let user_id = auth_gate.user_id.unwrap();
+    let profile = resolve_profile(user_id, &body.profile_id, &db_provider).await?;
+    let profile_photos = db_provider
+        .find_all_uploaded_profile_photos_for(&profile.id)
+        .await?;
+
+    // photos may still be uploading while the user drags, unlisted ones go after the listed
+    let mut requested_ids = body.keys.clone();
+    requested_ids.sort_unstable();
+    requested_ids.dedup();
+    let is_every_key_known = requested_ids.len() == body.keys.len()
+        && requested_ids
+            .iter()
+            .all(|id| profile_photos.iter().any(|photo| photo.id == *id));
+    if !is_every_key_known {
+        info!(
+            "Photo keys [{:?}] do not match profile [{}] photos",
+            &body.keys, &profile.id
+        );
+        return Err(JsonError::BadParams);
+    }
+    let ordered_ids: Vec<i64> = body
+        .keys
+        .iter()
+        .copied()
+        .chain(
+            profile_photos
+                .iter()
+                .map(|photo| photo.id)
+                .filter(|id| !body.keys.contains(id)),
+        )
+        .collect();
+
+    db_provider
+        .reorder_profile_photos(&profile.id, &ordered_ids)
+        .await?;
+    info!(
+        "Photos of profile [{}] reordered: [{:?}]",
+        &profile.id, &ordered_ids
+    );
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
 #[derive(MultipartForm)]
 pub struct AddProfilePhotoMultipartRequest {
     pub new_profile_photos: Vec<TempFile>,
@@ -241,14 +401,55 @@ pub struct AddProfilePhotoMultipartRequest {
     pub profile_id: Option<Text<Uuid>>,
 }
 
+#[derive(Deserialize)]
+pub struct ReorderProfilePhotosRequest {
+    // it means edit mode
+    pub profile_id: Option<Uuid>,
+    pub keys: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ProfilePhotoStatusQuery {
+    // comma separated profile photo ids
+    pub keys: String,
+}
+
+#[derive(Serialize)]
+pub struct ProfilePhotoStatusJsonResponse {
+    pub photos: Vec<ProfilePhotoStatusResponse>,
+}
+
+#[derive(Serialize)]
+pub struct ProfilePhotoStatusResponse {
+    pub key: i64,
+    pub status: String,
+    pub url: String,
+}
+
 #[derive(Deserialize)]
 pub struct DeleteProfileRequest {
     pub id: Uuid,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for DeleteProfileRequest {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
 }
 
 #[derive(Deserialize)]
 pub struct DeleteCommentRequest {
     pub id: Uuid,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for DeleteCommentRequest {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
 }
 
 #[derive(Deserialize)]
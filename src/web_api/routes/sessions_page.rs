@@ -0,0 +1,179 @@
+use actix_web::{http::header::LOCATION, web, HttpResponse, Responder};
+use log::info;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    db::{DbProvider, SessionModel},
+    web_api::{
+        auth::{AuthSessionManager, AuthenticationGate},
+        routes::{
+            authorization_endpoint::homepage,
+            common::{HeadContext, NavContext},
+            constant::{
+                MSG_ALL_SESSIONS_REVOKED_CODE, MSG_SESSION_REVOKED_CODE, MSG_SIGN_OUT_CODE,
+                SESSION_DATE_FORMAT,
+            },
+            html_render::HtmlPage,
+        },
+    },
+};
+use rust_i18n::t;
+
+use super::{
+    csrf_gate::{CsrfForm, CsrfToken, CsrfTokenField},
+    error::HtmlError,
+};
+
+pub async fn sessions_page(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    csrf_token: CsrfToken,
+    query: web::Query<SessionsQuery>,
+    config: web::Data<Config>,
+) -> Result<impl Responder, HtmlError> {
+    if !auth_gate.is_authorized {
+        return Err(HtmlError::NotAuthorized);
+    }
+
+    let db_sessions = db_provider
+        .all_active_user_sessions(auth_gate.user_id.unwrap())
+        .await?;
+    let data_context = SessionsPageDataContext {
+        sessions: db_sessions
+            .iter()
+            .map(|session| SessionContext::from_db_session(session, &auth_gate.session_id))
+            .collect(),
+        message_code: query.message.clone(),
+    };
+
+    let cities_names = db_provider.find_city_names().await?;
+    let nav_context = NavContext::new(
+        &auth_gate.user_name.clone().unwrap_or_default(),
+        "",
+        false,
+        &Option::None,
+        &cities_names,
+        &config,
+    )
+    .with_role(&auth_gate)
+    .with_csrf(&csrf_token);
+    let head_context = HeadContext::new(
+        t!("sessions_page_title").to_string().as_str(),
+        t!("sessions_page_description").to_string().as_str(),
+        &config,
+        &Option::None,
+    );
+
+    Ok(HtmlPage::sessions(
+        &head_context,
+        &nav_context,
+        &data_context,
+    ))
+}
+
+pub async fn revoke_session_endpoint(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    form: CsrfForm<RevokeSessionRequest>,
+) -> Result<impl Responder, HtmlError> {
+    if !auth_gate.is_authorized {
+        return Err(HtmlError::NotAuthorized);
+    }
+
+    let user_id = auth_gate.user_id.unwrap();
+    let session = db_provider
+        .find_active_session(&form.id, user_id)
+        .await?
+        .ok_or(HtmlError::NotFound)?;
+    db_provider.revoke_session(&session).await?;
+    info!("User [{}] revoked session [{}]", user_id, &session.id);
+
+    if auth_gate.session_id == Some(session.id) {
+        let empty_cookie = AuthSessionManager::get_empty_jwt_token();
+        return Ok(homepage(Some(empty_cookie), MSG_SIGN_OUT_CODE));
+    }
+    Ok(sessions_page_redirect(MSG_SESSION_REVOKED_CODE))
+}
+
+/// Log out everywhere, current session included
+pub async fn revoke_all_sessions_endpoint(
+    db_provider: web::Data<DbProvider>,
+    auth_gate: AuthenticationGate,
+    _form: CsrfForm<RevokeAllSessionsRequest>,
+) -> Result<impl Responder, HtmlError> {
+    if !auth_gate.is_authorized {
+        return Err(HtmlError::NotAuthorized);
+    }
+
+    let user_id = auth_gate.user_id.unwrap();
+    let revoked = db_provider.revoke_all_user_sessions(user_id).await?;
+    info!("User [{}] revoked all [{}] sessions", user_id, revoked);
+
+    let empty_cookie = AuthSessionManager::get_empty_jwt_token();
+    Ok(homepage(Some(empty_cookie), MSG_ALL_SESSIONS_REVOKED_CODE))
+}
+
+fn sessions_page_redirect(message: &str) -> HttpResponse {
+    let path = format!("/sessions?message={}", message);
+    HttpResponse::Found()
+        .append_header((LOCATION, path))
+        .finish()
+}
+
+pub struct SessionsPageDataContext {
+    pub sessions: Vec<SessionContext>,
+    pub message_code: Option<String>,
+}
+
+pub struct SessionContext {
+    pub id: Uuid,
+    pub user_agent: String,
+    pub ip_address: String,
+    pub date_create: String,
+    pub is_current: bool,
+}
+
+impl SessionContext {
+    fn from_db_session(session: &SessionModel, current_session_id: &Option<Uuid>) -> Self {
+        SessionContext {
+            id: session.id,
+            user_agent: session.user_agent.clone().unwrap_or_default(),
+            ip_address: session.ip_address.clone().unwrap_or_default(),
+            date_create: session.created_at.format(SESSION_DATE_FORMAT).to_string(),
+            is_current: current_session_id == &Some(session.id),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SessionsQuery {
+    pub message: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RevokeSessionRequest {
+    pub id: Uuid,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for RevokeSessionRequest {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+}
+
+/// The form has no fields but the csrf token
+#[derive(Deserialize)]
+pub struct RevokeAllSessionsRequest {
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for RevokeAllSessionsRequest {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
+}
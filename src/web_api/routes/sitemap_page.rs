@@ -39,7 +39,6 @@ pub async fn sitemap(
     config: web::Data<Config>,
     db_provider: web::Data<DbProvider>,
 ) -> Result<impl Responder, HtmlError> {
-
     let index = get_absolute_url(&config, "/");
 
     // cities
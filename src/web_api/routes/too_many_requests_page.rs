@@ -0,0 +1,50 @@
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+
+use crate::{
+    config::Config,
+    db::DbProvider,
+    web_api::{
+        auth::AuthenticationGate,
+        routes::{
+            common::{HeadContext, NavContext},
+            csrf_gate::CsrfToken,
+            html_render::HtmlPage,
+        },
+    },
+};
+use log::error;
+use rust_i18n::t;
+
+/// Shown by the rate limiter instead of the page or form result. Not a route
+pub async fn too_many_requests_page(
+    request: &HttpRequest,
+    retry_after_seconds: u64,
+) -> HttpResponse {
+    let config = request.app_data::<web::Data<Config>>().unwrap();
+    let db_provider = request.app_data::<web::Data<DbProvider>>().unwrap();
+    let auth_gate = AuthenticationGate::extract(request)
+        .await
+        .unwrap_or_else(|_| AuthenticationGate::empty());
+    let csrf_token = CsrfToken::extract(request)
+        .await
+        .unwrap_or_else(|_| CsrfToken(String::new()));
+
+    // the page still renders when the menu can not be loaded
+    let city_names = db_provider
+        .find_city_names()
+        .await
+        .inspect_err(|err| error!("[DbErr] city names for the 429 page: [{}]", err))
+        .unwrap_or_default();
+    let user_name = auth_gate.user_name.as_deref().unwrap_or_default();
+    let nav_context = NavContext::new(user_name, "", false, &Option::None, &city_names, config)
+        .with_role(&auth_gate)
+        .with_csrf(&csrf_token);
+    let head_context = HeadContext::new(
+        t!("too_many_requests_page_title").to_string().as_str(),
+        t!("404_page_description").to_string().as_str(),
+        config,
+        &Option::None,
+    );
+
+    HtmlPage::too_many_requests(&head_context, &nav_context, retry_after_seconds)
+}
@@ -1,6 +1,10 @@
 use std::collections::HashMap;
 
-use super::{add_profile_page::{AddOrEditProfileFormRequest, AddOrEditProfileFormRequestRaw}, view_profile_page::{AddCommentFormRequest, AddCommentFormRequestRaw}};
+use super::{
+    add_profile_page::{AddOrEditProfileFormRequest, AddOrEditProfileFormRequestRaw},
+    moderation_page::{RejectCommentRequest, RejectCommentRequestRaw},
+    view_profile_page::{AddCommentFormRequest, AddCommentFormRequestRaw},
+};
 
 type Key = String;
 type Code = String;
@@ -60,16 +64,30 @@ impl Validator<AddCommentFormRequest> for AddCommentFormRequestRaw {
         let mut err_context = ErrorContext::empty();
 
         err_context.if_true_add_error(self.is_empty(|f| &f.profile_id), "profile_id", "is_empty");
-        err_context.if_true_add_error(self.is_empty(|f| &f.captcha_token), "captcha_token", "is_empty");
         err_context.if_true_add_error(self.is_empty(|f| &f.text), "text", "is_empty");
+        err_context.if_true_add_error(self.has_not_length(|f| &f.text, 10, 200), "text", "length");
+
+        if err_context.is_empty() {
+            Ok(AddCommentFormRequest::from_raw(self))
+        } else {
+            Err(err_context)
+        }
+    }
+}
+
+impl Validator<RejectCommentRequest> for RejectCommentRequestRaw {
+    fn validate(&self) -> Result<RejectCommentRequest, ErrorContext> {
+        let mut err_context = ErrorContext::empty();
+
+        err_context.if_true_add_error(self.is_empty(|f| &f.reason), "reason", "is_empty");
         err_context.if_true_add_error(
-            self.has_not_length(|f| &f.text, 10, 200),
-            "text",
+            self.has_not_length(|f| &f.reason, 3, 200),
+            "reason",
             "length",
         );
 
         if err_context.is_empty() {
-            Ok(AddCommentFormRequest::from_raw(self))
+            Ok(RejectCommentRequest::from_raw(self))
         } else {
             Err(err_context)
         }
@@ -123,12 +141,7 @@ impl Validator<AddOrEditProfileFormRequest> for AddOrEditProfileFormRequestRaw {
             "length",
         );
 
-        // captcha
-        err_context.if_true_add_error(
-            self.is_empty(|f| &f.captcha_token),
-            "captcha_token",
-            "is_empty",
-        );
+        // captcha token is left to the captcha check, it is empty when captcha is off
 
         if err_context.is_empty() {
             Ok(AddOrEditProfileFormRequest::from_raw(self))
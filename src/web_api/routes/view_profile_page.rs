@@ -5,7 +5,7 @@ use awc::http::header::LOCATION;
 use chrono::Utc;
 use futures::future::OptionFuture;
 use log::{error, info};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
@@ -13,10 +13,14 @@ use crate::{
     db::{CommentModel, DbProvider, UserModel},
     web_api::{
         auth::AuthenticationGate,
-        recaptcha::Recaptcha,
+        captcha::Captcha,
+        moderation::{CommentModeration, COMMENT_STATUS_IN_REVIEW},
+        photo::{PhotoRendition, PhotoStorage},
         routes::{
-            common::{get_relative_photo_url, HeadContext, NavContext},
-            constant::{HOME_DATE_FORMAT, MSG_COMMENT_ADDED_CODE, NO_PHOTO_URL},
+            common::{get_photo_url, HeadContext, NavContext},
+            constant::{
+                HOME_DATE_FORMAT, MSG_COMMENT_ADDED_CODE, MSG_COMMENT_IN_REVIEW_CODE, NO_PHOTO_URL,
+            },
             html_render::HtmlPage,
             validator::{ErrorContext, Validator},
         },
@@ -24,13 +28,17 @@ use crate::{
 };
 use rust_i18n::t;
 
-use super::{bot_detector_gate::BotDetector, error::HtmlError};
+use super::{
+    bot_detector_gate::BotDetector,
+    csrf_gate::{CsrfForm, CsrfToken, CsrfTokenField},
+    error::HtmlError,
+};
 
 async fn resolve_view_profile_data_context(
     profile_id: &Uuid,
     message_code: &Option<String>,
     db_provider: &web::Data<DbProvider>,
-    config: &web::Data<Config>,
+    photo_storage: &dyn PhotoStorage,
     auth_gate: &AuthenticationGate,
     bot_detector: &BotDetector,
     skip_view_counter_increase: bool,
@@ -41,7 +49,7 @@ async fn resolve_view_profile_data_context(
 
     let photo_urls: Vec<String> = profile_photos
         .iter()
-        .map(|profile_photo| get_relative_photo_url(profile_photo, &config.all_photos_folder_name))
+        .map(|profile_photo| get_photo_url(profile_photo, PhotoRendition::Full, photo_storage))
         .collect();
     let photo_urls_or_placeholder = match photo_urls.is_empty() {
         true => vec![NO_PHOTO_URL.to_string()],
@@ -122,13 +130,12 @@ async fn resolve_nav_context(
     Ok(NavContext::new(
         name,
         "",
-        &config.captcha_google_id,
         false,
         &Option::None,
         &cities_names,
-        &config.oauth_google_client_id,
-        &config.oauth_google_redirect_url,
-    ))
+        config,
+    )
+    .with_role(auth_gate))
 }
 
 async fn resolve_head_context(
@@ -136,6 +143,7 @@ async fn resolve_head_context(
     db_provider: &web::Data<DbProvider>,
     profile_id: &Uuid,
     config: &web::Data<Config>,
+    photo_storage: &dyn PhotoStorage,
 ) -> Result<HeadContext, HtmlError> {
     let page_title = format!(
         "{} {} – 0{}",
@@ -149,15 +157,21 @@ async fn resolve_head_context(
         &page_title,
         &page_description,
         &config,
-        &profile_photos.first().cloned(),
+        &profile_photos
+            .first()
+            .map(|photo| get_photo_url(photo, PhotoRendition::Full, photo_storage)),
     ))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn add_comment(
     db_provider: web::Data<DbProvider>,
     auth_gate: AuthenticationGate,
+    csrf_token: CsrfToken,
     config: web::Data<Config>,
-    form_raw: web::Form<AddCommentFormRequestRaw>,
+    captcha: web::Data<Captcha>,
+    photo_storage: web::Data<dyn PhotoStorage>,
+    form_raw: CsrfForm<AddCommentFormRequestRaw>,
     bot_detector: BotDetector,
 ) -> Result<impl Responder, HtmlError> {
     info!(
@@ -197,22 +211,31 @@ pub async fn add_comment(
             user_name: auth_gate.user_name.clone().unwrap_or_default(),
             id: Uuid::new_v4(),
             is_draft: true,
+            is_in_review: false,
         };
 
         let mut data_context = resolve_view_profile_data_context(
             &profile_id,
             &None,
             &db_provider,
-            &config,
+            photo_storage.as_ref(),
             &auth_gate,
             &bot_detector,
             true,
         )
         .await?;
         data_context.user_comment = Some(temporary_comment);
-        let head_context =
-            resolve_head_context(&data_context, &db_provider, &profile_id, &config).await?;
-        let nav_context = resolve_nav_context(&db_provider, &auth_gate, &config).await?;
+        let head_context = resolve_head_context(
+            &data_context,
+            &db_provider,
+            &profile_id,
+            &config,
+            photo_storage.as_ref(),
+        )
+        .await?;
+        let nav_context = resolve_nav_context(&db_provider, &auth_gate, &config)
+            .await?
+            .with_csrf(&csrf_token);
 
         return Ok(HtmlPage::view_profile(
             &head_context,
@@ -224,21 +247,27 @@ pub async fn add_comment(
         form_validation.unwrap()
     };
 
-    let captcha_score =
-        Recaptcha::verify(&config.captcha_google_secret, &form.captcha_token).await?;
-    if captcha_score < config.captcha_google_score {
-        error!("Google captcha score is low [{}]", captcha_score);
+    if !captcha.is_human(&form.captcha_token).await? {
         return Err(HtmlError::BotDetection);
     }
 
+    let comment_status = CommentModeration::new(&config).initial_status(&form.text);
     let new_db_comment = db_provider
-        .add_comment(&profile_id, user_id, &form.text)
+        .add_comment(&profile_id, user_id, &form.text, comment_status)
         .await?;
-    info!("New comment was added: [{:?}]", &new_db_comment);
+    info!(
+        "New comment [{}] was added to profile [{}] as [{}]",
+        &new_db_comment.id, &new_db_comment.profile_id, &new_db_comment.status
+    );
 
+    let message_code = if new_db_comment.status == COMMENT_STATUS_IN_REVIEW {
+        MSG_COMMENT_IN_REVIEW_CODE
+    } else {
+        MSG_COMMENT_ADDED_CODE
+    };
     let redirect_to_view_page = format!(
         "/view_profile?id={}&message_code={}",
-        &profile_id, MSG_COMMENT_ADDED_CODE
+        &profile_id, message_code
     );
 
     Ok(HttpResponse::Found()
@@ -249,8 +278,10 @@ pub async fn add_comment(
 pub async fn view_profile_page(
     db_provider: web::Data<DbProvider>,
     auth_gate: AuthenticationGate,
+    csrf_token: CsrfToken,
     bot_detector: BotDetector,
     config: web::Data<Config>,
+    photo_storage: web::Data<dyn PhotoStorage>,
     query: web::Query<ViewProfileQuery>,
 ) -> Result<impl Responder, HtmlError> {
     info!(
@@ -261,20 +292,28 @@ pub async fn view_profile_page(
         bot_detector.is_bot
     );
 
-    let nav_context = resolve_nav_context(&db_provider, &auth_gate, &config).await?;
+    let nav_context = resolve_nav_context(&db_provider, &auth_gate, &config)
+        .await?
+        .with_csrf(&csrf_token);
     let data_context = resolve_view_profile_data_context(
         &query.id,
         &query.message_code,
         &db_provider,
-        &config,
+        photo_storage.as_ref(),
         &auth_gate,
         &bot_detector,
         false,
     )
     .await?;
 
-    let head_context =
-        resolve_head_context(&data_context, &db_provider, &query.id, &config).await?;
+    let head_context = resolve_head_context(
+        &data_context,
+        &db_provider,
+        &query.id,
+        &config,
+        photo_storage.as_ref(),
+    )
+    .await?;
 
     Ok(HtmlPage::view_profile(
         &head_context,
@@ -306,12 +345,14 @@ pub struct ViewProfilePageDataContext {
     pub message_code: Option<String>,
 }
 
+#[derive(Serialize)]
 pub struct ProfileCommentResponse {
     pub id: Uuid,
     pub user_name: String,
     pub date_create: String,
     pub text: String,
     pub is_draft: bool,
+    pub is_in_review: bool,
 }
 
 impl ProfileCommentResponse {
@@ -326,6 +367,7 @@ impl ProfileCommentResponse {
             date_create: input.0.created_at.format(HOME_DATE_FORMAT).to_string(),
             text: input.0.text.clone(),
             is_draft: false,
+            is_in_review: input.0.status == COMMENT_STATUS_IN_REVIEW,
         }
     }
 
@@ -336,6 +378,7 @@ impl ProfileCommentResponse {
             date_create: input.created_at.format(HOME_DATE_FORMAT).to_string(),
             text: input.text.clone(),
             is_draft: false,
+            is_in_review: input.status == COMMENT_STATUS_IN_REVIEW,
         }
     }
 }
@@ -345,6 +388,14 @@ pub struct AddCommentFormRequestRaw {
     pub profile_id: String,
     pub text: String,
     pub captcha_token: String,
+    #[serde(default)]
+    pub csrf_token: String,
+}
+
+impl CsrfTokenField for AddCommentFormRequestRaw {
+    fn csrf_token(&self) -> &str {
+        &self.csrf_token
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,356 @@
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
+    Error,
+};
+use futures::future::LocalBoxFuture;
+use log::warn;
+use rand::{thread_rng, RngCore};
+
+use crate::config::Config;
+
+pub static CSP_MODE_ENFORCE: &str = "enforce";
+pub static CSP_MODE_REPORT_ONLY: &str = "report_only";
+
+// hosts of Google Sign-In and reCAPTCHA, as recommended by their docs
+static GOOGLE_SCRIPT_SOURCES: &str = "https://accounts.google.com/gsi/client https://www.google.com/recaptcha/ https://www.gstatic.com/recaptcha/";
+static GOOGLE_FRAME_SOURCES: &str = "https://accounts.google.com/gsi/ https://www.google.com/recaptcha/ https://recaptcha.google.com/recaptcha/";
+static GOOGLE_STYLE_SOURCES: &str = "https://accounts.google.com/gsi/style";
+static GOOGLE_CONNECT_SOURCES: &str = "https://accounts.google.com/gsi/";
+// other captcha widgets load scripts, styles and frames from their hosts
+static HCAPTCHA_SOURCES: &str = "https://hcaptcha.com https://*.hcaptcha.com";
+static TURNSTILE_SOURCES: &str = "https://challenges.cloudflare.com";
+
+tokio::task_local! {
+    static CSP_NONCE: String;
+}
+
+/// Nonce of the `script-src` policy for inline scripts of the page. Empty outside of a request
+pub fn current_csp_nonce() -> String {
+    CSP_NONCE
+        .try_with(|nonce| nonce.clone())
+        .unwrap_or_default()
+}
+
+/// Sets CSP, HSTS, `X-Frame-Options`, `Referrer-Policy` and `Permissions-Policy` on every
+/// response. Headers set by the route are kept
+pub struct SecurityHeaders {
+    headers: Rc<SecurityHeaderValues>,
+}
+
+struct SecurityHeaderValues {
+    csp_header_opt: Option<HeaderName>,
+    // policy without the nonce, it is added per request
+    csp_policy: String,
+    fixed: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: &Config) -> Self {
+        let csp_header_opt = if config.csp_mode == CSP_MODE_ENFORCE {
+            Some(HeaderName::from_static("content-security-policy"))
+        } else if config.csp_mode == CSP_MODE_REPORT_ONLY {
+            Some(HeaderName::from_static(
+                "content-security-policy-report-only",
+            ))
+        } else {
+            None
+        };
+
+        let mut fixed = vec![
+            (
+                HeaderName::from_static("x-content-type-options"),
+                HeaderValue::from_static("nosniff"),
+            ),
+            (
+                HeaderName::from_static("x-frame-options"),
+                header_value("FRAME_OPTIONS", &config.frame_options),
+            ),
+            (
+                HeaderName::from_static("referrer-policy"),
+                header_value("REFERRER_POLICY", &config.referrer_policy),
+            ),
+            (
+                HeaderName::from_static("permissions-policy"),
+                header_value("PERMISSIONS_POLICY", &config.permissions_policy),
+            ),
+        ];
+        // browsers ignore it on plain http
+        let is_https = config.site_protocol == "https" || config.tls.is_some();
+        if is_https && config.hsts_max_age_seconds > 0 {
+            let hsts = format!("max-age={}; includeSubDomains", config.hsts_max_age_seconds);
+            fixed.push((
+                HeaderName::from_static("strict-transport-security"),
+                header_value("HSTS_MAX_AGE_SECONDS", &hsts),
+            ));
+        }
+
+        SecurityHeaders {
+            headers: Rc::new(SecurityHeaderValues {
+                csp_header_opt,
+                csp_policy: csp_policy(config),
+                fixed,
+            }),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SecurityHeaders
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = SecurityHeadersMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SecurityHeadersMiddleware {
+            service: Rc::new(service),
+            headers: self.headers.clone(),
+        }))
+    }
+}
+
+pub struct SecurityHeadersMiddleware<S> {
+    service: Rc<S>,
+    headers: Rc<SecurityHeaderValues>,
+}
+
+impl<S, B> Service<ServiceRequest> for SecurityHeadersMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let headers = self.headers.clone();
+        let nonce = new_nonce();
+
+        Box::pin(CSP_NONCE.scope(nonce.clone(), async move {
+            let mut response = service.call(request).await?;
+
+            let response_headers = response.headers_mut();
+            for (name, value) in &headers.fixed {
+                if !response_headers.contains_key(name) {
+                    response_headers.insert(name.clone(), value.clone());
+                }
+            }
+            if let Some(csp_header) = &headers.csp_header_opt {
+                let policy = headers.csp_policy.replace("{nonce}", &nonce);
+                if let Ok(value) = HeaderValue::from_str(&policy) {
+                    response_headers.insert(csp_header.clone(), value);
+                }
+            }
+            Ok(response)
+        }))
+    }
+}
+
+fn new_nonce() -> String {
+    let mut bytes = [0u8; 16];
+    thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+fn header_value(name: &str, value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).unwrap_or_else(|_| panic!("{} is not a valid header value", name))
+}
+
+/// Inline scripts run only with the nonce, so injected markup can not run any. Inline styles
+/// stay allowed for the `style` attributes of the templates
+fn csp_policy(config: &Config) -> String {
+    let mut img_sources = "'self' data: blob:".to_string();
+    match origin(&config.s3_public_url) {
+        Some(public_origin) => {
+            img_sources.push(' ');
+            img_sources.push_str(&public_origin);
+        }
+        None if !config.s3_public_url.is_empty() => {
+            warn!("S3_PUBLIC_URL has no origin, photos from it are blocked by the CSP")
+        }
+        None => {}
+    }
+    let captcha_provider = config
+        .captcha
+        .as_ref()
+        .map(|captcha| captcha.provider.as_str());
+    let captcha_sources = match captcha_provider {
+        Some("hcaptcha") => format!(" {}", HCAPTCHA_SOURCES),
+        Some("turnstile") => format!(" {}", TURNSTILE_SOURCES),
+        _ => String::new(),
+    };
+
+    let mut directives = vec![
+        "default-src 'self'".to_string(),
+        format!(
+            "script-src 'self' 'nonce-{{nonce}}' {}{}",
+            GOOGLE_SCRIPT_SOURCES, captcha_sources
+        ),
+        format!(
+            "style-src 'self' 'unsafe-inline' {}{}",
+            GOOGLE_STYLE_SOURCES, captcha_sources
+        ),
+        format!("img-src {}", img_sources),
+        "font-src 'self'".to_string(),
+        format!(
+            "connect-src 'self' {}{}",
+            GOOGLE_CONNECT_SOURCES, captcha_sources
+        ),
+        format!("frame-src {}{}", GOOGLE_FRAME_SOURCES, captcha_sources),
+        "object-src 'none'".to_string(),
+        "base-uri 'self'".to_string(),
+        "form-action 'self'".to_string(),
+    ];
+    if config.frame_options == "DENY" {
+        directives.push("frame-ancestors 'none'".to_string());
+    } else {
+        directives.push("frame-ancestors 'self'".to_string());
+    }
+    if !config.csp_report_uri.is_empty() {
+        directives.push(format!("report-uri {}", config.csp_report_uri));
+    }
+    directives.join("; ")
+}
+
+/// `https://cdn.example.com/photos` is `https://cdn.example.com`
+fn origin(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let host = rest.split('/').next().filter(|host| !host.is_empty())?;
+    Some(format!("{}://{}", scheme, host))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        body::to_bytes,
+        http::header::HeaderMap,
+        test::{self, TestRequest},
+        web, App, HttpRequest, HttpResponse,
+    };
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    use crate::{db::DbProvider, web_api::routes::too_many_requests_page};
+
+    use super::*;
+
+    // any page with inline scripts does, this one needs no data
+    async fn page(request: HttpRequest) -> HttpResponse {
+        too_many_requests_page(&request, 60).await
+    }
+
+    async fn framed_page() -> HttpResponse {
+        HttpResponse::Ok()
+            .insert_header(("x-frame-options", "SAMEORIGIN"))
+            .finish()
+    }
+
+    /// Headers and body of every request, all sent to one app
+    async fn get_all(config: Config, paths: &[&str]) -> Vec<(HeaderMap, String)> {
+        let db_con = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        let app = test::init_service(
+            App::new()
+                .wrap(SecurityHeaders::new(&config))
+                .app_data(web::Data::new(DbProvider::new(db_con)))
+                .app_data(web::Data::new(config))
+                .route("/page", web::get().to(page))
+                .route("/framed", web::get().to(framed_page)),
+        )
+        .await;
+
+        let mut responses = vec![];
+        for path in paths {
+            let response =
+                test::call_service(&app, TestRequest::get().uri(path).to_request()).await;
+            let headers = response.headers().clone();
+            let body = to_bytes(response.into_body()).await.unwrap();
+            responses.push((headers, String::from_utf8(body.to_vec()).unwrap()));
+        }
+        responses
+    }
+
+    async fn get(config: Config, path: &str) -> HeaderMap {
+        get_all(config, &[path]).await.remove(0).0
+    }
+
+    fn csp_nonce(headers: &HeaderMap) -> String {
+        let policy = headers
+            .get("content-security-policy")
+            .unwrap()
+            .to_str()
+            .unwrap();
+        let (_, rest) = policy.split_once("'nonce-").unwrap();
+        rest.split('\'').next().unwrap().to_string()
+    }
+
+    #[actix_rt::test]
+    async fn every_page_gets_a_fresh_nonce_matching_its_scripts() {
+        let responses = get_all(Config::for_tests(&[]), &["/page", "/page"]).await;
+
+        let first_nonce = csp_nonce(&responses[0].0);
+        let second_nonce = csp_nonce(&responses[1].0);
+        assert_eq!(first_nonce.len(), 32);
+        assert_ne!(first_nonce, second_nonce);
+        assert!(responses[0]
+            .1
+            .contains(&format!(r#"nonce="{}""#, first_nonce)));
+        assert!(responses[1]
+            .1
+            .contains(&format!(r#"nonce="{}""#, second_nonce)));
+        assert!(!responses[1].1.contains(&first_nonce));
+    }
+
+    #[actix_rt::test]
+    async fn nonce_is_empty_outside_of_a_request() {
+        assert_eq!(current_csp_nonce(), "");
+    }
+
+    #[actix_rt::test]
+    async fn csp_mode_picks_the_header() {
+        let headers = get(Config::for_tests(&[("CSP_MODE", "report_only")]), "/page").await;
+        assert!(headers.get("content-security-policy").is_none());
+        assert!(headers.get("content-security-policy-report-only").is_some());
+
+        let headers = get(Config::for_tests(&[("CSP_MODE", "off")]), "/page").await;
+        assert!(headers.get("content-security-policy").is_none());
+        assert!(headers.get("content-security-policy-report-only").is_none());
+    }
+
+    #[actix_rt::test]
+    async fn hsts_is_sent_only_over_https() {
+        let headers = get(Config::for_tests(&[("SITE_PROTOCOL", "https")]), "/page").await;
+        assert_eq!(
+            headers.get("strict-transport-security").unwrap(),
+            "max-age=31536000; includeSubDomains"
+        );
+
+        let headers = get(Config::for_tests(&[("SITE_PROTOCOL", "http")]), "/page").await;
+        assert!(headers.get("strict-transport-security").is_none());
+
+        let config =
+            Config::for_tests(&[("SITE_PROTOCOL", "https"), ("HSTS_MAX_AGE_SECONDS", "0")]);
+        let headers = get(config, "/page").await;
+        assert!(headers.get("strict-transport-security").is_none());
+    }
+
+    #[actix_rt::test]
+    async fn headers_of_the_route_are_kept() {
+        let headers = get(Config::for_tests(&[("FRAME_OPTIONS", "DENY")]), "/framed").await;
+
+        assert_eq!(headers.get("x-frame-options").unwrap(), "SAMEORIGIN");
+        assert_eq!(headers.get("x-content-type-options").unwrap(), "nosniff");
+    }
+}
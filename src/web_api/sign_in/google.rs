@@ -1,6 +1,8 @@
 use jsonwebtoken_google::{Parser, ParserError};
 use serde::{Deserialize, Serialize};
 
+use crate::web_api::telemetry::{start_span, SpanKind};
+
 use super::OAuthUser;
 
 #[derive(Serialize, Deserialize)]
@@ -13,8 +15,14 @@ pub async fn get_google_user(
     raw_jwt_credentail: &str,
     oauth_google_client_id: &str,
 ) -> Result<OAuthUser, ParserError> {
+    // the parser fetches google certificates on its own, so the traceparent is not passed
+    let mut span = start_span(SpanKind::Client, "verify google id token");
+    span.set_attribute("server.address", "www.googleapis.com");
     let parser = Parser::new(oauth_google_client_id);
-    let claims = parser.parse::<TokenClaims>(raw_jwt_credentail).await?;
+    let claims = parser
+        .parse::<TokenClaims>(raw_jwt_credentail)
+        .await
+        .inspect_err(|_| span.set_error())?;
 
     Ok(OAuthUser {
         email: claims.email,
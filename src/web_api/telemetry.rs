@@ -0,0 +1,232 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use rand::{thread_rng, RngCore};
+
+pub static TRACEPARENT_HEADER: &str = "traceparent";
+
+static IS_TRACING_ENABLED: AtomicBool = AtomicBool::new(false);
+// spans over this, while the exporter is late, are dropped
+static MAX_PENDING_SPANS: usize = 4096;
+static PENDING_SPANS: Mutex<Vec<SpanData>> = Mutex::new(Vec::new());
+static DROPPED_SPANS: AtomicUsize = AtomicUsize::new(0);
+
+tokio::task_local! {
+    static TRACE: Trace;
+    // span whose future is running, see `in_span`
+    static PARENT_SPAN_ID: String;
+}
+
+/// Trace of one request
+struct Trace {
+    trace_id: String,
+    // span of the caller from `traceparent`
+    remote_parent_id: Option<String>,
+}
+
+/// Values of the OTLP `SpanKind`
+#[derive(Clone, Copy, Debug)]
+pub enum SpanKind {
+    Internal = 1,
+    Server = 2,
+    Client = 3,
+}
+
+#[derive(Debug)]
+pub struct SpanData {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    pub kind: SpanKind,
+    pub start_unix_nanos: u128,
+    pub end_unix_nanos: u128,
+    pub attributes: Vec<(&'static str, String)>,
+    pub is_error: bool,
+}
+
+/// Ends when dropped. Does nothing outside of a traced request
+pub struct Span {
+    data: Option<SpanData>,
+}
+
+impl Span {
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        if let Some(data) = self.data.as_mut() {
+            data.name = name.into();
+        }
+    }
+
+    pub fn set_attribute(&mut self, key: &'static str, value: impl ToString) {
+        if let Some(data) = self.data.as_mut() {
+            data.attributes.push((key, value.to_string()));
+        }
+    }
+
+    pub fn set_error(&mut self) {
+        if let Some(data) = self.data.as_mut() {
+            data.is_error = true;
+        }
+    }
+
+    /// W3C `traceparent` of this span, for outbound requests
+    pub fn traceparent(&self) -> Option<String> {
+        self.data
+            .as_ref()
+            .map(|data| format!("00-{}-{}-01", data.trace_id, data.span_id))
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(mut data) = self.data.take() else {
+            return;
+        };
+        data.end_unix_nanos = unix_nanos();
+        let mut pending_spans = PENDING_SPANS.lock().unwrap();
+        if pending_spans.len() < MAX_PENDING_SPANS {
+            pending_spans.push(data);
+        } else {
+            DROPPED_SPANS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Spans are collected only after the exporter is started
+pub fn enable_tracing() {
+    IS_TRACING_ENABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn is_tracing_enabled() -> bool {
+    IS_TRACING_ENABLED.load(Ordering::SeqCst)
+}
+
+/// Runs the request future in a trace, continuing the one of the caller from `traceparent`
+pub async fn in_trace<F: Future>(traceparent: Option<String>, future: F) -> F::Output {
+    if !is_tracing_enabled() {
+        return future.await;
+    }
+    let (trace_id, remote_parent_id) = match traceparent.as_deref().and_then(parse_traceparent) {
+        Some((trace_id, parent_id)) => (trace_id, Some(parent_id)),
+        None => (random_hex(16), None),
+    };
+    let trace = Trace {
+        trace_id,
+        remote_parent_id,
+    };
+    TRACE.scope(trace, future).await
+}
+
+/// Runs the future as the work of the span, spans it starts become children of this one.
+/// Concurrent futures keep their own parents
+pub async fn in_span<F: Future>(span: &Span, future: F) -> F::Output {
+    match span.data.as_ref() {
+        Some(data) => PARENT_SPAN_ID.scope(data.span_id.clone(), future).await,
+        None => future.await,
+    }
+}
+
+/// Child of the span of the running `in_span`, or of the caller. Keep it in a variable
+/// until the work is done: `let _span = start_span(SpanKind::Internal, "...");`
+pub fn start_span(kind: SpanKind, name: impl Into<String>) -> Span {
+    let data = TRACE
+        .try_with(|trace| {
+            let parent_span_id = PARENT_SPAN_ID
+                .try_with(|span_id| span_id.clone())
+                .ok()
+                .or_else(|| trace.remote_parent_id.clone());
+            SpanData {
+                trace_id: trace.trace_id.clone(),
+                span_id: random_hex(8),
+                parent_span_id,
+                name: name.into(),
+                kind,
+                start_unix_nanos: unix_nanos(),
+                end_unix_nanos: 0,
+                attributes: vec![],
+                is_error: false,
+            }
+        })
+        .ok();
+    Span { data }
+}
+
+/// Span of a `DbProvider` method
+pub fn db_span(method: &str) -> Span {
+    let mut span = start_span(SpanKind::Client, format!("db.{}", method));
+    span.set_attribute("db.system", "postgresql");
+    span
+}
+
+/// Server span is named after the route pattern, known once the request is routed
+pub fn describe_server_span<B>(span: &mut Span, response: &ServiceResponse<B>) {
+    let request = response.request();
+    let route = request.match_pattern().unwrap_or("unmatched".to_string());
+    span.set_name(format!("{} {}", request.method(), &route));
+    span.set_attribute("http.request.method", request.method());
+    span.set_attribute("http.route", &route);
+    span.set_attribute("http.response.status_code", response.status().as_u16());
+    if response.status().is_server_error() {
+        span.set_error();
+    }
+}
+
+/// Incoming W3C `traceparent`, checked later by `in_trace`
+pub fn traceparent(request: &ServiceRequest) -> Option<String> {
+    request
+        .headers()
+        .get(TRACEPARENT_HEADER)
+        .and_then(|header| header.to_str().ok())
+        .map(|header| header.to_string())
+}
+
+/// Lets log lines be found from the trace
+pub fn current_trace_id() -> Option<String> {
+    TRACE.try_with(|trace| trace.trace_id.clone()).ok()
+}
+
+pub fn take_pending_spans() -> Vec<SpanData> {
+    std::mem::take(&mut *PENDING_SPANS.lock().unwrap())
+}
+
+/// Spans ended while MAX_PENDING_SPANS were waiting, since the last call
+pub fn take_dropped_span_count() -> usize {
+    DROPPED_SPANS.swap(0, Ordering::Relaxed)
+}
+
+/// `00-{trace id}-{parent span id}-{flags}`, ids are lowercase hex and not all zeros
+fn parse_traceparent(traceparent: &str) -> Option<(String, String)> {
+    let parts: Vec<&str> = traceparent.trim().split('-').collect();
+    let [version, trace_id, parent_id, _flags] = parts.as_slice() else {
+        return None;
+    };
+    let is_id = |id: &str, len: usize| {
+        id.len() == len
+            && id
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+            && id.chars().any(|c| c != '0')
+    };
+    (*version == "00" && is_id(trace_id, 32) && is_id(parent_id, 16))
+        .then(|| (trace_id.to_string(), parent_id.to_string()))
+}
+
+fn random_hex(len_bytes: usize) -> String {
+    let mut bytes = vec![0u8; len_bytes];
+    thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+fn unix_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}